@@ -0,0 +1,143 @@
+//! Opening dump files, which may be compressed or come from standard input.
+use bzip2::bufread::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+use std::{
+    fs::File,
+    io::{self, BufReader, Cursor, Read},
+    path::Path,
+};
+use xz2::bufread::XzDecoder;
+
+/// The path that stands for standard input.
+pub const STDIN: &str = "-";
+
+const MAGIC_LEN: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Bzip2,
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Recognize the compression format from the first bytes of a file.
+    pub fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if magic.starts_with(&[0x1F, 0x8B]) {
+            Compression::Gzip
+        } else if magic.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if magic.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Open a file, or standard input if `path` is `-`, and decompress it
+/// if it is compressed.
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read + Send>> {
+    let path = path.as_ref();
+    if path == Path::new(STDIN) {
+        decompress(io::stdin())
+    } else {
+        decompress(File::open(path)?)
+    }
+}
+
+/// Detect the compression format from the first few bytes of `reader`
+/// and wrap it in the appropriate decoder.
+pub fn decompress<R>(mut reader: R) -> io::Result<Box<dyn Read + Send>>
+where
+    R: Read + Send + 'static,
+{
+    let mut magic = [0u8; MAGIC_LEN];
+    let mut len = 0;
+    // A pipe may deliver fewer bytes per read than we ask for.
+    while len < MAGIC_LEN {
+        match reader.read(&mut magic[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let compression = Compression::detect(&magic[..len]);
+    let reader =
+        BufReader::new(Cursor::new(magic).take(len as u64).chain(reader));
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Bzip2 => Box::new(MultiBzDecoder::new(reader)),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+        Compression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress, Compression};
+    use std::io::{Read, Write};
+
+    const XML: &str = "<mediawiki>\n</mediawiki>\n";
+
+    fn roundtrip(compressed: Vec<u8>) -> String {
+        let mut decompressed = String::new();
+        decompress(std::io::Cursor::new(compressed))
+            .unwrap()
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        decompressed
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(Compression::detect(b"<mediawiki"), Compression::None);
+        assert_eq!(Compression::detect(b""), Compression::None);
+        assert_eq!(Compression::detect(b"BZh91AY"), Compression::Bzip2);
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(roundtrip(XML.into()), XML);
+        assert_eq!(roundtrip(b"<m".to_vec()), "<m");
+    }
+
+    #[test]
+    fn bzip2() {
+        let mut encoder = bzip2::write::BzEncoder::new(
+            Vec::new(),
+            bzip2::Compression::default(),
+        );
+        encoder.write_all(XML.as_bytes()).unwrap();
+        assert_eq!(roundtrip(encoder.finish().unwrap()), XML);
+    }
+
+    #[test]
+    fn gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(XML.as_bytes()).unwrap();
+        assert_eq!(roundtrip(encoder.finish().unwrap()), XML);
+    }
+
+    #[test]
+    fn xz() {
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(XML.as_bytes()).unwrap();
+        assert_eq!(roundtrip(encoder.finish().unwrap()), XML);
+    }
+
+    #[test]
+    fn zstd() {
+        let compressed = zstd::encode_all(XML.as_bytes(), 0).unwrap();
+        assert_eq!(roundtrip(compressed), XML);
+    }
+}
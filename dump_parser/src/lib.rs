@@ -1,4 +1,3 @@
-pub use parse_mediawiki_dump::Error;
 pub use parse_wiki_text::{
     self, Configuration, ConfigurationSource, Node, Parameter, Positioned,
     Warning,
@@ -6,20 +5,52 @@ pub use parse_wiki_text::{
 use std::io::{BufReader, Read};
 
 mod namespaces;
-pub use namespaces::Namespace;
+pub use namespaces::{Case, Namespace, NamespaceInfo, Namespaces};
+mod parser;
+pub use parser::{Error, Page, Parser};
+mod siteinfo;
+pub use siteinfo::SiteInfo;
 
-pub type DumpParser<R> = parse_mediawiki_dump::Parser<BufReader<R>, Namespace>;
+pub mod input;
+pub mod multistream;
+pub mod parallel;
+pub mod site_config;
 
-pub type Page = parse_mediawiki_dump::Page<Namespace>;
+pub type DumpParser<R> = Parser<BufReader<R>>;
 
 pub fn parse<R: Read>(dump_file: R) -> DumpParser<R> {
-    let reader = BufReader::new(dump_file);
-    parse_mediawiki_dump::parse_with_namespace(reader)
+    Parser::new(BufReader::new(dump_file))
+}
+
+/// Prints the warnings from parsing the text of the page to standard error.
+pub fn print_parser_warnings(page: &Page, warnings: &[Warning]) {
+    for warning in warnings {
+        let Warning {
+            start,
+            end,
+            message,
+        } = warning;
+        let range = 0..page.text.len();
+        let message = message.message().trim_end_matches('.');
+        if !(range.contains(&start) && range.contains(&end)) {
+            eprintln!("byte position {} or {} in warning {} is out of range of {:?}, size of [[{}]]",
+                start, end, message, range, &page.title);
+        } else {
+            eprintln!(
+                "{} at bytes {}..{} ({:?}) in [[{}]]",
+                &message,
+                start,
+                end,
+                &page.text[*start..*end],
+                &page.title
+            );
+        }
+    }
 }
 
 // Created using https://github.com/portstrom/fetch_mediawiki_configuration
-pub fn wiktionary_configuration() -> Configuration {
-    Configuration::new(&ConfigurationSource {
+pub const WIKTIONARY_CONFIGURATION_SOURCE: ConfigurationSource<'static> =
+    ConfigurationSource {
         category_namespaces: &["cat", "category"],
         extension_tags: &[
             "categorytree",
@@ -105,5 +136,20 @@ pub fn wiktionary_configuration() -> Configuration {
             "xmpp:",
         ],
         redirect_magic_words: &["REDIRECT"],
-    })
+    };
+
+pub fn wiktionary_configuration() -> Configuration {
+    Configuration::new(&WIKTIONARY_CONFIGURATION_SOURCE)
 }
+
+// The namespace aliases of English Wiktionary, which the siteinfo of a dump
+// does not list.
+pub const WIKTIONARY_NAMESPACE_ALIASES: &[(Namespace, &str)] = &[
+    (Namespace::PROJECT, "WT"),
+    (Namespace::TEMPLATE, "T"),
+    (Namespace::CATEGORY, "CAT"),
+    (Namespace(100), "AP"),
+    (Namespace(110), "WS"),
+    (Namespace(118), "RC"),
+    (Namespace::MODULE, "MOD"),
+];
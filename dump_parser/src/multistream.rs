@@ -0,0 +1,395 @@
+//! Random access to `pages-articles-multistream.xml.bz2`
+//! through `pages-articles-multistream-index.txt[.bz2]`.
+//!
+//! A multistream dump is a concatenation of bzip2 streams: one containing the
+//! `<siteinfo>` header, then streams of (usually) 100 `<page>` elements each,
+//! then one containing the closing `</mediawiki>` tag. Each line of the index
+//! has the form `offset:page_id:title`, where `offset` is the byte position in
+//! the dump at which the stream containing the page starts.
+use bzip2::bufread::BzDecoder;
+use crossbeam_channel::{bounded, Receiver};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{parallel::process_in_order, Page, SiteInfo};
+
+// The streams of pages lack the root element, which the XML parser requires.
+const STREAM_START: &str =
+    r#"<mediawiki xmlns="http://www.mediawiki.org/xml/export-0.10/">"#;
+const STREAM_END: &str = "</mediawiki>";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(crate::Error),
+    Index { line_number: usize, line: String },
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "failed to read multistream dump: {}", e),
+            Error::Parse(e) => write!(f, "error while parsing stream: {}", e),
+            Error::Index { line_number, line } => write!(
+                f,
+                "line {} of multistream index is not offset:id:title: {}",
+                line_number, line
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Index { .. } => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Byte offset of the bzip2 stream containing the page.
+    pub offset: u64,
+    pub id: u64,
+    pub title: String,
+}
+
+impl IndexEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(3, ':');
+        Some(IndexEntry {
+            offset: fields.next()?.parse().ok()?,
+            id: fields.next()?.parse().ok()?,
+            title: fields.next()?.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Index {
+    entries: Vec<IndexEntry>,
+    by_title: HashMap<String, usize>,
+    by_id: HashMap<u64, usize>,
+    stream_offsets: Vec<u64>,
+}
+
+impl Index {
+    /// Read a decompressed index.
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut index = Index::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let entry = match IndexEntry::parse(&line) {
+                Some(entry) => entry,
+                None => {
+                    return Err(Error::Index {
+                        line_number: i + 1,
+                        line,
+                    })
+                }
+            };
+            if index.stream_offsets.last() != Some(&entry.offset) {
+                index.stream_offsets.push(entry.offset);
+            }
+            index
+                .by_title
+                .insert(entry.title.clone(), index.entries.len());
+            index.by_id.insert(entry.id, index.entries.len());
+            index.entries.push(entry);
+        }
+        index.stream_offsets.sort_unstable();
+        index.stream_offsets.dedup();
+        Ok(index)
+    }
+
+    /// Open an index file, decompressing it if it is compressed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::read(BufReader::new(crate::input::open(path)?))
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn get_by_title(&self, title: &str) -> Option<&IndexEntry> {
+        self.by_title.get(title).map(|&i| &self.entries[i])
+    }
+
+    pub fn get_by_id(&self, id: u64) -> Option<&IndexEntry> {
+        self.by_id.get(&id).map(|&i| &self.entries[i])
+    }
+
+    /// Offsets of all streams containing pages, in ascending order.
+    pub fn stream_offsets(&self) -> &[u64] {
+        &self.stream_offsets
+    }
+
+    /// Offsets of the streams that contain the given titles, in ascending
+    /// order. Titles not found in the index are skipped.
+    pub fn stream_offsets_for_titles<I, S>(&self, titles: I) -> Vec<u64>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        stream_offsets(
+            titles
+                .into_iter()
+                .filter_map(|title| self.get_by_title(title.as_ref())),
+        )
+    }
+
+    /// Offsets of the streams that contain the pages with the given IDs, in
+    /// ascending order. IDs not found in the index are skipped.
+    pub fn stream_offsets_for_ids<I>(&self, ids: I) -> Vec<u64>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        stream_offsets(ids.into_iter().filter_map(|id| self.get_by_id(id)))
+    }
+}
+
+fn stream_offsets<'a>(
+    entries: impl Iterator<Item = &'a IndexEntry>,
+) -> Vec<u64> {
+    let mut offsets: Vec<_> = entries.map(|entry| entry.offset).collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+    offsets
+}
+
+/// Decompress and parse the stream that starts at `offset`.
+pub fn read_stream<P: AsRef<Path>>(
+    dump_path: P,
+    offset: u64,
+) -> Result<Vec<Page>, Error> {
+    let mut file = File::open(dump_path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    // `BzDecoder`, unlike `MultiBzDecoder`, stops at the end of the stream.
+    let stream = BzDecoder::new(BufReader::new(file));
+    let xml = STREAM_START
+        .as_bytes()
+        .chain(stream)
+        .chain(STREAM_END.as_bytes());
+    crate::parse(xml)
+        .collect::<Result<_, _>>()
+        .map_err(Error::Parse)
+}
+
+/// Read the `<siteinfo>` from the first stream, which starts at offset 0.
+pub fn read_siteinfo<P: AsRef<Path>>(dump_path: P) -> Result<SiteInfo, Error> {
+    let file = File::open(dump_path)?;
+    let stream = BzDecoder::new(BufReader::new(file));
+    let mut parser = crate::parse(stream.chain(STREAM_END.as_bytes()));
+    parser.siteinfo().cloned().map_err(Error::Parse)
+}
+
+pub struct Multistream {
+    dump_path: PathBuf,
+    index: Index,
+    siteinfo: SiteInfo,
+}
+
+impl Multistream {
+    pub fn open<P, Q>(dump_path: P, index_path: Q) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        Ok(Multistream {
+            dump_path: dump_path.as_ref().into(),
+            index: Index::open(index_path)?,
+            siteinfo: read_siteinfo(&dump_path)?,
+        })
+    }
+
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
+    pub fn siteinfo(&self) -> &SiteInfo {
+        &self.siteinfo
+    }
+
+    pub fn page_by_title(&self, title: &str) -> Result<Option<Page>, Error> {
+        match self.index.get_by_title(title) {
+            Some(entry) => Ok(read_stream(&self.dump_path, entry.offset)?
+                .into_iter()
+                .find(|page| page.title == title)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn page_by_id(&self, id: u64) -> Result<Option<Page>, Error> {
+        match self.index.get_by_id(id) {
+            Some(entry) => self.page_by_title(&entry.title),
+            None => Ok(None),
+        }
+    }
+
+    /// All pages in the dump, in dump order.
+    /// The streams are decompressed and parsed on `threads` threads.
+    pub fn pages(self, threads: usize) -> Pages {
+        let offsets = self.index.stream_offsets.clone();
+        Pages::new(self.dump_path, offsets, None, threads)
+    }
+
+    /// The pages with the given titles, in dump order. Only the streams that
+    /// contain them are decompressed.
+    pub fn pages_by_titles<I, S>(self, titles: I, threads: usize) -> Pages
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let titles: HashSet<String> =
+            titles.into_iter().map(Into::into).collect();
+        let offsets = self.index.stream_offsets_for_titles(&titles);
+        let selection = Selection::Titles(titles);
+        Pages::new(self.dump_path, offsets, Some(selection), threads)
+    }
+
+    /// The pages with the given IDs, in dump order. Only the streams that
+    /// contain them are decompressed.
+    pub fn pages_by_ids<I>(self, ids: I, threads: usize) -> Pages
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        let ids: HashSet<u64> = ids.into_iter().collect();
+        let offsets = self.index.stream_offsets_for_ids(ids.iter().copied());
+        Pages::new(self.dump_path, offsets, Some(Selection::Ids(ids)), threads)
+    }
+}
+
+// The pages to keep from the streams that are read.
+enum Selection {
+    Titles(HashSet<String>),
+    Ids(HashSet<u64>),
+}
+
+impl Selection {
+    fn contains(&self, page: &Page) -> bool {
+        match self {
+            Selection::Titles(titles) => titles.contains(&page.title),
+            Selection::Ids(ids) => page.id.is_some_and(|id| ids.contains(&id)),
+        }
+    }
+}
+
+/// Iterator over the pages in a set of streams, created by
+/// [`Multistream::pages`], [`Multistream::pages_by_titles`] and
+/// [`Multistream::pages_by_ids`].
+pub struct Pages {
+    receiver: Receiver<Result<Vec<Page>, Error>>,
+    current: std::vec::IntoIter<Page>,
+}
+
+// Returned by the stream consumer when the `Pages` iterator has been dropped.
+struct Disconnected;
+
+impl Pages {
+    fn new(
+        dump_path: PathBuf,
+        offsets: Vec<u64>,
+        selection: Option<Selection>,
+        threads: usize,
+    ) -> Self {
+        let (sender, receiver) = bounded(threads.max(1) * 4);
+        let selection = selection.map(Arc::new);
+        std::thread::spawn(move || {
+            let _: Result<(), Disconnected> = process_in_order(
+                offsets.into_iter().map(Ok),
+                threads,
+                |offset| -> Result<_, Error> {
+                    let mut pages = read_stream(&dump_path, offset)?;
+                    if let Some(selection) = &selection {
+                        pages.retain(|page| selection.contains(page));
+                    }
+                    Ok(pages)
+                },
+                |pages| sender.send(pages).map_err(|_| Disconnected),
+            );
+        });
+        Pages {
+            receiver,
+            current: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl Iterator for Pages {
+    type Item = Result<Page, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(page) = self.current.next() {
+                return Some(Ok(page));
+            }
+            match self.receiver.recv() {
+                Ok(Ok(pages)) => self.current = pages.into_iter(),
+                Ok(Err(e)) => return Some(Err(e)),
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Index, IndexEntry};
+
+    #[test]
+    fn read_index() {
+        let index = Index::read(
+            concat!(
+                "597:10:Template:en-noun\n",
+                "597:12:dictionary\n",
+                "2000:25:Wiktionary:Main Page\n",
+                "2000:26:A:B:C\n",
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(index.stream_offsets(), &[597, 2000]);
+        assert_eq!(
+            index.get_by_title("A:B:C"),
+            Some(&IndexEntry {
+                offset: 2000,
+                id: 26,
+                title: "A:B:C".into()
+            })
+        );
+        assert_eq!(
+            index.get_by_id(12).map(|entry| entry.title.as_str()),
+            Some("dictionary")
+        );
+        assert_eq!(
+            index.stream_offsets_for_titles([
+                "Wiktionary:Main Page",
+                "missing",
+                "A:B:C"
+            ]),
+            vec![2000]
+        );
+        assert_eq!(index.stream_offsets_for_ids([26, 99, 10]), vec![597, 2000]);
+    }
+
+    #[test]
+    fn bad_index_line() {
+        assert!(Index::read("597:ten:title\n".as_bytes()).is_err());
+        assert!(Index::read("597\n".as_bytes()).is_err());
+    }
+}
@@ -1,248 +1,343 @@
-pub use num_enum::TryFromPrimitiveError;
-use num_enum::{IntoPrimitive, TryFromPrimitive};
-use parse_mediawiki_dump::NamespaceId;
-use std::{convert::TryFrom, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    str::FromStr,
+};
 
-#[derive(Copy, Clone, Eq, Debug, Hash, PartialEq, TryFromPrimitive, IntoPrimitive)]
-#[repr(i32)]
+/// A namespace number. Which names belong to which number depends on the wiki,
+/// so names are looked up in a [`Namespaces`] table.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Namespace(pub i32);
+
+impl Namespace {
+    pub const MEDIA: Self = Namespace(-2);
+    pub const SPECIAL: Self = Namespace(-1);
+    pub const MAIN: Self = Namespace(0);
+    pub const TALK: Self = Namespace(1);
+    pub const USER: Self = Namespace(2);
+    pub const USER_TALK: Self = Namespace(3);
+    pub const PROJECT: Self = Namespace(4);
+    pub const PROJECT_TALK: Self = Namespace(5);
+    pub const FILE: Self = Namespace(6);
+    pub const FILE_TALK: Self = Namespace(7);
+    pub const MEDIAWIKI: Self = Namespace(8);
+    pub const MEDIAWIKI_TALK: Self = Namespace(9);
+    pub const TEMPLATE: Self = Namespace(10);
+    pub const TEMPLATE_TALK: Self = Namespace(11);
+    pub const HELP: Self = Namespace(12);
+    pub const HELP_TALK: Self = Namespace(13);
+    pub const CATEGORY: Self = Namespace(14);
+    pub const CATEGORY_TALK: Self = Namespace(15);
+    pub const MODULE: Self = Namespace(828);
+    pub const MODULE_TALK: Self = Namespace(829);
+
+    pub fn id(self) -> i32 {
+        self.0
+    }
+
+    pub fn is_talk(self) -> bool {
+        self.0 > 0 && self.0 % 2 == 1
+    }
+}
+
+impl Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<i32> for Namespace {
+    fn from(id: i32) -> Self {
+        Namespace(id)
+    }
+}
+
+impl From<Namespace> for i32 {
+    fn from(Namespace(id): Namespace) -> Self {
+        id
+    }
+}
+
+/// Names that MediaWiki (or Scribunto, for modules) recognizes on every wiki,
+/// whatever the local names are.
 #[rustfmt::skip]
-pub enum Namespace {
-    Media                =   -2,
-    Special              =   -1,
-    Main                 =    0,
-    Talk                 =    1,
-    User                 =    2,
-    UserTalk             =    3,
-    Wiktionary           =    4,
-    WiktionaryTalk       =    5,
-    File                 =    6,
-    FileTalk             =    7,
-    MediaWiki            =    8,
-    MediaWikiTalk        =    9,
-    Template             =   10,
-    TemplateTalk         =   11,
-    Help                 =   12,
-    HelpTalk             =   13,
-    Category             =   14,
-    CategoryTalk         =   15,
-    Thread               =   90,
-    ThreadTalk           =   91,
-    Summary              =   92,
-    SummaryTalk          =   93,
-    Appendix             =  100,
-    AppendixTalk         =  101,
-    Concordance          =  102,
-    ConcordanceTalk      =  103,
-    Index                =  104,
-    IndexTalk            =  105,
-    Rhymes               =  106,
-    RhymesTalk           =  107,
-    Transwiki            =  108,
-    TranswikiTalk        =  109,
-    Thesaurus            =  110,
-    ThesaurusTalk        =  111,
-    Citations            =  114,
-    CitationsTalk        =  115,
-    SignGloss            =  116,
-    SignGlossTalk        =  117,
-    Reconstruction       =  118,
-    ReconstructionTalk   =  119,
-    Module               =  828,
-    ModuleTalk           =  829,
-    Gadget               = 2300,
-    GadgetTalk           = 2301,
-    GadgetDefinition     = 2302,
-    GadgetDefinitionTalk = 2303,
+const CANONICAL_NAMES: &[(Namespace, &str)] = &[
+    (Namespace::MEDIA,          "Media"),
+    (Namespace::SPECIAL,        "Special"),
+    (Namespace::TALK,           "Talk"),
+    (Namespace::USER,           "User"),
+    (Namespace::USER_TALK,      "User talk"),
+    (Namespace::PROJECT,        "Project"),
+    (Namespace::PROJECT_TALK,   "Project talk"),
+    (Namespace::FILE,           "File"),
+    (Namespace::FILE_TALK,      "File talk"),
+    (Namespace::MEDIAWIKI,      "MediaWiki"),
+    (Namespace::MEDIAWIKI_TALK, "MediaWiki talk"),
+    (Namespace::TEMPLATE,       "Template"),
+    (Namespace::TEMPLATE_TALK,  "Template talk"),
+    (Namespace::HELP,           "Help"),
+    (Namespace::HELP_TALK,      "Help talk"),
+    (Namespace::CATEGORY,       "Category"),
+    (Namespace::CATEGORY_TALK,  "Category talk"),
+    (Namespace::MODULE,         "Module"),
+    (Namespace::MODULE_TALK,    "Module talk"),
+];
+
+const BUILTIN_ALIASES: &[(Namespace, &str)] = &[
+    (Namespace::FILE, "Image"),
+    (Namespace::FILE_TALK, "Image talk"),
+];
+
+/// The `case` setting of a wiki or namespace: whether the first letter
+/// of titles is uppercased.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Case {
+    #[default]
+    FirstLetter,
+    CaseSensitive,
 }
 
-impl Namespace {
-    pub const MAX_LEN: usize = 22;
-
-    #[rustfmt::skip]
-    pub fn as_str(self) -> &'static str {
-        match &self {
-            Namespace::Media                => "Media",
-            Namespace::Special              => "Special",
-            Namespace::Main                 => "",
-            Namespace::Talk                 => "Talk",
-            Namespace::User                 => "User",
-            Namespace::UserTalk             => "User talk",
-            Namespace::Wiktionary           => "Wiktionary",
-            Namespace::WiktionaryTalk       => "Wiktionary talk",
-            Namespace::File                 => "File",
-            Namespace::FileTalk             => "File talk",
-            Namespace::MediaWiki            => "MediaWiki",
-            Namespace::MediaWikiTalk        => "MediaWiki talk",
-            Namespace::Template             => "Template",
-            Namespace::TemplateTalk         => "Template talk",
-            Namespace::Help                 => "Help",
-            Namespace::HelpTalk             => "Help talk",
-            Namespace::Category             => "Category",
-            Namespace::CategoryTalk         => "Category talk",
-            Namespace::Thread               => "Thread",
-            Namespace::ThreadTalk           => "Thread talk",
-            Namespace::Summary              => "Summary",
-            Namespace::SummaryTalk          => "Summary talk",
-            Namespace::Appendix             => "Appendix",
-            Namespace::AppendixTalk         => "Appendix talk",
-            Namespace::Concordance          => "Concordance",
-            Namespace::ConcordanceTalk      => "Concordance talk",
-            Namespace::Index                => "Index",
-            Namespace::IndexTalk            => "Index talk",
-            Namespace::Rhymes               => "Rhymes",
-            Namespace::RhymesTalk           => "Rhymes talk",
-            Namespace::Transwiki            => "Transwiki",
-            Namespace::TranswikiTalk        => "Transwiki talk",
-            Namespace::Thesaurus            => "Thesaurus",
-            Namespace::ThesaurusTalk        => "Thesaurus talk",
-            Namespace::Citations            => "Citations",
-            Namespace::CitationsTalk        => "Citations talk",
-            Namespace::SignGloss            => "Sign gloss",
-            Namespace::SignGlossTalk        => "Sign gloss talk",
-            Namespace::Reconstruction       => "Reconstruction",
-            Namespace::ReconstructionTalk   => "Reconstruction talk",
-            Namespace::Module               => "Module",
-            Namespace::ModuleTalk           => "Module talk",
-            Namespace::Gadget               => "Gadget",
-            Namespace::GadgetTalk           => "Gadget talk",
-            Namespace::GadgetDefinition     => "Gadget definition",
-            Namespace::GadgetDefinitionTalk => "Gadget definition talk",
+impl FromStr for Case {
+    type Err = &'static str;
+
+    fn from_str(case: &str) -> Result<Self, Self::Err> {
+        match case {
+            "first-letter" => Ok(Case::FirstLetter),
+            "case-sensitive" => Ok(Case::CaseSensitive),
+            _ => Err("invalid case setting"),
         }
     }
+}
 
-    pub fn normalize_name<'a>(name: &str, buffer: &'a mut [u8]) -> &'a str {
-        let normalized_name = &mut buffer[..name.len()];
-        normalized_name.copy_from_slice(name.as_bytes());
-        normalized_name[0] = normalized_name[0].to_ascii_uppercase();
-        normalized_name[1..].make_ascii_lowercase();
-        for c in normalized_name.iter_mut() {
-            if *c == b'_' {
-                *c = b' ';
-            }
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NamespaceInfo {
+    pub id: Namespace,
+    /// The local name, which is used in titles in the dump.
+    /// Empty for the main namespace.
+    pub name: String,
+    pub canonical_name: Option<String>,
+    pub case: Case,
+    pub aliases: Vec<String>,
+}
+
+impl NamespaceInfo {
+    pub fn new<S: Into<String>>(id: Namespace, name: S, case: Case) -> Self {
+        let canonical_name = CANONICAL_NAMES
+            .iter()
+            .find(|(canonical_id, _)| *canonical_id == id)
+            .map(|(_, name)| name.to_string());
+        let aliases = BUILTIN_ALIASES
+            .iter()
+            .filter(|(alias_id, _)| *alias_id == id)
+            .map(|(_, alias)| alias.to_string())
+            .collect();
+        NamespaceInfo {
+            id,
+            name: name.into(),
+            canonical_name,
+            case,
+            aliases,
         }
-        unsafe { std::str::from_utf8_unchecked(&*normalized_name) }
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str())
+            .chain(self.canonical_name.as_deref())
+            .chain(self.aliases.iter().map(String::as_str))
     }
 }
 
-impl FromStr for Namespace {
-    type Err = &'static str;
+/// The namespaces of a wiki, as listed in the `<siteinfo>` of a dump.
+#[derive(Clone, Debug, Default)]
+pub struct Namespaces {
+    namespaces: BTreeMap<Namespace, NamespaceInfo>,
+    by_name: HashMap<String, Namespace>,
+}
+
+impl Namespaces {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    fn from_str(namespace_name: &str) -> Result<Self, Self::Err> {
-        if namespace_name.len() > Self::MAX_LEN || !namespace_name.is_ascii() {
-            return Err("invalid namespace name");
+    /// The namespaces with canonical names, for dumps that have no siteinfo.
+    pub fn canonical() -> Self {
+        let mut namespaces = Self::new();
+        namespaces.insert(NamespaceInfo::new(
+            Namespace::MAIN,
+            "",
+            Case::default(),
+        ));
+        for &(id, name) in CANONICAL_NAMES {
+            namespaces.insert(NamespaceInfo::new(id, name, Case::default()));
         }
-        let mut namespace_buffer = [0u8; Self::MAX_LEN];
-        let namespace_name =
-            Self::normalize_name(namespace_name, &mut namespace_buffer);
-        #[rustfmt::skip]
-        let namespace = match namespace_name {
-            "Media"                  => Namespace::Media,
-            "Special"                => Namespace::Special,
-            "Main"                   => Namespace::Main,
-            "Talk"                   => Namespace::Talk,
-            "User"                   => Namespace::User,
-            "User talk"              => Namespace::UserTalk,
-            "Wiktionary"             => Namespace::Wiktionary,
-            "Wiktionary talk"        => Namespace::WiktionaryTalk,
-            "File"                   => Namespace::File,
-            "File talk"              => Namespace::FileTalk,
-            "Media wiki"             => Namespace::MediaWiki,
-            "Media wiki talk"        => Namespace::MediaWikiTalk,
-            "Template"               => Namespace::Template,
-            "Template talk"          => Namespace::TemplateTalk,
-            "Help"                   => Namespace::Help,
-            "Help talk"              => Namespace::HelpTalk,
-            "Category"               => Namespace::Category,
-            "Category talk"          => Namespace::CategoryTalk,
-            "Thread"                 => Namespace::Thread,
-            "Thread talk"            => Namespace::ThreadTalk,
-            "Summary"                => Namespace::Summary,
-            "Summary talk"           => Namespace::SummaryTalk,
-            "Appendix"               => Namespace::Appendix,
-            "Appendix talk"          => Namespace::AppendixTalk,
-            "Concordance"            => Namespace::Concordance,
-            "Concordance talk"       => Namespace::ConcordanceTalk,
-            "Index"                  => Namespace::Index,
-            "Index talk"             => Namespace::IndexTalk,
-            "Rhymes"                 => Namespace::Rhymes,
-            "Rhymes talk"            => Namespace::RhymesTalk,
-            "Transwiki"              => Namespace::Transwiki,
-            "Transwiki talk"         => Namespace::TranswikiTalk,
-            "Thesaurus"              => Namespace::Thesaurus,
-            "Thesaurus talk"         => Namespace::ThesaurusTalk,
-            "Citations"              => Namespace::Citations,
-            "Citations talk"         => Namespace::CitationsTalk,
-            "Sign gloss"             => Namespace::SignGloss,
-            "Sign gloss talk"        => Namespace::SignGlossTalk,
-            "Reconstruction"         => Namespace::Reconstruction,
-            "Reconstruction talk"    => Namespace::ReconstructionTalk,
-            "Module"                 => Namespace::Module,
-            "Module talk"            => Namespace::ModuleTalk,
-            "Gadget"                 => Namespace::Gadget,
-            "Gadget talk"            => Namespace::GadgetTalk,
-            "Gadget definition"      => Namespace::GadgetDefinition,
-            "Gadget definition talk" => Namespace::GadgetDefinitionTalk,
-            _                        => return Err("invalid namespace name"),
-        };
-        Ok(namespace)
+        namespaces
+    }
+
+    /// Add a namespace, replacing any namespace with the same number.
+    pub fn insert(&mut self, info: NamespaceInfo) {
+        if let Some(old) = self.namespaces.remove(&info.id) {
+            for name in old.names() {
+                self.by_name.remove(&normalize_name(name));
+            }
+        }
+        for name in info.names() {
+            self.by_name.insert(normalize_name(name), info.id);
+        }
+        self.namespaces.insert(info.id, info);
     }
-}
 
-impl TryFrom<NamespaceId> for Namespace {
-    type Error = <Namespace as TryFrom<i32>>::Error;
-    fn try_from(NamespaceId(id): NamespaceId) -> Result<Self, Self::Error> {
-        Self::try_from(id)
+    /// Add an alias to an existing namespace, unless it is already one of its
+    /// names. Returns `false` if there is no namespace with that number.
+    pub fn add_alias(&mut self, id: Namespace, alias: &str) -> bool {
+        match self.namespaces.get_mut(&id) {
+            Some(info) => {
+                let name = normalize_name(alias);
+                if self.by_name.get(&name) != Some(&id) {
+                    info.aliases.push(alias.to_string());
+                    self.by_name.insert(name, id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, id: Namespace) -> Option<&NamespaceInfo> {
+        self.namespaces.get(&id)
+    }
+
+    pub fn name(&self, id: Namespace) -> Option<&str> {
+        self.get(id).map(|info| info.name.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NamespaceInfo> {
+        self.namespaces.values()
+    }
+
+    /// Look up a local name, canonical name or alias. Namespace names are
+    /// case-insensitive, and underscores are equivalent to spaces.
+    pub fn lookup(&self, name: &str) -> Option<Namespace> {
+        self.by_name.get(&normalize_name(name)).copied()
     }
+
+    /// Interpret a namespace given on the command line: a number, `main`
+    /// or a name.
+    pub fn parse_arg(&self, arg: &str) -> Option<Namespace> {
+        let arg = arg.trim();
+        if let Ok(id) = arg.parse() {
+            Some(Namespace(id)).filter(|id| self.get(*id).is_some())
+        } else if arg.eq_ignore_ascii_case("main") {
+            Some(Namespace::MAIN)
+        } else {
+            self.lookup(arg).filter(|&id| id != Namespace::MAIN)
+        }
+    }
+
+    /// Split a title into its namespace and the rest of the title, if the
+    /// part before the first colon is a namespace name.
+    pub fn split_title<'a>(&self, title: &'a str) -> (Namespace, &'a str) {
+        if let Some(colon) = title.find(':') {
+            match self.lookup(&title[..colon]) {
+                Some(namespace) if namespace != Namespace::MAIN => {
+                    return (namespace, &title[colon + 1..]);
+                }
+                _ => {}
+            }
+        }
+        (Namespace::MAIN, title)
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim().replace('_', " ").to_lowercase()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Namespace, TryFromPrimitiveError};
-    use parse_mediawiki_dump::NamespaceId;
-    use std::convert::TryFrom;
-    use std::str::FromStr;
+    use super::{Case, Namespace, NamespaceInfo, Namespaces};
+
+    fn wiktionary_namespaces() -> Namespaces {
+        let mut namespaces = Namespaces::new();
+        for &(id, name) in &[
+            (0, ""),
+            (1, "Talk"),
+            (4, "Wiktionary"),
+            (5, "Wiktionary talk"),
+            (6, "File"),
+            (10, "Template"),
+            (118, "Reconstruction"),
+        ] {
+            namespaces.insert(NamespaceInfo::new(
+                Namespace(id),
+                name,
+                Case::CaseSensitive,
+            ));
+        }
+        namespaces.add_alias(Namespace(4), "WT");
+        namespaces
+    }
 
     #[test]
-    fn namespace_from_str() {
-        assert_eq!(
-            Namespace::from_str("wiktionary talk"),
-            Ok(Namespace::WiktionaryTalk)
-        );
-        assert_eq!(
-            Namespace::from_str("Wiktionary talk"),
-            Ok(Namespace::WiktionaryTalk)
-        );
-        assert_eq!(
-            Namespace::from_str("Wiktionary_talk"),
-            Ok(Namespace::WiktionaryTalk)
-        );
+    fn add_alias() {
+        let mut namespaces = wiktionary_namespaces();
+        assert!(namespaces.add_alias(Namespace::FILE, "image"));
+        assert!(!namespaces.add_alias(Namespace::MODULE, "MOD"));
+        assert_eq!(namespaces.lookup("MOD"), None);
+        assert_eq!(namespaces.get(Namespace::FILE).unwrap().aliases, ["Image"]);
     }
 
     #[test]
-    fn namespace_as_str() {
-        assert_eq!(Namespace::Talk.as_str(), "Talk");
-        assert_eq!(Namespace::WiktionaryTalk.as_str(), "Wiktionary talk");
+    fn lookup() {
+        let namespaces = wiktionary_namespaces();
+        assert_eq!(namespaces.lookup("wiktionary talk"), Some(Namespace(5)));
+        assert_eq!(namespaces.lookup("Wiktionary_talk"), Some(Namespace(5)));
+        assert_eq!(namespaces.lookup("Project"), Some(Namespace(4)));
+        assert_eq!(namespaces.lookup("wt"), Some(Namespace(4)));
+        assert_eq!(namespaces.lookup("Image"), Some(Namespace::FILE));
+        assert_eq!(namespaces.lookup("Module"), None);
     }
 
     #[test]
-    fn namespace_numbers() {
-        assert_eq!(Namespace::try_from(828), Ok(Namespace::Module));
-        assert_eq!(Namespace::try_from(829), Ok(Namespace::ModuleTalk));
+    fn parse_arg() {
+        let namespaces = wiktionary_namespaces();
+        assert_eq!(namespaces.parse_arg("main"), Some(Namespace::MAIN));
+        assert_eq!(namespaces.parse_arg("0"), Some(Namespace::MAIN));
+        assert_eq!(namespaces.parse_arg("118"), Some(Namespace(118)));
         assert_eq!(
-            Namespace::try_from(1000),
-            Err(TryFromPrimitiveError { number: 1000i32 })
+            namespaces.parse_arg("reconstruction"),
+            Some(Namespace(118))
         );
-
-        assert_eq!(i32::from(Namespace::Module), 828);
-        assert_eq!(i32::from(Namespace::ModuleTalk), 829);
+        assert_eq!(namespaces.parse_arg("119"), None);
+        assert_eq!(namespaces.parse_arg(""), None);
     }
 
     #[test]
-    fn namespace_try_from_namespace_id() {
-        assert_eq!(Namespace::try_from(NamespaceId(0)), Ok(Namespace::Main));
-        assert_eq!(Namespace::try_from(NamespaceId(10)), Ok(Namespace::Template));
+    fn split_title() {
+        let namespaces = wiktionary_namespaces();
+        assert_eq!(
+            namespaces.split_title("Template:en-noun"),
+            (Namespace::TEMPLATE, "en-noun")
+        );
+        assert_eq!(
+            namespaces.split_title("WT:Beer parlour"),
+            (Namespace(4), "Beer parlour")
+        );
+        assert_eq!(
+            namespaces
+                .split_title("Reconstruction:Proto-Indo-European/méh₂tēr"),
+            (Namespace(118), "Proto-Indo-European/méh₂tēr")
+        );
+        assert_eq!(
+            namespaces.split_title("w:Dictionary"),
+            (Namespace::MAIN, "w:Dictionary")
+        );
+        assert_eq!(namespaces.split_title(":a"), (Namespace::MAIN, ":a"));
     }
 
     #[test]
-    fn normalize_namespace_name() {}
+    fn case() {
+        assert_eq!("first-letter".parse(), Ok(Case::FirstLetter));
+        assert_eq!("case-sensitive".parse(), Ok(Case::CaseSensitive));
+        assert!("Case-sensitive".parse::<Case>().is_err());
+    }
 }
@@ -0,0 +1,143 @@
+use crossbeam_channel::bounded;
+use std::collections::BTreeMap;
+
+/// Process pages on `threads` worker threads while another thread reads
+/// (and decompresses) the dump.
+///
+/// `process` runs on the worker threads. `consume` runs on the calling thread
+/// and receives the results in the order in which the pages were yielded by
+/// `pages`, so output stays deterministic. Iteration stops at the first error
+/// from `pages` or from `consume`.
+pub fn process_in_order<I, P, RE, T, E, F, C>(
+    pages: I,
+    threads: usize,
+    process: F,
+    mut consume: C,
+) -> Result<(), E>
+where
+    I: Iterator<Item = Result<P, RE>> + Send,
+    P: Send,
+    RE: Send,
+    T: Send,
+    E: From<RE>,
+    F: Fn(P) -> T + Sync,
+    C: FnMut(T) -> Result<(), E>,
+{
+    let threads = threads.max(1);
+    std::thread::scope(|scope| {
+        // Bounded so that the reader cannot get arbitrarily far ahead of the
+        // workers, and the workers ahead of `consume`.
+        let (page_sender, page_receiver) = bounded(threads * 4);
+        let (result_sender, result_receiver) = bounded(threads * 4);
+
+        let reader = scope.spawn(move || {
+            for (i, page) in pages.enumerate() {
+                if page_sender.send((i, page?)).is_err() {
+                    // `consume` failed and the workers have exited.
+                    break;
+                }
+            }
+            Ok(())
+        });
+
+        for _ in 0..threads {
+            let page_receiver = page_receiver.clone();
+            let result_sender = result_sender.clone();
+            let process = &process;
+            scope.spawn(move || {
+                for (i, page) in page_receiver {
+                    if result_sender.send((i, process(page))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(page_receiver);
+        drop(result_sender);
+
+        // Results arrive in whatever order the workers finish them.
+        // Hold on to them until all earlier results have been consumed.
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (i, result) in result_receiver {
+            pending.insert(i, result);
+            while let Some(result) = pending.remove(&next) {
+                consume(result)?;
+                next += 1;
+            }
+        }
+
+        reader.join().expect("dump reader thread panicked")?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::process_in_order;
+
+    #[test]
+    fn results_are_in_page_order() {
+        for threads in 1..=8 {
+            let mut results = Vec::new();
+            let pages = (0..1000u64).map(Ok::<_, ()>);
+            let result: Result<(), ()> = process_in_order(
+                pages,
+                threads,
+                |n| {
+                    // Make later pages finish earlier.
+                    std::thread::sleep(std::time::Duration::from_micros(
+                        (1000 - n) % 7,
+                    ));
+                    n * 2
+                },
+                |n| {
+                    results.push(n);
+                    Ok(())
+                },
+            );
+            assert_eq!(result, Ok(()));
+            assert_eq!(
+                results,
+                (0..1000u64).map(|n| n * 2).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn stops_at_reader_error() {
+        let mut results = Vec::new();
+        let pages = (0..100u32).map(|n| if n == 50 { Err(n) } else { Ok(n) });
+        let result: Result<(), u32> = process_in_order(
+            pages,
+            4,
+            |n| n,
+            |n| {
+                results.push(n);
+                Ok(())
+            },
+        );
+        assert_eq!(result, Err(50));
+        assert_eq!(results, (0..50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stops_at_consumer_error() {
+        let mut results = Vec::new();
+        let pages = (0..10_000u32).map(Ok::<_, u32>);
+        let result: Result<(), u32> = process_in_order(
+            pages,
+            4,
+            |n| n,
+            |n| {
+                if n == 20 {
+                    return Err(n);
+                }
+                results.push(n);
+                Ok(())
+            },
+        );
+        assert_eq!(result, Err(20));
+        assert_eq!(results, (0..20).collect::<Vec<_>>());
+    }
+}
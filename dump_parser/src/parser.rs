@@ -0,0 +1,776 @@
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use std::{fmt::Display, io::BufRead, mem};
+
+use crate::{Case, Namespace, NamespaceInfo, Namespaces, SiteInfo};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Page {
+    pub title: String,
+    pub namespace: Namespace,
+    /// The page ID, from `<id>`.
+    pub id: Option<u64>,
+    /// The target of a redirect, from `<redirect title="..." />`.
+    pub redirect: Option<String>,
+    /// The ID of the revision.
+    pub revision_id: Option<u64>,
+    /// The time of the revision, like `2020-07-28T12:00:00Z`.
+    pub timestamp: Option<String>,
+    /// `None` if the dump leaves out the contributor or it was deleted.
+    pub contributor: Option<Contributor>,
+    /// The SHA-1 hash of the text in base 36.
+    pub sha1: Option<String>,
+    pub text: String,
+    pub format: Option<String>,
+    pub model: Option<String>,
+}
+
+impl Page {
+    /// A page with the given text and none of the optional fields, as in
+    /// tests.
+    pub fn new(
+        title: impl Into<String>,
+        namespace: Namespace,
+        text: impl Into<String>,
+    ) -> Self {
+        Page {
+            title: title.into(),
+            namespace,
+            id: None,
+            redirect: None,
+            revision_id: None,
+            timestamp: None,
+            contributor: None,
+            sha1: None,
+            text: text.into(),
+            format: None,
+            model: None,
+        }
+    }
+
+    pub fn is_redirect(&self) -> bool {
+        self.redirect.is_some()
+    }
+}
+
+/// The user who made a revision.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Contributor {
+    User {
+        name: String,
+        id: Option<u64>,
+    },
+    /// An unregistered user, given by IP address.
+    Ip(String),
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Xml(quick_xml::Error),
+    Format {
+        position: usize,
+        message: &'static str,
+    },
+}
+
+impl From<quick_xml::Error> for Error {
+    fn from(e: quick_xml::Error) -> Self {
+        Error::Xml(e)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Xml(e) => write!(f, "invalid XML: {}", e),
+            Error::Format { position, message } => {
+                write!(f, "{} at byte {}", message, position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Xml(e) => Some(e),
+            Error::Format { .. } => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Start,
+    // The `<page>` start tag was consumed while looking for `<siteinfo>`.
+    PageStarted,
+    Pages,
+    // Between the revisions of a page, when iterating over all revisions.
+    Revisions,
+    Done,
+}
+
+/// Iterator over the pages of a dump.
+///
+/// The `<siteinfo>` header is read before the first page
+/// or when [`Parser::siteinfo`] is first called.
+pub struct Parser<R: BufRead> {
+    reader: Reader<R>,
+    buffer: Vec<u8>,
+    text_buffer: Vec<u8>,
+    siteinfo: SiteInfo,
+    state: State,
+    all_revisions: bool,
+    // The page whose revisions are being read.
+    page: PageInfo,
+}
+
+impl<R: BufRead> Parser<R> {
+    pub fn new(reader: R) -> Self {
+        Parser {
+            reader: Reader::from_reader(reader),
+            buffer: Vec::new(),
+            text_buffer: Vec::new(),
+            siteinfo: SiteInfo::default(),
+            state: State::Start,
+            all_revisions: false,
+            page: PageInfo::default(),
+        }
+    }
+
+    /// Iterate over every revision of each page, as in `pages-meta-history`
+    /// dumps, instead of only the last one. Each revision is given as a
+    /// separate [`Page`] with the title, namespace, ID and redirect of its
+    /// page.
+    pub fn all_revisions(mut self, all_revisions: bool) -> Self {
+        self.all_revisions = all_revisions;
+        self
+    }
+
+    /// The dump's siteinfo, or the default if it has none.
+    pub fn siteinfo(&mut self) -> Result<&SiteInfo, Error> {
+        self.siteinfo_mut().map(|siteinfo| &*siteinfo)
+    }
+
+    /// The dump's siteinfo, to add what the dump does not list, such as
+    /// namespace aliases.
+    pub fn siteinfo_mut(&mut self) -> Result<&mut SiteInfo, Error> {
+        if self.state == State::Start {
+            if let Err(e) = self.read_header() {
+                self.state = State::Done;
+                return Err(e);
+            }
+        }
+        Ok(&mut self.siteinfo)
+    }
+
+    fn format_error(&self, message: &'static str) -> Error {
+        Error::Format {
+            position: self.reader.buffer_position(),
+            message,
+        }
+    }
+
+    fn read_text(&mut self, end: &[u8]) -> Result<String, Error> {
+        self.text_buffer.clear();
+        Ok(self.reader.read_text(end, &mut self.text_buffer)?)
+    }
+
+    fn read_number(
+        &mut self,
+        end: &[u8],
+        message: &'static str,
+    ) -> Result<u64, Error> {
+        self.read_text(end)?
+            .parse()
+            .map_err(|_| self.format_error(message))
+    }
+
+    fn skip(&mut self, end: &[u8]) -> Result<(), Error> {
+        self.text_buffer.clear();
+        Ok(self.reader.read_to_end(end, &mut self.text_buffer)?)
+    }
+
+    fn read_header(&mut self) -> Result<(), Error> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == b"mediawiki" => break,
+                Event::Start(_) | Event::Empty(_) | Event::Eof => {
+                    return Err(self.format_error("expected <mediawiki>"));
+                }
+                _ => {}
+            }
+        }
+        loop {
+            self.buffer.clear();
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) => match e.name() {
+                    b"siteinfo" => {
+                        self.read_siteinfo()?;
+                        self.state = State::Pages;
+                        return Ok(());
+                    }
+                    b"page" => {
+                        self.state = State::PageStarted;
+                        return Ok(());
+                    }
+                    name => {
+                        let name = name.to_vec();
+                        self.skip(&name)?;
+                    }
+                },
+                Event::End(_) | Event::Eof => {
+                    self.state = State::Done;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_siteinfo(&mut self) -> Result<(), Error> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) => match e.name() {
+                    b"sitename" => {
+                        self.siteinfo.sitename = self.read_text(b"sitename")?
+                    }
+                    b"dbname" => {
+                        self.siteinfo.dbname = self.read_text(b"dbname")?
+                    }
+                    b"base" => self.siteinfo.base = self.read_text(b"base")?,
+                    b"generator" => {
+                        self.siteinfo.generator =
+                            self.read_text(b"generator")?
+                    }
+                    b"case" => {
+                        self.siteinfo.case = self
+                            .read_text(b"case")?
+                            .parse()
+                            .map_err(|e| self.format_error(e))?
+                    }
+                    b"namespaces" => {
+                        self.siteinfo.namespaces = self.read_namespaces()?
+                    }
+                    name => {
+                        let name = name.to_vec();
+                        self.skip(&name)?;
+                    }
+                },
+                Event::End(_) => return Ok(()),
+                Event::Eof => {
+                    return Err(self.format_error("unexpected end of file"))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_namespaces(&mut self) -> Result<Namespaces, Error> {
+        let mut namespaces = Namespaces::new();
+        loop {
+            self.buffer.clear();
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == b"namespace" => {
+                    let (id, case) = namespace_attributes(&self.reader, &e)?;
+                    let name = self.read_text(b"namespace")?;
+                    namespaces.insert(NamespaceInfo::new(id, name, case));
+                }
+                Event::Empty(e) if e.name() == b"namespace" => {
+                    let (id, case) = namespace_attributes(&self.reader, &e)?;
+                    namespaces.insert(NamespaceInfo::new(id, "", case));
+                }
+                Event::End(_) => return Ok(namespaces),
+                Event::Eof => {
+                    return Err(self.format_error("unexpected end of file"))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_page(&mut self) -> Result<Page, Error> {
+        let mut page = PageInfo::default();
+        let mut revision = Revision::default();
+        // Only the last revision is kept.
+        while let Some(next) = self.read_until_revision(&mut page)? {
+            revision = next;
+        }
+        page.page(revision)
+            .map_err(|message| self.format_error(message))
+    }
+
+    // Reads the elements of `<page>` up to and including the next
+    // `<revision>`, or returns `None` at `</page>`.
+    fn read_until_revision(
+        &mut self,
+        page: &mut PageInfo,
+    ) -> Result<Option<Revision>, Error> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) => match e.name() {
+                    b"title" => page.title = Some(self.read_text(b"title")?),
+                    b"ns" => {
+                        page.namespace = Some(Namespace(
+                            self.read_text(b"ns")?.parse().map_err(|_| {
+                                self.format_error("invalid <ns>")
+                            })?,
+                        ))
+                    }
+                    b"id" => {
+                        page.id =
+                            Some(self.read_number(b"id", "invalid page <id>")?)
+                    }
+                    b"redirect" => {
+                        page.redirect = redirect_title(&self.reader, &e)?;
+                        self.skip(b"redirect")?;
+                    }
+                    b"revision" => return self.read_revision().map(Some),
+                    name => {
+                        let name = name.to_vec();
+                        self.skip(&name)?;
+                    }
+                },
+                Event::Empty(e) if e.name() == b"redirect" => {
+                    page.redirect = redirect_title(&self.reader, &e)?
+                }
+                Event::End(_) => return Ok(None),
+                Event::Eof => {
+                    return Err(self.format_error("unexpected end of file"))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_revision(&mut self) -> Result<Revision, Error> {
+        let mut revision = Revision::default();
+        loop {
+            self.buffer.clear();
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) => match e.name() {
+                    b"id" => {
+                        revision.id = Some(
+                            self.read_number(b"id", "invalid revision <id>")?,
+                        )
+                    }
+                    b"timestamp" => {
+                        revision.timestamp = Some(self.read_text(b"timestamp")?)
+                    }
+                    b"contributor" => {
+                        revision.contributor = self.read_contributor()?
+                    }
+                    b"sha1" => revision.sha1 = Some(self.read_text(b"sha1")?),
+                    b"text" => revision.text = Some(self.read_text(b"text")?),
+                    b"format" => {
+                        revision.format = Some(self.read_text(b"format")?)
+                    }
+                    b"model" => {
+                        revision.model = Some(self.read_text(b"model")?)
+                    }
+                    name => {
+                        let name = name.to_vec();
+                        self.skip(&name)?;
+                    }
+                },
+                // Empty or deleted text.
+                Event::Empty(e) if e.name() == b"text" => {
+                    revision.text = Some(String::new())
+                }
+                Event::End(_) => return Ok(revision),
+                Event::Eof => {
+                    return Err(self.format_error("unexpected end of file"))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // A deleted contributor has no username or IP address.
+    fn read_contributor(&mut self) -> Result<Option<Contributor>, Error> {
+        let mut username = None;
+        let mut id = None;
+        let mut ip = None;
+        loop {
+            self.buffer.clear();
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) => match e.name() {
+                    b"username" => {
+                        username = Some(self.read_text(b"username")?)
+                    }
+                    b"id" => {
+                        id =
+                            Some(self.read_number(
+                                b"id",
+                                "invalid contributor <id>",
+                            )?)
+                    }
+                    b"ip" => ip = Some(self.read_text(b"ip")?),
+                    name => {
+                        let name = name.to_vec();
+                        self.skip(&name)?;
+                    }
+                },
+                Event::End(_) => break,
+                Event::Eof => {
+                    return Err(self.format_error("unexpected end of file"))
+                }
+                _ => {}
+            }
+        }
+        Ok(match (username, ip) {
+            (Some(name), _) => Some(Contributor::User { name, id }),
+            (None, Some(ip)) => Some(Contributor::Ip(ip)),
+            (None, None) => None,
+        })
+    }
+
+    fn next_page(&mut self) -> Result<Option<Page>, Error> {
+        if self.state == State::Start {
+            self.read_header()?;
+        }
+        loop {
+            match self.state {
+                State::Start | State::Done => return Ok(None),
+                State::PageStarted if self.all_revisions => {
+                    self.page = PageInfo::default();
+                    self.state = State::Revisions;
+                    continue;
+                }
+                State::PageStarted => {
+                    self.state = State::Pages;
+                    return self.read_page().map(Some);
+                }
+                State::Revisions => {
+                    let mut page = mem::take(&mut self.page);
+                    match self.read_until_revision(&mut page)? {
+                        Some(revision) => {
+                            let result = page.page(revision);
+                            page.revisions += 1;
+                            self.page = page;
+                            return result
+                                .map(Some)
+                                .map_err(|message| self.format_error(message));
+                        }
+                        None if page.revisions == 0 => {
+                            return Err(
+                                self.format_error("page without <revision>")
+                            );
+                        }
+                        None => {
+                            self.state = State::Pages;
+                            continue;
+                        }
+                    }
+                }
+                State::Pages => {}
+            }
+            self.buffer.clear();
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) => match e.name() {
+                    b"page" => self.state = State::PageStarted,
+                    name => {
+                        let name = name.to_vec();
+                        self.skip(&name)?;
+                    }
+                },
+                Event::End(_) | Event::Eof => self.state = State::Done,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Parser<R> {
+    type Item = Result<Page, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_page() {
+            Ok(page) => page.map(Ok),
+            Err(e) => {
+                self.state = State::Done;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// The elements of `<page>` before its revisions.
+#[derive(Default)]
+struct PageInfo {
+    title: Option<String>,
+    namespace: Option<Namespace>,
+    id: Option<u64>,
+    redirect: Option<String>,
+    // The number of revisions read so far.
+    revisions: usize,
+}
+
+impl PageInfo {
+    fn page(&self, revision: Revision) -> Result<Page, &'static str> {
+        match (&self.title, self.namespace, revision.text) {
+            (Some(title), Some(namespace), Some(text)) => Ok(Page {
+                title: title.clone(),
+                namespace,
+                id: self.id,
+                redirect: self.redirect.clone(),
+                revision_id: revision.id,
+                timestamp: revision.timestamp,
+                contributor: revision.contributor,
+                sha1: revision.sha1,
+                text,
+                format: revision.format,
+                model: revision.model,
+            }),
+            (None, _, _) => Err("page without <title>"),
+            (_, None, _) => Err("page without <ns>"),
+            (_, _, None) => Err("page without <text>"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Revision {
+    id: Option<u64>,
+    timestamp: Option<String>,
+    contributor: Option<Contributor>,
+    sha1: Option<String>,
+    text: Option<String>,
+    format: Option<String>,
+    model: Option<String>,
+}
+
+fn redirect_title<R: BufRead>(
+    reader: &Reader<R>,
+    start: &BytesStart,
+) -> Result<Option<String>, Error> {
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        if attribute.key == b"title" {
+            return Ok(Some(attribute.unescape_and_decode_value(reader)?));
+        }
+    }
+    Ok(None)
+}
+
+fn namespace_attributes<R: BufRead>(
+    reader: &Reader<R>,
+    start: &BytesStart,
+) -> Result<(Namespace, Case), Error> {
+    let position = reader.buffer_position();
+    let format_error = |message| Error::Format { position, message };
+    let mut id = None;
+    let mut case = Case::default();
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        match attribute.key {
+            b"key" => {
+                id = Some(
+                    attribute
+                        .unescape_and_decode_value(reader)?
+                        .parse()
+                        .map_err(|_| format_error("invalid namespace key"))?,
+                )
+            }
+            b"case" => {
+                case = attribute
+                    .unescape_and_decode_value(reader)?
+                    .parse()
+                    .map_err(format_error)?
+            }
+            _ => {}
+        }
+    }
+    match id {
+        Some(id) => Ok((Namespace(id), case)),
+        None => Err(format_error("namespace without key")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Contributor, Page, Parser};
+    use crate::{Case, Namespace};
+
+    const DUMP: &str = r#"<mediawiki xmlns="http://www.mediawiki.org/xml/export-0.10/" version="0.10" xml:lang="en">
+  <siteinfo>
+    <sitename>Wiktionary</sitename>
+    <dbname>enwiktionary</dbname>
+    <base>https://en.wiktionary.org/wiki/Wiktionary:Main_Page</base>
+    <generator>MediaWiki 1.36.0-wmf.2</generator>
+    <case>case-sensitive</case>
+    <namespaces>
+      <namespace key="-2" case="case-sensitive">Media</namespace>
+      <namespace key="0" case="case-sensitive" />
+      <namespace key="4" case="case-sensitive">Wiktionary</namespace>
+      <namespace key="10" case="case-sensitive">Template</namespace>
+      <namespace key="2300" case="case-sensitive">Gadget</namespace>
+    </namespaces>
+  </siteinfo>
+  <page>
+    <title>Template:l</title>
+    <ns>10</ns>
+    <id>2541</id>
+    <revision>
+      <id>60047070</id>
+      <timestamp>2020-07-28T12:00:00Z</timestamp>
+      <contributor>
+        <username>Example</username>
+        <id>1</id>
+      </contributor>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="35" xml:space="preserve">{{#invoke:links/templates|l_term_t}}&lt;noinclude&gt;</text>
+      <sha1>abc</sha1>
+    </revision>
+  </page>
+  <page>
+    <title>empty</title>
+    <ns>7777</ns>
+    <id>1</id>
+    <revision>
+      <text bytes="0" />
+    </revision>
+  </page>
+  <page>
+    <title>Template:link</title>
+    <ns>10</ns>
+    <id>2542</id>
+    <redirect title="Template:l" />
+    <revision>
+      <id>60047071</id>
+      <text bytes="21" xml:space="preserve">#REDIRECT [[Template:l]]</text>
+    </revision>
+  </page>
+</mediawiki>
+"#;
+
+    #[test]
+    fn siteinfo() {
+        let mut parser = Parser::new(DUMP.as_bytes());
+        let siteinfo = parser.siteinfo().unwrap();
+        assert_eq!(siteinfo.dbname, "enwiktionary");
+        assert_eq!(siteinfo.case, Case::CaseSensitive);
+        assert_eq!(siteinfo.namespaces.lookup("gadget"), Some(Namespace(2300)));
+        assert_eq!(siteinfo.namespaces.lookup("Project"), Some(Namespace(4)));
+        assert_eq!(siteinfo.namespaces.name(Namespace::MAIN), Some(""));
+        assert_eq!(siteinfo.namespaces.iter().count(), 5);
+    }
+
+    #[test]
+    fn pages() {
+        let pages: Vec<_> = Parser::new(DUMP.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            pages,
+            vec![
+                Page {
+                    title: "Template:l".into(),
+                    namespace: Namespace::TEMPLATE,
+                    id: Some(2541),
+                    redirect: None,
+                    revision_id: Some(60047070),
+                    timestamp: Some("2020-07-28T12:00:00Z".into()),
+                    contributor: Some(Contributor::User {
+                        name: "Example".into(),
+                        id: Some(1),
+                    }),
+                    sha1: Some("abc".into()),
+                    text: "{{#invoke:links/templates|l_term_t}}<noinclude>"
+                        .into(),
+                    format: Some("text/x-wiki".into()),
+                    model: Some("wikitext".into()),
+                },
+                // Namespaces missing from the siteinfo are not an error.
+                Page {
+                    title: "empty".into(),
+                    namespace: Namespace(7777),
+                    id: Some(1),
+                    redirect: None,
+                    revision_id: None,
+                    timestamp: None,
+                    contributor: None,
+                    sha1: None,
+                    text: String::new(),
+                    format: None,
+                    model: None,
+                },
+                Page {
+                    title: "Template:link".into(),
+                    namespace: Namespace::TEMPLATE,
+                    id: Some(2542),
+                    redirect: Some("Template:l".into()),
+                    revision_id: Some(60047071),
+                    timestamp: None,
+                    contributor: None,
+                    sha1: None,
+                    text: "#REDIRECT [[Template:l]]".into(),
+                    format: None,
+                    model: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn all_revisions() {
+        let history = "<mediawiki><page><title>a</title><ns>0</ns><id>5</id>\
+             <revision><id>1</id><timestamp>2004-01-01T00:00:00Z</timestamp>\
+             <contributor><ip>127.0.0.1</ip></contributor>\
+             <text>b</text></revision>\
+             <revision><id>2</id><timestamp>2005-01-01T00:00:00Z</timestamp>\
+             <contributor deleted=\"deleted\" />\
+             <text>c</text></revision></page></mediawiki>";
+        let revisions: Vec<_> = Parser::new(history.as_bytes())
+            .all_revisions(true)
+            .map(|page| {
+                let page = page.unwrap();
+                (page.id, page.revision_id, page.contributor, page.text)
+            })
+            .collect();
+        assert_eq!(
+            revisions,
+            vec![
+                (
+                    Some(5),
+                    Some(1),
+                    Some(Contributor::Ip("127.0.0.1".into())),
+                    "b".into()
+                ),
+                (Some(5), Some(2), None, "c".into()),
+            ]
+        );
+        let last = Parser::new(history.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(last.revision_id, Some(2));
+    }
+
+    #[test]
+    fn no_siteinfo() {
+        let mut parser = Parser::new(
+            "<mediawiki><page><title>a</title><ns>0</ns>\
+             <revision><text>b</text></revision></page></mediawiki>"
+                .as_bytes(),
+        );
+        assert_eq!(
+            parser.siteinfo().unwrap().namespaces.lookup("Template"),
+            Some(Namespace::TEMPLATE)
+        );
+        assert_eq!(parser.next().unwrap().unwrap().text, "b");
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn missing_title() {
+        let mut parser = Parser::new(
+            "<mediawiki><page><ns>0</ns></page></mediawiki>".as_bytes(),
+        );
+        assert!(parser.next().unwrap().is_err());
+        assert!(parser.next().is_none());
+    }
+}
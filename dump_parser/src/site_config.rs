@@ -0,0 +1,500 @@
+//! Parser configuration built from the output of the MediaWiki API's
+//! [siteinfo query](https://www.mediawiki.org/wiki/API:Siteinfo), requested
+//! with at least `siprop=general|namespaces|namespacealiases|extensiontags|magicwords|protocols`.
+//! Both `formatversion=1` and `formatversion=2` are accepted.
+use serde::Deserialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    io::Read,
+};
+
+use crate::{Configuration, ConfigurationSource, Namespace, Namespaces};
+
+#[derive(Debug)]
+pub enum Error {
+    Json(serde_json::Error),
+    LinkTrail(String),
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "invalid siteinfo JSON: {}", e),
+            Error::LinkTrail(link_trail) => write!(
+                f,
+                "cannot convert link trail regex {} to a set of characters",
+                link_trail
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Json(e) => Some(e),
+            Error::LinkTrail(_) => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SiteInfoResponse {
+    query: SiteInfoQuery,
+}
+
+#[derive(Deserialize)]
+struct SiteInfoQuery {
+    general: General,
+    namespaces: HashMap<String, NamespaceEntry>,
+    #[serde(rename = "namespacealiases")]
+    namespace_aliases: Vec<NamespaceAlias>,
+    #[serde(rename = "extensiontags")]
+    extension_tags: Vec<String>,
+    #[serde(rename = "magicwords")]
+    magic_words: Vec<MagicWord>,
+    protocols: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct General {
+    #[serde(rename = "linktrail")]
+    link_trail: String,
+}
+
+#[derive(Deserialize)]
+struct NamespaceEntry {
+    id: i32,
+    #[serde(rename = "*", alias = "name")]
+    name: String,
+    canonical: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NamespaceAlias {
+    id: i32,
+    #[serde(rename = "*", alias = "alias")]
+    alias: String,
+}
+
+#[derive(Deserialize)]
+struct MagicWord {
+    name: String,
+    aliases: Vec<String>,
+}
+
+/// An owned version of [`ConfigurationSource`], with the namespace aliases,
+/// which are not in the `<siteinfo>` of a dump.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SiteConfig {
+    pub category_namespaces: Vec<String>,
+    pub extension_tags: Vec<String>,
+    pub file_namespaces: Vec<String>,
+    pub link_trail: String,
+    pub magic_words: Vec<String>,
+    pub protocols: Vec<String>,
+    pub redirect_magic_words: Vec<String>,
+    pub namespace_aliases: Vec<(Namespace, String)>,
+}
+
+impl SiteConfig {
+    /// The built-in configuration used by [`crate::wiktionary_configuration`],
+    /// with [`crate::WIKTIONARY_NAMESPACE_ALIASES`].
+    pub fn wiktionary() -> Self {
+        SiteConfig {
+            namespace_aliases: crate::WIKTIONARY_NAMESPACE_ALIASES
+                .iter()
+                .map(|&(id, alias)| (id, alias.to_string()))
+                .collect(),
+            ..Self::from(&crate::WIKTIONARY_CONFIGURATION_SOURCE)
+        }
+    }
+
+    pub fn from_siteinfo_json<R: Read>(reader: R) -> Result<Self, Error> {
+        let SiteInfoResponse { query } = serde_json::from_reader(reader)?;
+        let namespace_names = |id: Namespace| -> Vec<String> {
+            let names = query
+                .namespaces
+                .values()
+                .filter(|namespace| namespace.id == id.0)
+                .flat_map(|namespace| {
+                    std::iter::once(&namespace.name)
+                        .chain(namespace.canonical.as_ref())
+                });
+            let aliases = query
+                .namespace_aliases
+                .iter()
+                .filter(|alias| alias.id == id.0)
+                .map(|alias| &alias.alias);
+            sorted(names.chain(aliases).map(|name| name.to_lowercase()))
+        };
+        let mut magic_words = Vec::new();
+        let mut redirect_magic_words = Vec::new();
+        for magic_word in &query.magic_words {
+            if magic_word.name == "redirect" {
+                redirect_magic_words.extend(
+                    magic_word
+                        .aliases
+                        .iter()
+                        .map(|alias| alias.trim_start_matches('#').to_string()),
+                );
+            } else {
+                // Behavior switches like __NOTOC__, not variables like
+                // {{PAGENAME}} or parser functions like {{#if:}}.
+                magic_words.extend(magic_word.aliases.iter().filter_map(
+                    |alias| {
+                        if alias.len() > 4
+                            && alias.starts_with("__")
+                            && alias.ends_with("__")
+                        {
+                            Some(alias[2..alias.len() - 2].to_string())
+                        } else {
+                            None
+                        }
+                    },
+                ));
+            }
+        }
+        Ok(SiteConfig {
+            category_namespaces: namespace_names(Namespace::CATEGORY),
+            extension_tags: sorted(query.extension_tags.iter().map(|tag| {
+                tag.trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_lowercase()
+            })),
+            file_namespaces: namespace_names(Namespace::FILE),
+            link_trail: parse_link_trail(&query.general.link_trail)?,
+            magic_words: sorted(magic_words),
+            protocols: sorted(query.protocols.iter().map(|p| p.to_lowercase())),
+            redirect_magic_words: sorted(redirect_magic_words),
+            namespace_aliases: query
+                .namespace_aliases
+                .iter()
+                .map(|alias| (Namespace(alias.id), alias.alias.clone()))
+                .collect(),
+        })
+    }
+
+    /// Add the namespace aliases to namespaces read from the siteinfo of a
+    /// dump. Aliases of namespaces that are not in it are ignored.
+    pub fn add_namespace_aliases(&self, namespaces: &mut Namespaces) {
+        for (id, alias) in &self.namespace_aliases {
+            namespaces.add_alias(*id, alias);
+        }
+    }
+
+    pub fn to_configuration(&self) -> Configuration {
+        Configuration::new(&ConfigurationSource {
+            category_namespaces: &strs(&self.category_namespaces),
+            extension_tags: &strs(&self.extension_tags),
+            file_namespaces: &strs(&self.file_namespaces),
+            link_trail: &self.link_trail,
+            magic_words: &strs(&self.magic_words),
+            protocols: &strs(&self.protocols),
+            redirect_magic_words: &strs(&self.redirect_magic_words),
+        })
+    }
+
+    fn fields(&self) -> Vec<(&'static str, Vec<&str>)> {
+        let link_trail = &self.link_trail;
+        vec![
+            ("category_namespaces", strs(&self.category_namespaces)),
+            ("extension_tags", strs(&self.extension_tags)),
+            ("file_namespaces", strs(&self.file_namespaces)),
+            (
+                "link_trail",
+                link_trail
+                    .char_indices()
+                    .map(|(i, c)| &link_trail[i..i + c.len_utf8()])
+                    .collect(),
+            ),
+            ("magic_words", strs(&self.magic_words)),
+            ("protocols", strs(&self.protocols)),
+            ("redirect_magic_words", strs(&self.redirect_magic_words)),
+        ]
+    }
+
+    /// The fields in which `other` differs from `self`. The link trail is
+    /// compared character by character.
+    pub fn diff<'a>(&'a self, other: &'a SiteConfig) -> Vec<Difference<'a>> {
+        self.fields()
+            .into_iter()
+            .zip(other.fields())
+            .filter_map(|((field, old), (_, new))| {
+                let old: BTreeSet<_> = old.into_iter().collect();
+                let new: BTreeSet<_> = new.into_iter().collect();
+                let removed: Vec<_> = old.difference(&new).copied().collect();
+                let added: Vec<_> = new.difference(&old).copied().collect();
+                if removed.is_empty() && added.is_empty() {
+                    None
+                } else {
+                    Some(Difference {
+                        field,
+                        removed,
+                        added,
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+impl<'a> From<&ConfigurationSource<'a>> for SiteConfig {
+    fn from(source: &ConfigurationSource<'a>) -> Self {
+        fn strings(strs: &[&str]) -> Vec<String> {
+            strs.iter().map(|s| s.to_string()).collect()
+        }
+        SiteConfig {
+            category_namespaces: strings(source.category_namespaces),
+            extension_tags: strings(source.extension_tags),
+            file_namespaces: strings(source.file_namespaces),
+            link_trail: source.link_trail.to_string(),
+            magic_words: strings(source.magic_words),
+            protocols: strings(source.protocols),
+            redirect_magic_words: strings(source.redirect_magic_words),
+            namespace_aliases: Vec::new(),
+        }
+    }
+}
+
+/// Values of a field that are only in the first configuration (`removed`)
+/// or only in the second (`added`).
+#[derive(Debug, PartialEq, Eq)]
+pub struct Difference<'a> {
+    pub field: &'static str,
+    pub removed: Vec<&'a str>,
+    pub added: Vec<&'a str>,
+}
+
+impl<'a> Display for Difference<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}:", self.field)?;
+        for value in &self.removed {
+            writeln!(f, "- {}", value)?;
+        }
+        for value in &self.added {
+            writeln!(f, "+ {}", value)?;
+        }
+        Ok(())
+    }
+}
+
+fn strs(strings: &[String]) -> Vec<&str> {
+    strings.iter().map(String::as_str).collect()
+}
+
+fn sorted<I: IntoIterator<Item = String>>(strings: I) -> Vec<String> {
+    strings
+        .into_iter()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Convert a link trail regex, such as `/^([a-z]+)(.*)$/sD`, to the set of
+/// characters that it allows, such as `abcdefghijklmnopqrstuvwxyz`.
+fn parse_link_trail(regex: &str) -> Result<String, Error> {
+    let error = || Error::LinkTrail(regex.to_string());
+    if regex.starts_with("/^()(.*)$/") {
+        return Ok(String::new());
+    }
+    let class = regex
+        .strip_prefix("/^([")
+        .and_then(|rest| rest.find("]+)(.*)$/").map(|end| &rest[..end]))
+        .ok_or_else(error)?;
+    let mut chars = Vec::new();
+    let mut iter = class.chars();
+    while let Some(c) = iter.next() {
+        let c = if c == '\\' {
+            iter.next().ok_or_else(error)?
+        } else {
+            c
+        };
+        chars.push(c);
+    }
+    let mut link_trail = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        // A range, unless the hyphen is the last character in the class.
+        if i + 2 < chars.len() && chars[i + 1] == '-' {
+            let (start, end) = (chars[i], chars[i + 2]);
+            if start > end {
+                return Err(error());
+            }
+            link_trail.extend(start..=end);
+            i += 3;
+        } else {
+            link_trail.push(chars[i]);
+            i += 1;
+        }
+    }
+    Ok(link_trail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_link_trail, Difference, SiteConfig};
+    use crate::Namespace;
+
+    const SITEINFO: &str = r##"{
+        "batchcomplete": "",
+        "query": {
+            "general": {
+                "mainpage": "Wiktionary:Main Page",
+                "linktrail": "/^([a-z]+)(.*)$/sD"
+            },
+            "namespaces": {
+                "0": {"id": 0, "case": "case-sensitive", "content": "", "*": ""},
+                "6": {"id": 6, "case": "case-sensitive", "canonical": "File", "*": "File"},
+                "14": {"id": 14, "case": "case-sensitive", "canonical": "Category", "*": "Category"}
+            },
+            "namespacealiases": [
+                {"id": 6, "*": "Image"},
+                {"id": 14, "*": "CAT"}
+            ],
+            "extensiontags": ["<pre>", "<nowiki>", "<ref>"],
+            "magicwords": [
+                {"name": "redirect", "aliases": ["#REDIRECT"], "case-sensitive": ""},
+                {"name": "notoc", "aliases": ["__NOTOC__"]},
+                {"name": "pagename", "aliases": ["PAGENAME"], "case-sensitive": ""},
+                {"name": "if", "aliases": ["if"]}
+            ],
+            "protocols": ["http://", "https://", "//"]
+        }
+    }"##;
+
+    const SITEINFO_FORMAT_VERSION_2: &str = r##"{
+        "batchcomplete": true,
+        "query": {
+            "general": {"linktrail": "/^()(.*)$/sD"},
+            "namespaces": {
+                "6": {"id": 6, "case": "first-letter", "name": "Fichier", "canonical": "File"},
+                "14": {"id": 14, "case": "first-letter", "name": "Catégorie", "canonical": "Category"}
+            },
+            "namespacealiases": [{"id": 6, "alias": "Image"}],
+            "extensiontags": [],
+            "magicwords": [
+                {"name": "redirect", "aliases": ["#REDIRECTION", "#REDIRECT"], "case-sensitive": false}
+            ],
+            "protocols": []
+        }
+    }"##;
+
+    #[test]
+    fn from_siteinfo_json() {
+        let config =
+            SiteConfig::from_siteinfo_json(SITEINFO.as_bytes()).unwrap();
+        assert_eq!(
+            config,
+            SiteConfig {
+                category_namespaces: vec!["cat".into(), "category".into()],
+                extension_tags: vec![
+                    "nowiki".into(),
+                    "pre".into(),
+                    "ref".into()
+                ],
+                file_namespaces: vec!["file".into(), "image".into()],
+                link_trail: "abcdefghijklmnopqrstuvwxyz".into(),
+                magic_words: vec!["NOTOC".into()],
+                protocols: vec![
+                    "//".into(),
+                    "http://".into(),
+                    "https://".into()
+                ],
+                redirect_magic_words: vec!["REDIRECT".into()],
+                namespace_aliases: vec![
+                    (Namespace::FILE, "Image".into()),
+                    (Namespace::CATEGORY, "CAT".into()),
+                ],
+            }
+        );
+
+        let config = SiteConfig::from_siteinfo_json(
+            SITEINFO_FORMAT_VERSION_2.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(config.category_namespaces, vec!["category", "catégorie"]);
+        assert_eq!(config.file_namespaces, vec!["fichier", "file", "image"]);
+        assert_eq!(config.link_trail, "");
+        assert_eq!(
+            config.redirect_magic_words,
+            vec!["REDIRECT", "REDIRECTION"]
+        );
+    }
+
+    #[test]
+    fn namespace_aliases() {
+        let mut parser = crate::parse(
+            r#"<mediawiki>
+  <siteinfo>
+    <namespaces>
+      <namespace key="0" case="case-sensitive" />
+      <namespace key="6" case="case-sensitive">File</namespace>
+      <namespace key="14" case="case-sensitive">Category</namespace>
+    </namespaces>
+  </siteinfo>
+</mediawiki>"#
+                .as_bytes(),
+        );
+        let mut namespaces = parser.siteinfo().unwrap().namespaces.clone();
+        assert_eq!(namespaces.lookup("CAT"), None);
+        let config =
+            SiteConfig::from_siteinfo_json(SITEINFO.as_bytes()).unwrap();
+        config.add_namespace_aliases(&mut namespaces);
+        assert_eq!(namespaces.lookup("cat"), Some(Namespace::CATEGORY));
+        assert_eq!(
+            namespaces.split_title("CAT:English nouns"),
+            (Namespace::CATEGORY, "English nouns")
+        );
+        assert_eq!(namespaces.get(Namespace::FILE).unwrap().aliases, ["Image"]);
+
+        let mut namespaces = parser.siteinfo().unwrap().namespaces.clone();
+        SiteConfig::wiktionary().add_namespace_aliases(&mut namespaces);
+        assert_eq!(namespaces.lookup("CAT"), Some(Namespace::CATEGORY));
+        // Template is not in the siteinfo.
+        assert_eq!(namespaces.lookup("T"), None);
+    }
+
+    #[test]
+    fn link_trail() {
+        assert_eq!(
+            parse_link_trail("/^([a-zäöüß]+)(.*)$/sDu").unwrap(),
+            "abcdefghijklmnopqrstuvwxyzäöüß"
+        );
+        assert_eq!(parse_link_trail(r"/^([a-c\-]+)(.*)$/sD").unwrap(), "abc-");
+        assert_eq!(parse_link_trail("/^([a-]+)(.*)$/sD").unwrap(), "a-");
+        assert!(parse_link_trail("/^((?:[a-z]|ab)+)(.*)$/sD").is_err());
+    }
+
+    #[test]
+    fn diff() {
+        let builtin = SiteConfig::wiktionary();
+        let mut new = builtin.clone();
+        new.extension_tags.retain(|tag| tag != "thread");
+        new.extension_tags.push("phonos".into());
+        new.link_trail.push('é');
+        assert_eq!(builtin.diff(&builtin), vec![]);
+        assert_eq!(
+            builtin.diff(&new),
+            vec![
+                Difference {
+                    field: "extension_tags",
+                    removed: vec!["thread"],
+                    added: vec!["phonos"],
+                },
+                Difference {
+                    field: "link_trail",
+                    removed: vec![],
+                    added: vec!["é"],
+                },
+            ]
+        );
+    }
+}
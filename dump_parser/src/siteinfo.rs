@@ -0,0 +1,27 @@
+use crate::namespaces::{Case, Namespaces};
+
+/// The `<siteinfo>` element at the start of a dump.
+#[derive(Clone, Debug)]
+pub struct SiteInfo {
+    pub sitename: String,
+    pub dbname: String,
+    pub base: String,
+    pub generator: String,
+    pub case: Case,
+    pub namespaces: Namespaces,
+}
+
+impl Default for SiteInfo {
+    /// Used for dumps without `<siteinfo>`, such as the page streams
+    /// of a multistream dump.
+    fn default() -> Self {
+        SiteInfo {
+            sitename: String::new(),
+            dbname: String::new(),
+            base: String::new(),
+            generator: String::new(),
+            case: Case::default(),
+            namespaces: Namespaces::canonical(),
+        }
+    }
+}
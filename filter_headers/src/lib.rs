@@ -1,14 +1,13 @@
 use dump_parser::{
-    wiktionary_configuration as create_configuration, DumpParser,
-    Node::{self, *},
-    Namespace, Page, Positioned, Warning,
+    parallel::process_in_order, print_parser_warnings, Configuration,
+    Namespace, Node, Page, Positioned,
 };
+use parse_wiki_text_ext::visit::{walk, Context, Visit, Visitor};
 use serde::{Serialize, Serializer};
-use std::{
-    collections::{HashMap, HashSet},
-    convert::TryInto,
-    io::Read,
-};
+use std::collections::{HashMap, HashSet};
+
+mod parts_of_speech;
+pub use parts_of_speech::is_part_of_speech;
 
 #[derive(Debug)]
 pub struct HeaderFilterer {
@@ -49,6 +48,19 @@ impl Serialize for HeaderFilterer {
     }
 }
 
+fn add_page(
+    header_to_titles: &mut HashMap<String, HashSet<String>>,
+    title: String,
+    headers: HashSet<String>,
+) {
+    for header in headers {
+        header_to_titles
+            .entry(header)
+            .or_insert_with(HashSet::new)
+            .insert(title.clone());
+    }
+}
+
 impl HeaderFilterer {
     pub fn new(
         top_level_headers: Vec<String>,
@@ -61,140 +73,73 @@ impl HeaderFilterer {
         }
     }
 
-    pub fn parse<R: Read>(
+    pub fn parse<I, E>(
         &mut self,
-        parser: DumpParser<R>,
+        pages: I,
         page_limit: usize,
         namespaces: Vec<Namespace>,
+        configuration: &Configuration,
+        threads: usize,
         verbose: bool,
-    ) {
+    ) -> Result<(), E>
+    where
+        I: Iterator<Item = Result<Page, E>> + Send,
+        E: Send,
+    {
         let namespaces: HashSet<Namespace> = namespaces.into_iter().collect();
-        let parser = parser
-            .map(|result| {
-                result.unwrap_or_else(|e| {
-                    panic!("Error while parsing dump: {}", e);
-                })
-            })
-            .filter(|page| {
-                namespaces.contains(&page.namespace.try_into().unwrap())
+        let pages = pages
+            .filter(|result| match result {
+                Ok(page) => namespaces.contains(&page.namespace),
+                Err(_) => true,
             })
             .take(page_limit);
-        let configuration = create_configuration();
-        for page in parser {
-            let parser_output = configuration.parse(&page.text);
-            if verbose {
-                for warning in parser_output.warnings {
-                    let Warning {
-                        start,
-                        end,
-                        message,
-                    } = warning;
-                    let range = 0..page.text.len();
-                    let message = message.message().trim_end_matches('.');
-                    if !(range.contains(&start) && range.contains(&end)) {
-                        eprintln!("byte position {} or {} in warning {} is out of range of {:?}, size of [[{}]]",
-                            start, end, message, range, &page.title);
-                    } else {
-                        eprintln!(
-                            "{} at bytes {}..{} ({:?}) in [[{}]]",
-                            &message,
-                            start,
-                            end,
-                            &page.text[start..end],
-                            &page.title
-                        );
-                    }
+        let mut header_to_titles = std::mem::take(&mut self.header_to_titles);
+        let filterer = &*self;
+        process_in_order(
+            pages,
+            threads,
+            |page| {
+                let parser_output = configuration.parse(&page.text);
+                if verbose {
+                    print_parser_warnings(&page, &parser_output.warnings);
                 }
-            }
+                let headers =
+                    filterer.page_headers(&page, &parser_output.nodes);
+                (page.title, headers)
+            },
+            |(title, headers)| {
+                add_page(&mut header_to_titles, title, headers);
+                Ok(())
+            },
+        )?;
+        self.header_to_titles = header_to_titles;
+        Ok(())
+    }
 
-            self.process_nodes(&page, &parser_output.nodes);
-        }
+    /// The headers in a parsed page that are not in the lists of headers.
+    pub fn page_headers(&self, page: &Page, nodes: &[Node]) -> HashSet<String> {
+        let mut visitor = PageHeaders {
+            filterer: self,
+            headers: HashSet::new(),
+        };
+        walk(&page.text, nodes, &mut visitor);
+        visitor.headers
     }
 
-    fn process_nodes(&mut self, page: &Page, nodes: &[Node]) {
-        for node in nodes {
-            match node {
-                DefinitionList { items, .. } => {
-                    for item in items {
-                        self.process_nodes(&page, &item.nodes);
-                    }
-                }
-                Heading { nodes, level, .. } => {
-                    self.process_header(&page, &nodes, *level);
-                }
-                Preformatted { nodes, .. } | Tag { nodes, .. } => {
-                    self.process_nodes(&page, &nodes);
-                }
-                Image { text, .. } | Link { text, .. } => {
-                    self.process_nodes(&page, &text);
-                }
-                OrderedList { items, .. } | UnorderedList { items, .. } => {
-                    for item in items {
-                        self.process_nodes(&page, &item.nodes);
-                    }
-                }
-                Parameter { name, default, .. } => {
-                    match default {
-                        Some(nodes) => self.process_nodes(&page, &nodes),
-                        None => {}
-                    }
-                    self.process_nodes(&page, &name);
-                }
-                Table {
-                    attributes,
-                    captions,
-                    rows,
-                    ..
-                } => {
-                    self.process_nodes(&page, &attributes);
-                    for caption in captions {
-                        if let Some(attributes) = &caption.attributes {
-                            self.process_nodes(&page, attributes)
-                        }
-                        self.process_nodes(&page, &caption.content);
-                    }
-                    for row in rows {
-                        self.process_nodes(&page, &row.attributes);
-                        for cell in &row.cells {
-                            if let Some(attributes) = &cell.attributes {
-                                self.process_nodes(&page, attributes);
-                            }
-                            self.process_nodes(&page, &cell.content);
-                        }
-                    }
-                }
-                Template {
-                    name, parameters, ..
-                } => {
-                    self.process_nodes(&page, &name);
-                    for parameter in parameters {
-                        if let Some(name) = &parameter.name {
-                            self.process_nodes(&page, name);
-                        }
-                        self.process_nodes(&page, &parameter.value);
-                    }
-                }
-                Bold { .. }
-                | BoldItalic { .. }
-                | Category { .. }
-                | CharacterEntity { .. }
-                | Comment { .. }
-                | EndTag { .. }
-                | ExternalLink { .. }
-                | HorizontalDivider { .. }
-                | Italic { .. }
-                | MagicWord { .. }
-                | ParagraphBreak { .. }
-                | Redirect { .. }
-                | StartTag { .. }
-                | Text { .. } => {}
-            }
-        }
+    /// Record the headers found by `page_headers`.
+    pub fn add_page(&mut self, title: String, headers: HashSet<String>) {
+        add_page(&mut self.header_to_titles, title, headers);
     }
 
-    fn process_header(&mut self, page: &Page, nodes: &[Node], level: u8) {
+    fn process_header(
+        &self,
+        wikitext: &str,
+        nodes: &[Node],
+        level: u8,
+        headers: &mut HashSet<String>,
+    ) {
         let text = nodes
-            .get_text_from(&page.text)
+            .get_text_from(wikitext)
             .trim_matches(|c: char| c == ' ' || c == '\t');
         if !match level {
             2 => &self.top_level_headers,
@@ -202,11 +147,31 @@ impl HeaderFilterer {
         }
         .contains(text)
         {
-            let titles = self
-                .header_to_titles
-                .entry(text.into())
-                .or_insert_with(HashSet::new);
-            titles.insert(page.title.to_string());
+            headers.insert(text.into());
         }
     }
 }
+
+// Collects the headers of a page that the filterer does not know.
+struct PageHeaders<'b> {
+    filterer: &'b HeaderFilterer,
+    headers: HashSet<String>,
+}
+
+impl<'a> Visitor<'a> for PageHeaders<'_> {
+    fn visit_heading(
+        &mut self,
+        _node: &'a Node<'a>,
+        level: u8,
+        nodes: &'a [Node<'a>],
+        context: &Context<'a>,
+    ) -> Visit {
+        self.filterer.process_header(
+            context.wikitext(),
+            nodes,
+            level,
+            &mut self.headers,
+        );
+        Visit::Continue
+    }
+}
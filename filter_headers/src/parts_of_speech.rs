@@ -0,0 +1,56 @@
+//! The headers of part-of-speech sections, following
+//! [Wiktionary:Entry layout](https://en.wiktionary.org/wiki/Wiktionary:Entry_layout#Part_of_speech).
+
+static PARTS_OF_SPEECH: &[&str] = &[
+    "Abbreviation",
+    "Adjective",
+    "Adverb",
+    "Affix",
+    "Ambiposition",
+    "Article",
+    "Character",
+    "Circumfix",
+    "Circumposition",
+    "Classifier",
+    "Combining form",
+    "Conjunction",
+    "Contraction",
+    "Counter",
+    "Determiner",
+    "Diacritical mark",
+    "Han character",
+    "Hanja",
+    "Hanzi",
+    "Idiom",
+    "Infix",
+    "Initialism",
+    "Interfix",
+    "Interjection",
+    "Kanji",
+    "Letter",
+    "Noun",
+    "Number",
+    "Numeral",
+    "Participle",
+    "Particle",
+    "Phrase",
+    "Postposition",
+    "Prefix",
+    "Preposition",
+    "Prepositional phrase",
+    "Pronoun",
+    "Proper noun",
+    "Proverb",
+    "Punctuation mark",
+    "Romanization",
+    "Suffix",
+    "Syllable",
+    "Symbol",
+    "Verb",
+];
+
+/// Whether a header, without the equals signs, is the header of a
+/// part-of-speech section like `===Noun===`.
+pub fn is_part_of_speech(header: &str) -> bool {
+    PARTS_OF_SPEECH.binary_search(&header).is_ok()
+}
@@ -1,14 +1,12 @@
 use dump_parser::{
-    wiktionary_configuration as create_configuration, DumpParser, Namespace,
-    Node::{self, *},
-    Page, Positioned, Warning,
+    parallel::process_in_order, print_parser_warnings, Configuration,
+    Namespace, Node, Page, Positioned,
 };
+use parse_wiki_text_ext::visit::{walk, Context, Visit, Visitor};
 use serde::{ser::Serializer, Serialize};
 use std::{
     collections::{HashMap, HashSet},
-    convert::TryInto,
     default::Default,
-    io::Read,
     ops::{Index, IndexMut},
 };
 
@@ -80,141 +78,65 @@ impl HeaderStats {
         Default::default()
     }
 
-    pub fn parse<R: Read>(
+    pub fn parse<I, E>(
         &mut self,
-        parser: DumpParser<R>,
+        pages: I,
         page_limit: usize,
         namespaces: Vec<Namespace>,
+        configuration: &Configuration,
+        threads: usize,
         verbose: bool,
-    ) {
+    ) -> Result<(), E>
+    where
+        I: Iterator<Item = Result<Page, E>> + Send,
+        E: Send,
+    {
         let namespaces: HashSet<Namespace> = namespaces.into_iter().collect();
-        let parser = parser
-            .map(|result| {
-                result.unwrap_or_else(|e| {
-                    panic!("Error while parsing dump: {}", e);
-                })
-            })
-            .filter(|page| {
-                namespaces.contains(&page.namespace.try_into().unwrap())
+        let pages = pages
+            .filter(|result| match result {
+                Ok(page) => namespaces.contains(&page.namespace),
+                Err(_) => true,
             })
             .take(page_limit);
-        let configuration = create_configuration();
-        for page in parser {
-            // eprintln!("title: [[{}]]", &page.title);
-            let parser_output = configuration.parse(&page.text);
-            if verbose {
-                for warning in parser_output.warnings {
-                    let Warning {
-                        start,
-                        end,
-                        message,
-                    } = warning;
-                    let range = 0..page.text.len();
-                    let message = message.message().trim_end_matches('.');
-                    if !(range.contains(&start) && range.contains(&end)) {
-                        eprintln!("byte position {} or {} in warning {} is out of range of {:?}, size of [[{}]]",
-                            start, end, message, range, &page.title);
-                    } else {
-                        eprintln!(
-                            "{} at bytes {}..{} ({:?}) in [[{}]]",
-                            &message,
-                            start,
-                            end,
-                            &page.text[start..end],
-                            &page.title
-                        );
-                    }
+        process_in_order(
+            pages,
+            threads,
+            |page| {
+                let parser_output = configuration.parse(&page.text);
+                if verbose {
+                    print_parser_warnings(&page, &parser_output.warnings);
                 }
-            }
+                HeaderStats::from_page(&page, &parser_output.nodes)
+            },
+            |page_stats| {
+                self.merge(page_stats);
+                Ok(())
+            },
+        )
+    }
 
-            self.process_nodes(&page, &parser_output.nodes);
-        }
+    /// Count the headers in a parsed page.
+    pub fn from_page(page: &Page, nodes: &[Node]) -> Self {
+        let mut page_stats = HeaderStats::new();
+        walk(&page.text, nodes, &mut page_stats);
+        page_stats
     }
 
-    fn process_nodes(&mut self, page: &Page, nodes: &[Node]) {
-        for node in nodes {
-            match node {
-                DefinitionList { items, .. } => {
-                    for item in items {
-                        self.process_nodes(&page, &item.nodes);
-                    }
-                }
-                Heading { nodes, level, .. } => {
-                    self.process_header(&page, &nodes, *level);
-                }
-                Preformatted { nodes, .. } | Tag { nodes, .. } => {
-                    self.process_nodes(&page, &nodes);
-                }
-                Image { text, .. } | Link { text, .. } => {
-                    self.process_nodes(&page, &text);
-                }
-                OrderedList { items, .. } | UnorderedList { items, .. } => {
-                    for item in items {
-                        self.process_nodes(&page, &item.nodes);
-                    }
-                }
-                Parameter { name, default, .. } => {
-                    match default {
-                        Some(nodes) => self.process_nodes(&page, &nodes),
-                        None => {}
-                    }
-                    self.process_nodes(&page, &name);
-                }
-                Table {
-                    attributes,
-                    captions,
-                    rows,
-                    ..
-                } => {
-                    self.process_nodes(&page, &attributes);
-                    for caption in captions {
-                        if let Some(attributes) = &caption.attributes {
-                            self.process_nodes(&page, attributes)
-                        }
-                        self.process_nodes(&page, &caption.content);
-                    }
-                    for row in rows {
-                        self.process_nodes(&page, &row.attributes);
-                        for cell in &row.cells {
-                            if let Some(attributes) = &cell.attributes {
-                                self.process_nodes(&page, attributes);
-                            }
-                            self.process_nodes(&page, &cell.content);
-                        }
-                    }
-                }
-                Template {
-                    name, parameters, ..
-                } => {
-                    self.process_nodes(&page, &name);
-                    for parameter in parameters {
-                        if let Some(name) = &parameter.name {
-                            self.process_nodes(&page, name);
-                        }
-                        self.process_nodes(&page, &parameter.value);
-                    }
-                }
-                Bold { .. }
-                | BoldItalic { .. }
-                | Category { .. }
-                | CharacterEntity { .. }
-                | Comment { .. }
-                | EndTag { .. }
-                | ExternalLink { .. }
-                | HorizontalDivider { .. }
-                | Italic { .. }
-                | MagicWord { .. }
-                | ParagraphBreak { .. }
-                | Redirect { .. }
-                | StartTag { .. }
-                | Text { .. } => {}
+    pub fn merge(&mut self, other: HeaderStats) {
+        for (header, counts) in other.header_counts {
+            let total = self
+                .header_counts
+                .entry(header)
+                .or_insert_with(HeaderCounts::new);
+            for (total, count) in total.0.iter_mut().zip(counts.0.iter()) {
+                *total += count;
             }
         }
     }
 
-    fn process_header(&mut self, page: &Page, nodes: &[Node], level: u8) {
+    fn process_header(&mut self, wikitext: &str, nodes: &[Node], level: u8) {
         let key = nodes
-            .get_text_from(&page.text)
+            .get_text_from(wikitext)
             .trim_matches(|c: char| c == ' ' || c == '\t');
         let value = self
             .header_counts
@@ -223,3 +145,16 @@ impl HeaderStats {
         value[level as HeaderLevel] += 1;
     }
 }
+
+impl<'a> Visitor<'a> for HeaderStats {
+    fn visit_heading(
+        &mut self,
+        _node: &'a Node<'a>,
+        level: u8,
+        nodes: &'a [Node<'a>],
+        context: &Context<'a>,
+    ) -> Visit {
+        self.process_header(context.wikitext(), nodes, level);
+        Visit::Continue
+    }
+}
@@ -0,0 +1,262 @@
+//! The entries in the part-of-speech sections of a page: the headword-line
+//! template and the definitions with their examples, quotations and
+//! sub-senses.
+use dump_parser::{parse_wiki_text::ListItem, Node, Page, Positioned};
+use filter_headers::is_part_of_speech;
+use serde::Serialize;
+use template_iter::TemplateBorrowed;
+
+use crate::{language_sections, Section};
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Quotation<'a> {
+    /// The `#*` line, which usually gives the source of the quotation.
+    pub citation: &'a str,
+    /// The `#*:` lines under it, which usually give the quoted passage and
+    /// its translation.
+    pub passage: Vec<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Definition<'a> {
+    /// The number of the definition in its list, starting at 1.
+    pub number: usize,
+    /// The wikitext of the definition without the lines nested under it.
+    pub text: &'a str,
+    /// The `#:` lines.
+    pub examples: Vec<&'a str>,
+    pub quotations: Vec<Quotation<'a>>,
+    /// The `##` lines.
+    pub subsenses: Vec<Definition<'a>>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Entry<'a> {
+    pub language: &'a str,
+    pub part_of_speech: &'a str,
+    /// The first template on the last line with templates before the
+    /// definitions, normally a headword-line template like `{{en-noun}}`.
+    pub head: Option<TemplateBorrowed<'a>>,
+    pub definitions: Vec<Definition<'a>>,
+}
+
+fn is_list(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::DefinitionList { .. }
+            | Node::OrderedList { .. }
+            | Node::UnorderedList { .. }
+    )
+}
+
+// The trimmed wikitext of the nodes, which may be empty.
+fn text<'a>(wikitext: &'a str, nodes: &[Node]) -> &'a str {
+    match (nodes.first(), nodes.last()) {
+        (Some(first), Some(last)) => wikitext[first.start()..last.end()].trim(),
+        _ => "",
+    }
+}
+
+// The wikitext of the nodes before the first nested list, and the nodes
+// after it.
+pub(crate) fn split_item<'a, 'b>(
+    wikitext: &'a str,
+    nodes: &'b [Node<'b>],
+) -> (&'a str, &'b [Node<'b>]) {
+    let list_start = nodes.iter().position(is_list).unwrap_or(nodes.len());
+    (text(wikitext, &nodes[..list_start]), &nodes[list_start..])
+}
+
+fn definition<'a>(
+    wikitext: &'a str,
+    number: usize,
+    nodes: &'a [Node<'a>],
+) -> Definition<'a> {
+    let (text_before_lists, rest) = split_item(wikitext, nodes);
+    let mut definition = Definition {
+        number,
+        text: text_before_lists,
+        examples: Vec::new(),
+        quotations: Vec::new(),
+        subsenses: Vec::new(),
+    };
+    for node in rest {
+        match node {
+            Node::OrderedList { items, .. } => {
+                let offset = definition.subsenses.len();
+                definition
+                    .subsenses
+                    .extend(definitions(wikitext, items, offset));
+            }
+            Node::DefinitionList { items, .. } => definition
+                .examples
+                .extend(items.iter().map(|item| text(wikitext, &item.nodes))),
+            Node::UnorderedList { items, .. } => definition.quotations.extend(
+                items.iter().map(|item| quotation(wikitext, &item.nodes)),
+            ),
+            _ => {}
+        }
+    }
+    definition
+}
+
+fn quotation<'a>(wikitext: &'a str, nodes: &'a [Node<'a>]) -> Quotation<'a> {
+    let (citation, rest) = split_item(wikitext, nodes);
+    let passage = rest
+        .iter()
+        .filter_map(|node| match node {
+            Node::DefinitionList { items, .. } => Some(items),
+            _ => None,
+        })
+        .flatten()
+        .map(|item| text(wikitext, &item.nodes))
+        .collect();
+    Quotation { citation, passage }
+}
+
+// Definitions numbered after the `offset` definitions before them.
+fn definitions<'a>(
+    wikitext: &'a str,
+    items: &'a [ListItem<'a>],
+    offset: usize,
+) -> Vec<Definition<'a>> {
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| definition(wikitext, offset + i + 1, &item.nodes))
+        .collect()
+}
+
+// The entry for the section if it is a part-of-speech section, and the
+// entries in its subsections.
+fn section_entries<'a>(
+    wikitext: &'a str,
+    nodes: &'a [Node<'a>],
+    language: &'a str,
+    section: &Section<'a>,
+    entries: &mut Vec<Entry<'a>>,
+) {
+    if is_part_of_speech(section.name) {
+        let mut head = None;
+        let mut last_template_end = None;
+        let mut definitions = Vec::new();
+        for node in section.content(nodes) {
+            match node {
+                Node::OrderedList { items, .. } => {
+                    let offset = definitions.len();
+                    definitions
+                        .extend(self::definitions(wikitext, items, offset));
+                }
+                Node::Template {
+                    name, parameters, ..
+                } if definitions.is_empty() => {
+                    let same_line = matches!(
+                        last_template_end,
+                        Some(end) if !wikitext[end..node.start()].contains('\n')
+                    );
+                    if !same_line {
+                        head = Some(TemplateBorrowed::new(
+                            wikitext, name, parameters,
+                        ));
+                    }
+                    last_template_end = Some(node.end());
+                }
+                _ => {}
+            }
+        }
+        entries.push(Entry {
+            language,
+            part_of_speech: section.name,
+            head,
+            definitions,
+        });
+    }
+    for subsection in &section.subsections {
+        section_entries(wikitext, nodes, language, subsection, entries);
+    }
+}
+
+/// The entries in the part-of-speech sections of the page, including ones
+/// nested under other sections like `===Etymology 1===`, in the order they
+/// appear.
+pub fn entries<'a>(page: &'a Page, nodes: &'a [Node<'a>]) -> Vec<Entry<'a>> {
+    let mut entries = Vec::new();
+    for language in language_sections(page, nodes) {
+        for section in &language.subsections {
+            section_entries(
+                &page.text,
+                nodes,
+                language.name,
+                section,
+                &mut entries,
+            );
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entries, Definition, Quotation};
+    use dump_parser::{wiktionary_configuration, Namespace, Page};
+
+    #[test]
+    fn definitions() {
+        let page = Page::new(
+            "word",
+            Namespace::MAIN,
+            concat!(
+                "==English==\n",
+                "===Etymology 1===\n",
+                "From {{inh|en|enm|word}}.\n",
+                "====Noun====\n",
+                "{{wikipedia}}\n",
+                "{{en-noun}} {{tlb|en|rare}}\n\n",
+                "# A unit of language.\n",
+                "#: {{ux|en|a long '''word'''}}\n",
+                "## A unit of text.\n",
+                "# A promise.\n",
+                "#* {{quote-book|en|year=2000}}\n",
+                "#*: My '''word''' is good.\n",
+                "====Synonyms====\n",
+                "* {{l|en|term}}\n",
+                "===Verb===\n",
+                "# To put into words.\n",
+            ),
+        );
+        let output = wiktionary_configuration().parse(&page.text);
+        let entries = entries(&page, &output.nodes);
+        assert_eq!(entries.len(), 2);
+        let noun = &entries[0];
+        assert_eq!((noun.language, noun.part_of_speech), ("English", "Noun"));
+        assert_eq!(noun.head.as_ref().unwrap().name, "en-noun");
+        let definition = |number, text| Definition {
+            number,
+            text,
+            examples: vec![],
+            quotations: vec![],
+            subsenses: vec![],
+        };
+        assert_eq!(
+            noun.definitions,
+            vec![
+                Definition {
+                    examples: vec!["{{ux|en|a long '''word'''}}"],
+                    subsenses: vec![definition(1, "A unit of text.")],
+                    ..definition(1, "A unit of language.")
+                },
+                Definition {
+                    quotations: vec![Quotation {
+                        citation: "{{quote-book|en|year=2000}}",
+                        passage: vec!["My '''word''' is good."],
+                    }],
+                    ..definition(2, "A promise.")
+                },
+            ]
+        );
+        let verb = &entries[1];
+        assert_eq!(verb.part_of_speech, "Verb");
+        assert!(verb.head.is_none());
+        assert_eq!(verb.definitions, vec![definition(1, "To put into words.")]);
+    }
+}
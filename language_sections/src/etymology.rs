@@ -0,0 +1,322 @@
+//! Etymological relations in the Etymology sections of a page, like
+//! `{{inh|en|enm|word}}`, and the trees of descendants in the Descendants
+//! sections.
+use dump_parser::{Node, Page};
+use serde::Serialize;
+use template_iter::{TemplateBorrowed, TemplateVisitor};
+
+use crate::{entries::split_item, language_sections, Section};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Relation {
+    Inherited,
+    Borrowed,
+    Derived,
+    Calque,
+    Cognate,
+    Affix,
+    Compound,
+}
+
+impl Relation {
+    fn from_template_name(name: &str) -> Option<Self> {
+        use Relation::*;
+        let relation = match name {
+            "inh" | "inh+" | "inherited" => Inherited,
+            "bor" | "bor+" | "borrowed" => Borrowed,
+            "der" | "der+" | "derived" => Derived,
+            "cal" | "calque" => Calque,
+            "cog" | "cognate" => Cognate,
+            "af" | "affix" => Affix,
+            "com" | "compound" => Compound,
+            _ => return None,
+        };
+        Some(relation)
+    }
+}
+
+/// A relation between the entry and a term in another language or the same
+/// language, from a template in an Etymology section.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Edge<'a> {
+    /// The header of the section, like `Etymology 1`.
+    pub section: &'a str,
+    pub relation: Relation,
+    /// The language code of the term.
+    pub language: &'a str,
+    /// The term, unless the template leaves it out with `-` or nothing.
+    pub term: Option<&'a str>,
+    pub gloss: Option<&'a str>,
+}
+
+// A term parameter without the `-` that means that there is no term.
+fn term(value: Option<&str>) -> Option<&str> {
+    value.filter(|&value| value != "-")
+}
+
+fn gloss<'a>(
+    template: &TemplateBorrowed<'a>,
+    position: &str,
+) -> Option<&'a str> {
+    template
+        .parameter("t")
+        .or_else(|| template.parameter("gloss"))
+        .or_else(|| template.parameter(position))
+}
+
+// The edges given by a template, which are several for the parts in
+// `{{af}}` and `{{compound}}`.
+fn add_edges<'a>(
+    section: &'a str,
+    template: &TemplateBorrowed<'a>,
+    edges: &mut Vec<Edge<'a>>,
+) {
+    let relation = match Relation::from_template_name(template.name.trim()) {
+        Some(relation) => relation,
+        None => return,
+    };
+    match relation {
+        Relation::Inherited
+        | Relation::Borrowed
+        | Relation::Derived
+        | Relation::Calque => {
+            if let Some(language) = template.parameter("2") {
+                edges.push(Edge {
+                    section,
+                    relation,
+                    language,
+                    term: term(template.parameter("3")),
+                    gloss: gloss(template, "5"),
+                })
+            }
+        }
+        Relation::Cognate => {
+            if let Some(language) = template.parameter("1") {
+                edges.push(Edge {
+                    section,
+                    relation,
+                    language,
+                    term: term(template.parameter("2")),
+                    gloss: gloss(template, "4"),
+                })
+            }
+        }
+        Relation::Affix | Relation::Compound => {
+            let language = match template.parameter("1") {
+                Some(language) => language,
+                None => return,
+            };
+            let parts = template
+                .numbered_parameters(2)
+                .enumerate()
+                .filter(|&(_, part)| !part.is_empty() && part != "-");
+            for (i, part) in parts {
+                let n = (i + 1).to_string();
+                edges.push(Edge {
+                    section,
+                    relation,
+                    language: template
+                        .parameter(&format!("lang{}", n))
+                        .unwrap_or(language),
+                    term: Some(part),
+                    gloss: template.parameter(&format!("t{}", n)),
+                });
+            }
+        }
+    }
+}
+
+static DESCENDANT_TEMPLATES: &[&str] =
+    &["desc", "descendant", "desctree", "l", "l-self"];
+
+/// A line in a Descendants section, with the lines nested under it.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Descendant<'a> {
+    /// The language code in the first `{{desc}}` or `{{l}}` template in the
+    /// line.
+    pub language: Option<&'a str>,
+    /// The terms in the `{{desc}}` and `{{l}}` templates in that language.
+    pub terms: Vec<&'a str>,
+    /// The wikitext of the line without the lines nested under it.
+    pub text: &'a str,
+    pub descendants: Vec<Descendant<'a>>,
+}
+
+fn descendant<'a>(wikitext: &'a str, nodes: &'a [Node<'a>]) -> Descendant<'a> {
+    let (text, rest) = split_item(wikitext, nodes);
+    let mut language = None;
+    let mut terms = Vec::new();
+    let line = &nodes[..nodes.len() - rest.len()];
+    TemplateVisitor::new(wikitext).visit(line, &mut |template, _| {
+        let name = template.name.trim();
+        if !DESCENDANT_TEMPLATES.contains(&name) {
+            return;
+        }
+        let template_language = template.parameter("1");
+        if language.is_none() {
+            language = template_language;
+        } else if template_language != language {
+            return;
+        }
+        if name == "l" || name == "l-self" {
+            terms.extend(term(template.parameter("2")));
+        } else {
+            terms.extend(
+                template
+                    .numbered_parameters(2)
+                    .filter(|value| !value.is_empty() && *value != "-"),
+            );
+        }
+    });
+    Descendant {
+        language,
+        terms,
+        text,
+        descendants: descendants(wikitext, rest),
+    }
+}
+
+fn descendants<'a>(
+    wikitext: &'a str,
+    nodes: &'a [Node<'a>],
+) -> Vec<Descendant<'a>> {
+    nodes
+        .iter()
+        .filter_map(|node| match node {
+            Node::UnorderedList { items, .. } => Some(items),
+            _ => None,
+        })
+        .flatten()
+        .map(|item| descendant(wikitext, &item.nodes))
+        .collect()
+}
+
+/// The etymological relations and descendants in a language section.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Etymology<'a> {
+    pub language: &'a str,
+    pub edges: Vec<Edge<'a>>,
+    pub descendants: Vec<Descendant<'a>>,
+}
+
+fn add_section<'a>(
+    wikitext: &'a str,
+    nodes: &'a [Node<'a>],
+    section: &Section<'a>,
+    etymology: &mut Etymology<'a>,
+) {
+    if section.name.starts_with("Etymology") {
+        TemplateVisitor::new(wikitext).visit(
+            section.content(nodes),
+            &mut |template, _| {
+                add_edges(section.name, &template, &mut etymology.edges)
+            },
+        );
+    } else if section.name == "Descendants" {
+        etymology
+            .descendants
+            .extend(descendants(wikitext, section.content(nodes)));
+    }
+    for subsection in &section.subsections {
+        add_section(wikitext, nodes, subsection, etymology);
+    }
+}
+
+/// The etymological relations and descendants in each language section of
+/// the page that has any.
+pub fn etymologies<'a>(
+    page: &'a Page,
+    nodes: &'a [Node<'a>],
+) -> Vec<Etymology<'a>> {
+    language_sections(page, nodes)
+        .into_iter()
+        .filter_map(|language| {
+            let mut etymology = Etymology {
+                language: language.name,
+                edges: Vec::new(),
+                descendants: Vec::new(),
+            };
+            for section in &language.subsections {
+                add_section(&page.text, nodes, section, &mut etymology);
+            }
+            if etymology.edges.is_empty() && etymology.descendants.is_empty() {
+                None
+            } else {
+                Some(etymology)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{etymologies, Descendant, Edge, Relation::*};
+    use dump_parser::{wiktionary_configuration, Namespace, Page};
+
+    #[test]
+    fn etymology_and_descendants() {
+        let page = Page::new(
+            "catus",
+            Namespace::MAIN,
+            concat!(
+                "==Latin==\n",
+                "===Etymology===\n",
+                "From {{inh|la|itc-pro|*katos|t=cat}}, ",
+                "perhaps {{bor|la|grc|-}}. ",
+                "{{af|la|cat|-us|t2=suffix}}. ",
+                "Compare {{cog|osc|-}}.\n",
+                "===Noun===\n",
+                "{{la-noun|catus<2>}}\n\n",
+                "# cat\n",
+                "====Descendants====\n",
+                "* Old French: {{desc|fro|chat}}\n",
+                "** French: {{desc|fr|chat|chatte}}\n",
+                "* {{desc|es|gato|bor=1}}\n",
+            ),
+        );
+        let output = wiktionary_configuration().parse(&page.text);
+        let edge = |relation, language, term, gloss| Edge {
+            section: "Etymology",
+            relation,
+            language,
+            term,
+            gloss,
+        };
+        let etymologies = etymologies(&page, &output.nodes);
+        assert_eq!(etymologies.len(), 1);
+        assert_eq!(etymologies[0].language, "Latin");
+        assert_eq!(
+            etymologies[0].edges,
+            vec![
+                edge(Inherited, "itc-pro", Some("*katos"), Some("cat")),
+                edge(Borrowed, "grc", None, None),
+                edge(Affix, "la", Some("cat"), None),
+                edge(Affix, "la", Some("-us"), Some("suffix")),
+                edge(Cognate, "osc", None, None),
+            ]
+        );
+        assert_eq!(
+            etymologies[0].descendants,
+            vec![
+                Descendant {
+                    language: Some("fro"),
+                    terms: vec!["chat"],
+                    text: "Old French: {{desc|fro|chat}}",
+                    descendants: vec![Descendant {
+                        language: Some("fr"),
+                        terms: vec!["chat", "chatte"],
+                        text: "French: {{desc|fr|chat|chatte}}",
+                        descendants: vec![],
+                    }],
+                },
+                Descendant {
+                    language: Some("es"),
+                    terms: vec!["gato"],
+                    text: "{{desc|es|gato|bor=1}}",
+                    descendants: vec![],
+                },
+            ]
+        );
+    }
+}
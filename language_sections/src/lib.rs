@@ -0,0 +1,223 @@
+//! Splitting Wiktionary entries into language sections, the sections under
+//! level-2 headers like `==English==`, and those into their subsections.
+use dump_parser::{Node, Page, Positioned};
+use parse_wiki_text_ext::visit;
+use serde::Serialize;
+use std::ops::Range;
+
+pub mod entries;
+pub mod etymology;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Section<'a> {
+    /// The text of the header without the equals signs and the whitespace
+    /// around it.
+    pub name: &'a str,
+    pub level: u8,
+    /// The byte offset of the header.
+    pub start: usize,
+    /// The byte offset after the last non-whitespace character of the
+    /// section, leaving out the `----` divider before the next language
+    /// section.
+    pub end: usize,
+    /// Sections with higher-level headers, even if some levels are skipped,
+    /// as in `====Noun====` directly under `==English==`.
+    pub subsections: Vec<Section<'a>>,
+}
+
+impl<'a> Section<'a> {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// The wikitext of the section, including the header and the
+    /// subsections.
+    pub fn text<'b>(&self, wikitext: &'b str) -> &'b str {
+        &wikitext[self.range()]
+    }
+
+    /// The top-level nodes after the header and before the first subsection,
+    /// given the nodes of the whole page.
+    pub fn content<'b>(&self, nodes: &'b [Node<'b>]) -> &'b [Node<'b>] {
+        let end = self
+            .subsections
+            .first()
+            .map_or(self.end, |subsection| subsection.start);
+        let first = nodes
+            .iter()
+            .position(|node| node.start() > self.start)
+            .unwrap_or(nodes.len());
+        let last = nodes[first..]
+            .iter()
+            .position(|node| node.start() >= end)
+            .map_or(nodes.len(), |i| first + i);
+        &nodes[first..last]
+    }
+}
+
+// Where a section ends if the next header starts at `next_start`.
+fn section_end(
+    wikitext: &str,
+    start: usize,
+    next_start: usize,
+    divider: Option<usize>,
+) -> usize {
+    let end = wikitext[..next_start].trim_end().len();
+    match divider {
+        Some(divider)
+            if divider > start
+                && wikitext[divider..end].bytes().all(|b| b == b'-') =>
+        {
+            wikitext[..divider].trim_end().len()
+        }
+        _ => end,
+    }
+}
+
+struct SectionBuilder<'a> {
+    wikitext: &'a str,
+    languages: Vec<Section<'a>>,
+    // The sections that have been started but not ended, from the lowest
+    // level to the highest.
+    open: Vec<Section<'a>>,
+    last_divider: Option<usize>,
+}
+
+impl<'a> SectionBuilder<'a> {
+    // End the sections whose level is the same as or lower than `level`.
+    fn close(&mut self, level: u8, next_start: usize) {
+        while let Some(section) = self.open.last() {
+            if section.level < level {
+                break;
+            }
+            let mut section = self.open.pop().unwrap();
+            section.end = section_end(
+                self.wikitext,
+                section.start,
+                next_start,
+                self.last_divider,
+            );
+            match self.open.last_mut() {
+                Some(parent) => parent.subsections.push(section),
+                None => self.languages.push(section),
+            }
+        }
+    }
+
+    fn heading(&mut self, level: u8, name: &'a str, start: usize) {
+        self.close(level, start);
+        // Headers above the first language header and level-1 headers are
+        // not part of any language section.
+        if level == 2 || (level > 2 && !self.open.is_empty()) {
+            self.open.push(Section {
+                name,
+                level,
+                start,
+                end: start,
+                subsections: Vec::new(),
+            });
+        }
+    }
+}
+
+fn sections<'a>(wikitext: &'a str, nodes: &'a [Node<'a>]) -> Vec<Section<'a>> {
+    let mut builder = SectionBuilder {
+        wikitext,
+        languages: Vec::new(),
+        open: Vec::new(),
+        last_divider: None,
+    };
+    for node in visit::nodes(nodes) {
+        match node {
+            Node::Heading {
+                level,
+                nodes,
+                start,
+                ..
+            } => builder.heading(
+                *level,
+                nodes.get_text_from(wikitext).trim(),
+                *start,
+            ),
+            Node::HorizontalDivider { start, .. } => {
+                builder.last_divider = Some(*start)
+            }
+            _ => {}
+        }
+    }
+    builder.close(0, wikitext.len());
+    builder.languages
+}
+
+/// The language sections of a page in the order they appear. If there are
+/// two sections for the same language, both are returned.
+pub fn language_sections<'a>(
+    page: &'a Page,
+    nodes: &'a [Node<'a>],
+) -> Vec<Section<'a>> {
+    sections(&page.text, nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sections, Section};
+    use dump_parser::wiktionary_configuration;
+
+    #[test]
+    fn language_sections() {
+        let wikitext = concat!(
+            "{{also|Word}}\n",
+            "==English==\n",
+            "====Noun====\n",
+            "word\n",
+            "===Verb===\n",
+            "to word\n\n",
+            "----\n\n",
+            "==French==\n",
+            "===Noun===\n",
+            "mot\n",
+            "==English==\n",
+            "more\n",
+        );
+        let output = wiktionary_configuration().parse(wikitext);
+        let sections = sections(wikitext, &output.nodes);
+        let section =
+            |name, level, start: &str, end: &str, subsections| Section {
+                name,
+                level,
+                start: wikitext.find(start).unwrap(),
+                end: wikitext.find(end).unwrap() + end.len(),
+                subsections,
+            };
+        assert_eq!(
+            sections,
+            vec![
+                section(
+                    "English",
+                    2,
+                    "==English==",
+                    "to word",
+                    vec![
+                        section("Noun", 4, "====Noun", "word", vec![]),
+                        section("Verb", 3, "===Verb", "to word", vec![]),
+                    ],
+                ),
+                section(
+                    "French",
+                    2,
+                    "==French==",
+                    "mot",
+                    vec![section("Noun", 3, "===Noun", "mot", vec![])],
+                ),
+                Section {
+                    name: "English",
+                    level: 2,
+                    start: wikitext.rfind("==English==").unwrap(),
+                    end: wikitext.len() - 1,
+                    subsections: vec![],
+                },
+            ]
+        );
+        assert_eq!(sections[0].text(wikitext).lines().count(), 5);
+    }
+}
@@ -0,0 +1,70 @@
+use serde::{Serialize, Serializer};
+
+/// The headers that enclose a position in a page, from the highest level to
+/// the lowest, for instance `["English", "Etymology 1", "Noun"]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderPath<'a> {
+    headers: Vec<(u8, &'a str)>,
+}
+
+impl<'a> HeaderPath<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enter a header, leaving the headers of the same or a lower level.
+    pub fn push(&mut self, level: u8, header: &'a str) {
+        while let Some(&(last_level, _)) = self.headers.last() {
+            if last_level < level {
+                break;
+            }
+            self.headers.pop();
+        }
+        self.headers.push((level, header));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.headers.iter().map(|&(_, header)| header)
+    }
+
+    pub fn to_vec(&self) -> Vec<&'a str> {
+        self.iter().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+}
+
+impl Serialize for HeaderPath<'_> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeaderPath;
+
+    #[test]
+    fn push() {
+        let mut path = HeaderPath::new();
+        path.push(2, "English");
+        path.push(3, "Etymology 1");
+        path.push(4, "Noun");
+        assert_eq!(path.to_vec(), ["English", "Etymology 1", "Noun"]);
+        path.push(4, "Verb");
+        assert_eq!(path.to_vec(), ["English", "Etymology 1", "Verb"]);
+        path.push(3, "Etymology 2");
+        assert_eq!(path.to_vec(), ["English", "Etymology 2"]);
+        // A level can be skipped.
+        path.push(5, "Synonyms");
+        path.push(4, "Noun");
+        assert_eq!(path.to_vec(), ["English", "Etymology 2", "Noun"]);
+        path.push(2, "French");
+        assert_eq!(path.to_vec(), ["French"]);
+    }
+}
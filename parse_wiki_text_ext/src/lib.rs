@@ -1,4 +1,7 @@
+pub mod headers;
+pub use headers::HeaderPath;
 pub mod template_parameters;
+pub mod visit;
 
 #[cfg(test)]
 mod tests {
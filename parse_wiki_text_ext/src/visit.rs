@@ -0,0 +1,420 @@
+//! Walking the tree of nodes produced by the parser.
+//!
+//! [`walk`] visits the nodes depth-first and calls the hooks of a
+//! [`Visitor`] with a [`Context`] that tells where each node is: its parent
+//! nodes, the headers it is under and how deeply it is nested. [`nodes`]
+//! iterates over the nodes in the same order.
+use parse_wiki_text::{Node, Parameter, Positioned};
+
+use crate::headers::HeaderPath;
+
+/// What the walk does after a hook returns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visit {
+    Continue,
+    /// Do not visit the children of the node. Returned by a hook that is
+    /// called after the children, this is the same as `Continue`.
+    SkipChildren,
+    /// Stop the walk without visiting any more nodes.
+    Stop,
+}
+
+/// Where a node is in the page.
+#[derive(Debug)]
+pub struct Context<'a> {
+    wikitext: &'a str,
+    parents: Vec<&'a Node<'a>>,
+    headers: HeaderPath<'a>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(wikitext: &'a str) -> Self {
+        Context {
+            wikitext,
+            parents: Vec::new(),
+            headers: HeaderPath::new(),
+        }
+    }
+
+    pub fn wikitext(&self) -> &'a str {
+        self.wikitext
+    }
+
+    /// The nodes that contain the node, from the outermost to the innermost.
+    pub fn parents(&self) -> &[&'a Node<'a>] {
+        &self.parents
+    }
+
+    pub fn parent(&self) -> Option<&'a Node<'a>> {
+        self.parents.last().copied()
+    }
+
+    /// The number of nodes that contain the node.
+    pub fn depth(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// The headers that the node is under. In the hooks for a heading, the
+    /// heading itself is the last one.
+    pub fn headers(&self) -> &HeaderPath<'a> {
+        &self.headers
+    }
+
+    /// The name of the innermost template that contains the node.
+    pub fn enclosing_template(&self) -> Option<&'a str> {
+        self.parents.iter().rev().find_map(|parent| match parent {
+            Node::Template { name, .. } => {
+                Some(name.get_text_from(self.wikitext).trim())
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Hooks called by [`walk`]. `visit_node` is called for every node before
+/// its children and `leave_node` after them; by default they call the hook
+/// for the kind of node, if there is one.
+#[allow(unused_variables)]
+pub trait Visitor<'a> {
+    fn visit_node(
+        &mut self,
+        node: &'a Node<'a>,
+        context: &Context<'a>,
+    ) -> Visit {
+        match node {
+            Node::Comment { .. } => self.visit_comment(node, context),
+            Node::Heading { level, nodes, .. } => {
+                self.visit_heading(node, *level, nodes, context)
+            }
+            Node::Link { target, text, .. } => {
+                self.visit_link(node, target, text, context)
+            }
+            Node::Template {
+                name, parameters, ..
+            } => self.visit_template(node, name, parameters, context),
+            Node::Text { value, .. } => self.visit_text(node, value, context),
+            _ => Visit::Continue,
+        }
+    }
+
+    fn leave_node(
+        &mut self,
+        node: &'a Node<'a>,
+        context: &Context<'a>,
+    ) -> Visit {
+        match node {
+            Node::Template {
+                name, parameters, ..
+            } => self.leave_template(node, name, parameters, context),
+            _ => Visit::Continue,
+        }
+    }
+
+    fn visit_comment(
+        &mut self,
+        node: &'a Node<'a>,
+        context: &Context<'a>,
+    ) -> Visit {
+        Visit::Continue
+    }
+
+    fn visit_heading(
+        &mut self,
+        node: &'a Node<'a>,
+        level: u8,
+        nodes: &'a [Node<'a>],
+        context: &Context<'a>,
+    ) -> Visit {
+        Visit::Continue
+    }
+
+    fn visit_link(
+        &mut self,
+        node: &'a Node<'a>,
+        target: &'a str,
+        text: &'a [Node<'a>],
+        context: &Context<'a>,
+    ) -> Visit {
+        Visit::Continue
+    }
+
+    fn visit_template(
+        &mut self,
+        node: &'a Node<'a>,
+        name: &'a [Node<'a>],
+        parameters: &'a [Parameter<'a>],
+        context: &Context<'a>,
+    ) -> Visit {
+        Visit::Continue
+    }
+
+    /// Called after the nodes in the name and parameters of the template.
+    fn leave_template(
+        &mut self,
+        node: &'a Node<'a>,
+        name: &'a [Node<'a>],
+        parameters: &'a [Parameter<'a>],
+        context: &Context<'a>,
+    ) -> Visit {
+        Visit::Continue
+    }
+
+    fn visit_text(
+        &mut self,
+        node: &'a Node<'a>,
+        value: &'a str,
+        context: &Context<'a>,
+    ) -> Visit {
+        Visit::Continue
+    }
+}
+
+fn call_if_some<'a, F>(nodes: &'a Option<Vec<Node<'a>>>, func: &mut F) -> bool
+where
+    F: FnMut(&'a [Node<'a>]) -> bool,
+{
+    match nodes {
+        Some(nodes) => func(nodes),
+        None => true,
+    }
+}
+
+// Call `func` on each list of child nodes in the order they appear in the
+// wikitext, until it returns `false`.
+fn for_each_child_list<'a, F>(node: &'a Node<'a>, mut func: F) -> bool
+where
+    F: FnMut(&'a [Node<'a>]) -> bool,
+{
+    use Node::*;
+    match node {
+        DefinitionList { items, .. } => {
+            items.iter().all(|item| func(&item.nodes))
+        }
+        OrderedList { items, .. } | UnorderedList { items, .. } => {
+            items.iter().all(|item| func(&item.nodes))
+        }
+        Heading { nodes, .. }
+        | Preformatted { nodes, .. }
+        | Tag { nodes, .. } => func(nodes),
+        Image { text, .. } | Link { text, .. } => func(text),
+        Parameter { name, default, .. } => {
+            func(name) && call_if_some(default, &mut func)
+        }
+        Table {
+            attributes,
+            captions,
+            rows,
+            ..
+        } => {
+            func(attributes)
+                && captions.iter().all(|caption| {
+                    call_if_some(&caption.attributes, &mut func)
+                        && func(&caption.content)
+                })
+                && rows.iter().all(|row| {
+                    func(&row.attributes)
+                        && row.cells.iter().all(|cell| {
+                            call_if_some(&cell.attributes, &mut func)
+                                && func(&cell.content)
+                        })
+                })
+        }
+        Template {
+            name, parameters, ..
+        } => {
+            func(name)
+                && parameters.iter().all(|parameter| {
+                    call_if_some(&parameter.name, &mut func)
+                        && func(&parameter.value)
+                })
+        }
+        Bold { .. }
+        | BoldItalic { .. }
+        | Category { .. }
+        | CharacterEntity { .. }
+        | Comment { .. }
+        | EndTag { .. }
+        | ExternalLink { .. }
+        | HorizontalDivider { .. }
+        | Italic { .. }
+        | MagicWord { .. }
+        | ParagraphBreak { .. }
+        | Redirect { .. }
+        | StartTag { .. }
+        | Text { .. } => true,
+    }
+}
+
+fn walk_nodes<'a, V>(
+    nodes: &'a [Node<'a>],
+    context: &mut Context<'a>,
+    visitor: &mut V,
+) -> bool
+where
+    V: Visitor<'a> + ?Sized,
+{
+    for node in nodes {
+        if let Node::Heading { level, nodes, .. } = node {
+            context
+                .headers
+                .push(*level, nodes.get_text_from(context.wikitext).trim());
+        }
+        match visitor.visit_node(node, context) {
+            Visit::Stop => return false,
+            Visit::SkipChildren => {}
+            Visit::Continue => {
+                context.parents.push(node);
+                let finished = for_each_child_list(node, |nodes| {
+                    walk_nodes(nodes, context, visitor)
+                });
+                context.parents.pop();
+                if !finished {
+                    return false;
+                }
+            }
+        }
+        if visitor.leave_node(node, context) == Visit::Stop {
+            return false;
+        }
+    }
+    true
+}
+
+/// Visit the nodes and the nodes inside them depth-first. Returns `false`
+/// if a hook stopped the walk.
+pub fn walk<'a, V>(
+    wikitext: &'a str,
+    nodes: &'a [Node<'a>],
+    visitor: &mut V,
+) -> bool
+where
+    V: Visitor<'a> + ?Sized,
+{
+    walk_nodes(nodes, &mut Context::new(wikitext), visitor)
+}
+
+/// Iterator over nodes and the nodes inside them, in the order in which
+/// [`walk`] visits them.
+pub struct Nodes<'a> {
+    stack: Vec<std::slice::Iter<'a, Node<'a>>>,
+    children: Vec<&'a [Node<'a>]>,
+}
+
+pub fn nodes<'a>(nodes: &'a [Node<'a>]) -> Nodes<'a> {
+    Nodes {
+        stack: vec![nodes.iter()],
+        children: Vec::new(),
+    }
+}
+
+impl<'a> Iterator for Nodes<'a> {
+    type Item = &'a Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let iter = self.stack.last_mut()?;
+            match iter.next() {
+                Some(node) => {
+                    let children = &mut self.children;
+                    for_each_child_list(node, |nodes| {
+                        children.push(nodes);
+                        true
+                    });
+                    self.stack.extend(
+                        self.children.drain(..).rev().map(|nodes| nodes.iter()),
+                    );
+                    return Some(node);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{nodes, walk, Context, Visit, Visitor};
+    use parse_wiki_text::{Configuration, Node, Parameter, Positioned};
+
+    #[derive(Default)]
+    struct Templates<'a> {
+        entered: Vec<(&'a str, usize, Vec<&'a str>)>,
+        left: Vec<(&'a str, Option<&'a str>)>,
+    }
+
+    impl<'a> Visitor<'a> for Templates<'a> {
+        fn visit_template(
+            &mut self,
+            _node: &'a Node<'a>,
+            name: &'a [Node<'a>],
+            _parameters: &'a [Parameter<'a>],
+            context: &Context<'a>,
+        ) -> Visit {
+            self.entered.push((
+                name.get_text_from(context.wikitext()),
+                context.depth(),
+                context.headers().to_vec(),
+            ));
+            Visit::Continue
+        }
+
+        fn leave_template(
+            &mut self,
+            _node: &'a Node<'a>,
+            name: &'a [Node<'a>],
+            _parameters: &'a [Parameter<'a>],
+            context: &Context<'a>,
+        ) -> Visit {
+            let name = name.get_text_from(context.wikitext());
+            self.left.push((name, context.enclosing_template()));
+            if name == "stop" {
+                Visit::Stop
+            } else {
+                Visit::Continue
+            }
+        }
+    }
+
+    #[test]
+    fn visitor() {
+        let wikitext = concat!(
+            "==English==\n",
+            "{{a|[[b|{{c}}]]}}\n",
+            "===Noun===\n",
+            "* {{d}} {{stop}} {{e}}",
+        );
+        let output = Configuration::default().parse(wikitext);
+        let mut visitor = Templates::default();
+        assert!(!walk(wikitext, &output.nodes, &mut visitor));
+        assert_eq!(
+            visitor.entered,
+            vec![
+                ("a", 0, vec!["English"]),
+                ("c", 2, vec!["English"]),
+                ("d", 1, vec!["English", "Noun"]),
+                ("stop", 1, vec!["English", "Noun"]),
+            ]
+        );
+        assert_eq!(
+            visitor.left,
+            vec![("c", Some("a")), ("a", None), ("d", None), ("stop", None)]
+        );
+    }
+
+    #[test]
+    fn iterator() {
+        let wikitext = "{{a|[[b|{{c}}]]}} {{d}}";
+        let output = Configuration::default().parse(wikitext);
+        let templates: Vec<_> = nodes(&output.nodes)
+            .filter_map(|node| match node {
+                Node::Template { name, .. } => {
+                    Some(name.get_text_from(wikitext))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(templates, ["a", "c", "d"]);
+    }
+}
@@ -0,0 +1,31 @@
+//! Stopping the walk over the nodes of a page when a Lua function returns
+//! `false` or throws an error.
+use rlua::{Error as LuaError, Result as LuaResult};
+use template_iter::parse_wiki_text_ext::visit::Visit;
+
+#[derive(Default)]
+pub struct LuaFlow {
+    error: Option<LuaError>,
+}
+
+impl LuaFlow {
+    /// Continue the walk if the Lua function returned `true`.
+    pub fn check(&mut self, result: LuaResult<bool>) -> Visit {
+        match result {
+            Ok(true) => Visit::Continue,
+            Ok(false) => Visit::Stop,
+            Err(e) => {
+                self.error = Some(e);
+                Visit::Stop
+            }
+        }
+    }
+
+    /// Whether to go on to the next page, given whether the walk finished.
+    pub fn finish(&mut self, finished: bool) -> LuaResult<bool> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(finished),
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use bzip2::bufread::BzDecoder;
 use getopts::Options;
 use rlua::{
     Context, Function, Lua, Result as LuaResult,
@@ -9,7 +8,10 @@ use std::fs::File;
 use std::io::{BufRead, BufReader, Read};
 use std::str::FromStr;
 use unicase::UniCase;
-use dump_parser::Namespace;
+use dump_parser::{
+    input, site_config::SiteConfig, DumpParser, Namespace, Namespaces,
+};
+use template_iter::Title;
 
 #[macro_export]
 macro_rules! exit_with_error {
@@ -19,6 +21,8 @@ macro_rules! exit_with_error {
     })
 }
 
+mod lua_flow;
+
 mod process_templates;
 use process_templates::process_templates_with_function;
 
@@ -31,22 +35,34 @@ use process_comments_with_headers::process_comments_and_headers_with_function;
 mod process_headers;
 use process_headers::process_headers_with_function;
 
-struct Page(dump_parser::Page);
-
-impl From<dump_parser::Page> for Page {
-    fn from(page: dump_parser::Page) -> Self {
-        Page(page)
-    }
+struct Page<'a> {
+    page: dump_parser::Page,
+    namespaces: &'a Namespaces,
 }
 
-impl<'lua, 'a> ToLua<'lua> for Page {
+impl<'lua, 'a> ToLua<'lua> for Page<'a> {
     fn to_lua(self, lua: Context<'lua>) -> LuaResult<Value<'lua>> {
-        let page = self.0;
+        let Page { page, namespaces } = self;
         let table = lua.create_table()?;
         table.set("title", page.title)?;
         table.set("text", page.text)?;
-        let namespace_str = page.namespace.as_str();
-        table.set("namespace", namespace_str)?;
+        if let Some(id) = page.id {
+            table.set("id", id)?;
+        }
+        if let Some(redirect) = page.redirect {
+            table.set("redirect", redirect)?;
+        }
+        if let Some(revision_id) = page.revision_id {
+            table.set("revision_id", revision_id)?;
+        }
+        if let Some(timestamp) = page.timestamp {
+            table.set("timestamp", timestamp)?;
+        }
+        // Namespaces missing from the siteinfo are given by number.
+        match namespaces.name(page.namespace) {
+            Some(name) => table.set("namespace", name)?,
+            None => table.set("namespace", page.namespace.to_string())?,
+        }
         if let Some(format) = page.format {
             table.set("format", format)?;
         }
@@ -92,19 +108,27 @@ where
     chunk.call(script_args)
 }
 
-fn process_text_with_function<R: BufRead>(
-    dump_file: R,
+fn process_text_with_function<R: Read>(
+    mut parser: DumpParser<R>,
     process_page: Function,
     namespaces: HashSet<Namespace>,
 ) -> LuaResult<()> {
-    let parser = dump_parser::parse(dump_file).map(|result| {
+    let namespace_names = match parser.siteinfo() {
+        Ok(siteinfo) => siteinfo.namespaces.clone(),
+        Err(e) => exit_with_error!("error while parsing dump: {}", e),
+    };
+    let parser = parser.map(|result| {
         result.unwrap_or_else(|e| {
             exit_with_error!("error while parsing dump: {}", e);
         })
     });
     for page in parser {
         if namespaces.contains(&page.namespace) {
-            let continue_parsing: bool = process_page.call(Page::from(page))?;
+            let page = Page {
+                page,
+                namespaces: &namespace_names,
+            };
+            let continue_parsing: bool = process_page.call(page)?;
             if !continue_parsing {
                 break;
             }
@@ -202,7 +226,12 @@ fn main() {
     let mut options = Options::new();
     options.optopt("s", "script", "Lua script", "FILE");
     options.optopt("e", "eval", "Lua code", "TEXT");
-    options.optopt("i", "dump", "XML page dump file", "FILE");
+    options.optopt(
+        "i",
+        "dump",
+        "XML page dump file, optionally compressed, or - for stdin",
+        "FILE",
+    );
     options.optmulti(
         "n",
         "namespaces",
@@ -237,24 +266,6 @@ fn main() {
         exit_with_error!("--templates or --template-file only allowed with subcommand templates or templates-and-headers");
     }
 
-    let namespace_args = matches.opt_strs("namespaces");
-    let (mut namespaces, mut failures) = (Vec::new(), Vec::<&str>::new());
-    for namespace_arg in &namespace_args {
-        match namespace_arg.parse::<Namespace>() {
-            Ok(n) => namespaces.push(n),
-            Err(_) => failures.push(namespace_arg),
-        }
-    }
-    if !failures.is_empty() {
-        exit_with_error!(
-            "invalid namespace{}: {}",
-            if failures.len() == 1 { "" } else { "s" },
-            failures.join(", ")
-        );
-    } else if namespaces.is_empty() {
-        namespaces.push(Namespace::Main);
-    }
-
     let (script, name, eval) = if matches.opt_present("eval") {
         let script = matches.opt_str("eval").unwrap();
         let name = "(command line)".to_string();
@@ -281,16 +292,41 @@ fn main() {
         .opt_str("dump")
         .unwrap_or_else(|| "pages-articles.xml".into());
 
-    let dump = File::open(&dump_filename).unwrap_or_else(|e| {
+    let dump = input::open(&dump_filename).unwrap_or_else(|e| {
         exit_with_error!("could not open dump file '{}': {}", &dump_filename, e)
     });
+    let mut parser = dump_parser::parse(dump);
+    let siteinfo = parser.siteinfo_mut().unwrap_or_else(|e| {
+        exit_with_error!("error while parsing dump: {}", e);
+    });
+    // The siteinfo of the dump does not list namespace aliases like T:.
+    SiteConfig::wiktionary().add_namespace_aliases(&mut siteinfo.namespaces);
+
+    // Namespace names are looked up in the dump's siteinfo.
+    let namespace_args = matches.opt_strs("namespaces");
+    let (mut namespaces, mut failures) = (Vec::new(), Vec::<&str>::new());
+    for namespace_arg in &namespace_args {
+        match siteinfo.namespaces.parse_arg(namespace_arg) {
+            Some(n) => namespaces.push(n),
+            None => failures.push(namespace_arg),
+        }
+    }
+    if !failures.is_empty() {
+        exit_with_error!(
+            "invalid namespace{}: {}",
+            if failures.len() == 1 { "" } else { "s" },
+            failures.join(", ")
+        );
+    } else if namespaces.is_empty() {
+        namespaces.push(Namespace::MAIN);
+    }
 
     let namespaces: HashSet<_> = namespaces.into_iter().collect();
 
     let templates: Option<HashSet<_>> = if subcommand == Subcommand::Templates
         || subcommand == Subcommand::TemplatesAndHeaders
     {
-        let templates: HashSet<_> = if matches.opt_present("templates") {
+        let templates: Vec<_> = if matches.opt_present("templates") {
             if matches.opt_present("template-file") {
                 eprintln!("both --template and --template-file provided; --template-file ignored.");
             }
@@ -309,7 +345,13 @@ fn main() {
                 "Either --templates or --template-file is required"
             );
         };
-        Some(templates)
+        // Template names are normalized with the namespaces of the dump.
+        Some(templates.iter().filter(|name| !name.trim().is_empty()).map(|name| {
+            Title::from_template_name(name, &siteinfo.namespaces)
+                .unwrap_or_else(|e| {
+                    exit_with_error!("invalid template name {}: {}", name, e)
+                })
+        }).collect())
     } else {
         None
     };
@@ -349,75 +391,37 @@ fn main() {
             make_function(ctx, &script, &name, script_args)
         }?;
 
-        let dump = BufReader::new(dump);
-        if dump_filename.ends_with(".bz2") {
-            let dump = BzDecoder::new(dump);
-            let dump = BufReader::new(dump);
-            match subcommand {
-                Subcommand::Text => {
-                    process_text_with_function(dump, process_page, namespaces)
-                }
-                Subcommand::Templates => process_templates_with_function(
-                    dump,
-                    process_page,
-                    namespaces,
-                    templates.unwrap(),
-                ),
-                Subcommand::TemplatesAndHeaders => {
-                    process_templates_and_headers_with_function(
-                        dump,
-                        process_page,
-                        namespaces,
-                        templates.unwrap(),
-                    )
-                }
-                Subcommand::CommentsAndHeaders => {
-                    process_comments_and_headers_with_function(
-                        dump,
-                        process_page,
-                        namespaces,
-                    )
-                }
-                Subcommand::Headers => process_headers_with_function(
-                    dump,
-                    process_page,
-                    namespaces,
-                ),
-                _ => Ok(()),
+        match subcommand {
+            Subcommand::Text => {
+                process_text_with_function(parser, process_page, namespaces)
             }
-        } else {
-            match subcommand {
-                Subcommand::Text => {
-                    process_text_with_function(dump, process_page, namespaces)
-                }
-                Subcommand::Templates => process_templates_with_function(
-                    dump,
+            Subcommand::Templates => process_templates_with_function(
+                parser,
+                process_page,
+                namespaces,
+                templates.unwrap(),
+            ),
+            Subcommand::TemplatesAndHeaders => {
+                process_templates_and_headers_with_function(
+                    parser,
                     process_page,
                     namespaces,
                     templates.unwrap(),
-                ),
-                Subcommand::TemplatesAndHeaders => {
-                    process_templates_and_headers_with_function(
-                        dump,
-                        process_page,
-                        namespaces,
-                        templates.unwrap(),
-                    )
-                }
-                Subcommand::CommentsAndHeaders => {
-                    process_comments_and_headers_with_function(
-                        dump,
-                        process_page,
-                        namespaces,
-                    )
-                }
-                Subcommand::Headers => process_headers_with_function(
-                    dump,
+                )
+            }
+            Subcommand::CommentsAndHeaders => {
+                process_comments_and_headers_with_function(
+                    parser,
                     process_page,
                     namespaces,
-                ),
-                _ => Ok(()),
+                )
             }
+            Subcommand::Headers => process_headers_with_function(
+                parser,
+                process_page,
+                namespaces,
+            ),
+            _ => Ok(()),
         }?;
 
         Ok(())
@@ -1,17 +1,15 @@
 use dump_parser::{
     parse_wiki_text::Positioned, wiktionary_configuration, Node,
 };
-use rlua::{
-    Context, Error as LuaError, Function, Result as LuaResult, ToLua, Value,
-};
+use rlua::{Context, Function, Result as LuaResult, ToLua, Value};
 use std::collections::HashSet;
-use std::convert::From;
-use std::io::BufRead;
+use std::io::Read;
 use std::ops::{Deref, DerefMut, Index, IndexMut};
-use std::result::Result as StdResult;
-use dump_parser::Namespace;
+use dump_parser::{DumpParser, Namespace};
+use template_iter::parse_wiki_text_ext::visit::{self, walk, Visit, Visitor};
 
 use crate::exit_with_error;
+use crate::lua_flow::LuaFlow;
 
 const LOWEST_HEADER: usize = 1;
 const HIGHEST_HEADER: usize = 6;
@@ -61,169 +59,93 @@ impl<'a, 'b, 'lua> ToLua<'lua> for &'a HeaderStack<'b> {
     }
 }
 
-// This error type is solely to make it easier to exit from Visitor::do_visit.
-pub enum VisitError {
-    LuaError(LuaError),
-    StopParsing,
-}
-
-impl From<LuaError> for VisitError {
-    fn from(error: LuaError) -> Self {
-        VisitError::LuaError(error)
-    }
-}
-
-struct Visitor<'a> {
-    wikitext: &'a str,
+// Collects the comments under each header and calls `func` with them when
+// the next header or the end of the page is reached.
+struct CommentsUnderHeaders<'a, F> {
     comments: Vec<&'a str>,
     headers: HeaderStack<'a>,
+    func: F,
+    flow: LuaFlow,
 }
 
-impl<'a> Visitor<'a> {
-    pub fn new(wikitext: &'a str) -> Self {
-        Visitor {
-            wikitext,
-            comments: Vec::new(),
-            headers: HeaderStack::new(),
-        }
+impl<'a, F> CommentsUnderHeaders<'a, F>
+where
+    F: FnMut(&[&'a str], &HeaderStack<'a>) -> LuaResult<bool>,
+{
+    fn call_func(&mut self) -> LuaResult<bool> {
+        let result = if self.comments.is_empty() {
+            Ok(true)
+        } else {
+            (self.func)(self.comments.as_slice(), &self.headers)
+        };
+        self.comments.clear();
+        result
     }
+}
 
-    fn visit<F>(&mut self, nodes: &[Node<'a>], func: &mut F) -> LuaResult<bool>
-    where
-        F: FnMut(&[&'a str], &HeaderStack<'a>) -> LuaResult<bool>,
-    {
-        match self.do_visit(nodes, func) {
-            Err(VisitError::LuaError(e)) => return Err(e),
-            Err(VisitError::StopParsing) | Ok(false) => return Ok(false),
-            _ => (),
-        };
-        // Process comments in the last section.
-        if !self.comments.is_empty() {
-            let result = func(&self.comments, &self.headers);
-            self.comments.clear();
-            result
-        } else {
-            Ok(true)
+impl<'a, F> Visitor<'a> for CommentsUnderHeaders<'a, F>
+where
+    F: FnMut(&[&'a str], &HeaderStack<'a>) -> LuaResult<bool>,
+{
+    fn visit_heading(
+        &mut self,
+        _node: &'a Node<'a>,
+        level: u8,
+        nodes: &'a [Node<'a>],
+        context: &visit::Context<'a>,
+    ) -> Visit {
+        // Process all comments under the previously encountered header
+        // (or at the beginning of the page).
+        let result = self.call_func();
+        let level = level as usize;
+        self.headers[level] = Some(nodes.get_text_from(context.wikitext()));
+        for i in level + 1..HIGHEST_HEADER {
+            self.headers[i] = None;
         }
+        self.flow.check(result)
     }
 
-    fn do_visit<F>(
+    fn visit_comment(
         &mut self,
-        nodes: &[Node<'a>],
-        func: &mut F,
-    ) -> StdResult<bool, VisitError>
-    where
-        F: FnMut(&[&'a str], &HeaderStack<'a>) -> LuaResult<bool>,
-    {
-        use dump_parser::Node::*;
-        for node in nodes {
-            match node {
-                DefinitionList { items, .. } => {
-                    for item in items {
-                        self.do_visit(&item.nodes, func)?;
-                    }
-                }
-                Heading { nodes, level, .. } => {
-                    // Process all comments under the previously encountered header
-                    // (or at the beginning of the page).
-                    if !self.comments.is_empty() {
-                        let continue_parsing =
-                            func(self.comments.as_slice(), &self.headers)?;
-                        if !continue_parsing {
-                            return Err(VisitError::StopParsing);
-                        }
-                    }
-                    self.comments.clear();
-                    let level = *level as usize;
-                    self.headers[level] =
-                        Some(&nodes.get_text_from(&self.wikitext));
-                    for i in level + 1..HIGHEST_HEADER {
-                        self.headers[i] = None;
-                    }
-
-                    self.do_visit(&nodes, func)?;
-                }
-                Preformatted { nodes, .. } | Tag { nodes, .. } => {
-                    self.do_visit(&nodes, func)?;
-                }
-                Image { text, .. } | Link { text, .. } => {
-                    self.do_visit(&text, func)?;
-                }
-                OrderedList { items, .. } | UnorderedList { items, .. } => {
-                    for item in items {
-                        self.do_visit(&item.nodes, func)?;
-                    }
-                }
-                Parameter { name, default, .. } => {
-                    if let Some(nodes) = default {
-                        self.do_visit(&nodes, func)?;
-                    }
-                    self.do_visit(&name, func)?;
-                }
-                Table {
-                    attributes,
-                    captions,
-                    rows,
-                    ..
-                } => {
-                    self.do_visit(&attributes, func)?;
-                    for caption in captions {
-                        if let Some(attributes) = &caption.attributes {
-                            self.do_visit(attributes, func)?;
-                        }
-                        self.do_visit(&caption.content, func)?;
-                    }
-                    for row in rows {
-                        self.do_visit(&row.attributes, func)?;
-                        for cell in &row.cells {
-                            if let Some(attributes) = &cell.attributes {
-                                self.do_visit(attributes, func)?;
-                            }
-                            self.do_visit(&cell.content, func)?;
-                        }
-                    }
-                }
-                Template {
-                    name, parameters, ..
-                } => {
-                    self.do_visit(&name, func)?;
-                    for parameter in parameters {
-                        if let Some(name) = &parameter.name {
-                            self.do_visit(name, func)?;
-                        }
-                        self.do_visit(&parameter.value, func)?;
-                    }
-                }
-                Comment { .. } => {
-                    let comment = &self.wikitext[node.start()..node.end()];
-                    self.comments.push(comment);
-                }
-                Bold { .. }
-                | BoldItalic { .. }
-                | Category { .. }
-                | CharacterEntity { .. }
-                | EndTag { .. }
-                | ExternalLink { .. }
-                | HorizontalDivider { .. }
-                | Italic { .. }
-                | MagicWord { .. }
-                | ParagraphBreak { .. }
-                | Redirect { .. }
-                | StartTag { .. }
-                | Text { .. } => {}
-            }
-        }
-        Ok(true)
+        node: &'a Node<'a>,
+        context: &visit::Context<'a>,
+    ) -> Visit {
+        self.comments.push(node.get_text_from(context.wikitext()));
+        Visit::Continue
+    }
+}
+
+// Call `func` with the comments under each header until it returns `false`,
+// and return whether it did not.
+fn visit_comments_under_headers<'a, F>(
+    wikitext: &'a str,
+    nodes: &'a [Node<'a>],
+    func: F,
+) -> LuaResult<bool>
+where
+    F: FnMut(&[&'a str], &HeaderStack<'a>) -> LuaResult<bool>,
+{
+    let mut visitor = CommentsUnderHeaders {
+        comments: Vec::new(),
+        headers: HeaderStack::new(),
+        func,
+        flow: LuaFlow::default(),
+    };
+    let finished = walk(wikitext, nodes, &mut visitor);
+    if !visitor.flow.finish(finished)? {
+        return Ok(false);
     }
+    // Process comments in the last section.
+    visitor.call_func()
 }
 
-pub fn process_comments_and_headers_with_function<R: BufRead>(
-    dump_file: R,
+pub fn process_comments_and_headers_with_function<R: Read>(
+    parser: DumpParser<R>,
     lua_func: Function,
     namespaces: HashSet<Namespace>,
 ) -> LuaResult<()> {
     let configuration = wiktionary_configuration();
-    let parser = dump_parser::parse(dump_file).map(|result| {
+    let parser = parser.map(|result| {
         result.unwrap_or_else(|e| {
             exit_with_error!("Error while parsing dump: {}", e);
         })
@@ -232,14 +154,15 @@ pub fn process_comments_and_headers_with_function<R: BufRead>(
         if namespaces.contains(&page.namespace) {
             let wikitext = &page.text;
             let parser_output = configuration.parse(&page.text);
-            let continue_parsing = Visitor::new(wikitext).visit(
+            let continue_parsing = visit_comments_under_headers(
+                wikitext,
                 &parser_output.nodes,
-                &mut |comments, headers| {
-                    Ok(lua_func.call((
+                |comments, headers| {
+                    lua_func.call((
                         comments.to_vec(),
                         headers,
                         page.title.as_str(),
-                    ))?)
+                    ))
                 },
             )?;
             if !continue_parsing {
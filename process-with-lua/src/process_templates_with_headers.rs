@@ -1,24 +1,25 @@
 use dump_parser::{
     parse_wiki_text::Positioned, wiktionary_configuration, Node,
 };
-use rlua::{
-    Context, Error as LuaError, Function, Result as LuaResult, ToLua, Value,
-};
+use rlua::{Context, Function, Result as LuaResult, ToLua, Value};
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashSet};
-use std::convert::From;
-use std::io::BufRead;
+use std::io::Read;
 use std::ops::{Deref, DerefMut, Index, IndexMut};
 use std::result::Result as StdResult;
 use std::string::ToString;
 use string_wrapper::StringWrapper;
 use template_iter::{
-    normalize_title,
-    parse_wiki_text_ext::template_parameters::{self, ParameterKey},
+    parse_wiki_text_ext::{
+        template_parameters::{self, ParameterKey},
+        visit::{self, walk, Visit, Visitor},
+    },
+    Title,
 };
-use dump_parser::Namespace;
+use dump_parser::{DumpParser, Namespace, Namespaces};
 
 use crate::exit_with_error;
+use crate::lua_flow::LuaFlow;
 
 #[derive(Debug)]
 pub struct BorrowedTemplateWithText<'a> {
@@ -32,17 +33,16 @@ pub struct BorrowedTemplateWithText<'a> {
 impl<'a> BorrowedTemplateWithText<'a> {
     pub fn new(
         wikitext: &'a str,
-        name: &'a str,
+        title: &Title,
         parameters: &'a [dump_parser::Parameter<'a>],
         template: &'a Node,
     ) -> StdResult<Self, &'static str> {
-        let name = if let Ok(Some(name)) =
-            normalize_title(name).map(|n| StringWrapper::from_str_safe(&n))
-        {
-            name
-        } else {
-            return Err("invalid template name");
-        };
+        let name =
+            if let Some(name) = StringWrapper::from_str_safe(title.dbkey()) {
+                name
+            } else {
+                return Err("invalid template name");
+            };
         let parameters = template_parameters::enumerate(parameters)
             .map(|(key, value)| {
                 let key = match key {
@@ -135,194 +135,119 @@ impl<'a, 'b, 'lua> ToLua<'lua> for &'a HeaderStack<'b> {
     }
 }
 
-// This error type is solely to make it easier to exit from Visitor::do_visit.
-// Also used by process_templates.
-pub enum VisitError {
-    LuaError(LuaError),
-    StopParsing,
-}
-
-impl From<LuaError> for VisitError {
-    fn from(error: LuaError) -> Self {
-        VisitError::LuaError(error)
-    }
-}
-
-struct Visitor<'a, 'b> {
-    wikitext: &'a str,
+// Collects the templates under each header and calls `func` with them when
+// the next header or the end of the page is reached.
+struct TemplatesUnderHeaders<'a, 'b, F> {
     templates: Vec<BorrowedTemplateWithText<'a>>,
     headers: HeaderStack<'a>,
-    template_filter: &'b HashSet<String>,
+    template_filter: &'b HashSet<Title>,
+    namespaces: &'b Namespaces,
+    func: F,
+    flow: LuaFlow,
 }
 
-impl<'a, 'b> Visitor<'a, 'b> {
-    pub fn new(
-        wikitext: &'a str,
-        template_filter: &'b HashSet<String>,
-    ) -> Self {
-        Visitor {
-            wikitext,
-            templates: Vec::new(),
-            headers: HeaderStack::new(),
-            template_filter,
-        }
+impl<'a, 'b, F> TemplatesUnderHeaders<'a, 'b, F>
+where
+    F: FnMut(&[BorrowedTemplateWithText], &HeaderStack<'a>) -> LuaResult<bool>,
+{
+    fn call_func(&mut self) -> LuaResult<bool> {
+        let result = if self.templates.is_empty() {
+            Ok(true)
+        } else {
+            (self.func)(self.templates.as_slice(), &self.headers)
+        };
+        self.templates.clear();
+        result
     }
+}
 
-    fn visit<F>(
+impl<'a, 'b, F> Visitor<'a> for TemplatesUnderHeaders<'a, 'b, F>
+where
+    F: FnMut(&[BorrowedTemplateWithText], &HeaderStack<'a>) -> LuaResult<bool>,
+{
+    fn visit_heading(
         &mut self,
+        _node: &'a Node<'a>,
+        level: u8,
         nodes: &'a [Node<'a>],
-        func: &mut F,
-    ) -> LuaResult<bool>
-    where
-        F: FnMut(
-            &[BorrowedTemplateWithText],
-            &HeaderStack<'a>,
-        ) -> LuaResult<bool>,
-    {
-        match self.do_visit(nodes, func) {
-            Err(VisitError::LuaError(e)) => return Err(e),
-            Err(VisitError::StopParsing) | Ok(false) => return Ok(false),
-            _ => (),
-        };
-        // Process templates in the last section.
-        if !self.templates.is_empty() {
-            let result = func(self.templates.as_slice(), &self.headers);
-            self.templates.clear();
-            result
-        } else {
-            Ok(true)
+        context: &visit::Context<'a>,
+    ) -> Visit {
+        // Process all templates under the previously encountered header
+        // (or at the beginning of the page).
+        let result = self.call_func();
+        let level = level as usize;
+        self.headers[level] = Some(nodes.get_text_from(context.wikitext()));
+        for i in level + 1..HIGHEST_HEADER {
+            self.headers[i] = None;
         }
+        self.flow.check(result)
     }
 
-    fn do_visit<F>(
+    fn leave_template(
         &mut self,
-        nodes: &'a [Node<'a>],
-        func: &mut F,
-    ) -> StdResult<bool, VisitError>
-    where
-        F: FnMut(
-            &[BorrowedTemplateWithText],
-            &HeaderStack<'a>,
-        ) -> LuaResult<bool>,
-    {
-        use dump_parser::Node::*;
-        for node in nodes {
-            match node {
-                DefinitionList { items, .. } => {
-                    for item in items {
-                        self.do_visit(&item.nodes, func)?;
-                    }
-                }
-                Heading { nodes, level, .. } => {
-                    // Process all templates under the previously encountered header
-                    // (or at the beginning of the page).
-                    if !self.templates.is_empty() {
-                        let continue_parsing =
-                            func(self.templates.as_slice(), &self.headers)?;
-                        if !continue_parsing {
-                            return Err(VisitError::StopParsing);
-                        }
-                    }
-                    self.templates.clear();
-                    let level = *level as usize;
-                    self.headers[level] =
-                        Some(&nodes.get_text_from(&self.wikitext));
-                    for i in level + 1..HIGHEST_HEADER {
-                        self.headers[i] = None;
-                    }
-
-                    self.do_visit(&nodes, func)?;
-                }
-                Preformatted { nodes, .. } | Tag { nodes, .. } => {
-                    self.do_visit(&nodes, func)?;
-                }
-                Image { text, .. } | Link { text, .. } => {
-                    self.do_visit(&text, func)?;
-                }
-                OrderedList { items, .. } | UnorderedList { items, .. } => {
-                    for item in items {
-                        self.do_visit(&item.nodes, func)?;
-                    }
-                }
-                Parameter { name, default, .. } => {
-                    if let Some(nodes) = default {
-                        self.do_visit(&nodes, func)?;
-                    }
-                    self.do_visit(&name, func)?;
-                }
-                Table {
-                    attributes,
-                    captions,
-                    rows,
-                    ..
-                } => {
-                    self.do_visit(&attributes, func)?;
-                    for caption in captions {
-                        if let Some(attributes) = &caption.attributes {
-                            self.do_visit(attributes, func)?;
-                        }
-                        self.do_visit(&caption.content, func)?;
-                    }
-                    for row in rows {
-                        self.do_visit(&row.attributes, func)?;
-                        for cell in &row.cells {
-                            if let Some(attributes) = &cell.attributes {
-                                self.do_visit(attributes, func)?;
-                            }
-                            self.do_visit(&cell.content, func)?;
-                        }
-                    }
-                }
-                Template {
-                    name, parameters, ..
-                } => {
-                    self.do_visit(&name, func)?;
-                    for parameter in parameters {
-                        if let Some(name) = &parameter.name {
-                            self.do_visit(name, func)?;
-                        }
-                        self.do_visit(&parameter.value, func)?;
-                    }
-                    let name = name.get_text_from(&self.wikitext);
-                    if self.template_filter.contains(name) {
-                        if let Ok(template) = BorrowedTemplateWithText::new(
-                            &self.wikitext,
-                            &name,
-                            &parameters,
-                            &node,
-                        ) {
-                            self.templates.push(template);
-                        }
-                    }
-                }
-                Bold { .. }
-                | BoldItalic { .. }
-                | Category { .. }
-                | CharacterEntity { .. }
-                | Comment { .. }
-                | EndTag { .. }
-                | ExternalLink { .. }
-                | HorizontalDivider { .. }
-                | Italic { .. }
-                | MagicWord { .. }
-                | ParagraphBreak { .. }
-                | Redirect { .. }
-                | StartTag { .. }
-                | Text { .. } => {}
+        node: &'a Node<'a>,
+        name: &'a [Node<'a>],
+        parameters: &'a [dump_parser::Parameter<'a>],
+        context: &visit::Context<'a>,
+    ) -> Visit {
+        let name = name.get_text_from(context.wikitext());
+        let title = Title::from_template_name(name, self.namespaces);
+        if let Some(title) =
+            title.ok().filter(|t| self.template_filter.contains(t))
+        {
+            if let Ok(template) = BorrowedTemplateWithText::new(
+                context.wikitext(),
+                &title,
+                parameters,
+                node,
+            ) {
+                self.templates.push(template);
             }
         }
-        Ok(true)
+        Visit::Continue
+    }
+}
+
+// Call `func` with the templates under each header until it returns `false`,
+// and return whether it did not.
+fn visit_templates_under_headers<'a, F>(
+    wikitext: &'a str,
+    nodes: &'a [Node<'a>],
+    template_filter: &HashSet<Title>,
+    namespaces: &Namespaces,
+    func: F,
+) -> LuaResult<bool>
+where
+    F: FnMut(&[BorrowedTemplateWithText], &HeaderStack<'a>) -> LuaResult<bool>,
+{
+    let mut visitor = TemplatesUnderHeaders {
+        templates: Vec::new(),
+        headers: HeaderStack::new(),
+        template_filter,
+        namespaces,
+        func,
+        flow: LuaFlow::default(),
+    };
+    let finished = walk(wikitext, nodes, &mut visitor);
+    if !visitor.flow.finish(finished)? {
+        return Ok(false);
     }
+    // Process templates in the last section.
+    visitor.call_func()
 }
 
-pub fn process_templates_and_headers_with_function<R: BufRead>(
-    dump_file: R,
+pub fn process_templates_and_headers_with_function<R: Read>(
+    mut parser: DumpParser<R>,
     lua_func: Function,
     namespaces: HashSet<Namespace>,
-    templates: HashSet<String>,
+    templates: HashSet<Title>,
 ) -> LuaResult<()> {
     let configuration = wiktionary_configuration();
-    let parser = dump_parser::parse(dump_file).map(|result| {
+    let namespace_names = match parser.siteinfo() {
+        Ok(siteinfo) => siteinfo.namespaces.clone(),
+        Err(e) => exit_with_error!("Error while parsing dump: {}", e),
+    };
+    let parser = parser.map(|result| {
         result.unwrap_or_else(|e| {
             exit_with_error!("Error while parsing dump: {}", e);
         })
@@ -331,14 +256,17 @@ pub fn process_templates_and_headers_with_function<R: BufRead>(
         if namespaces.contains(&page.namespace) {
             let wikitext = &page.text;
             let parser_output = configuration.parse(&page.text);
-            let continue_parsing = Visitor::new(wikitext, &templates).visit(
+            let continue_parsing = visit_templates_under_headers(
+                wikitext,
                 &parser_output.nodes,
-                &mut |templates, headers| {
-                    Ok(lua_func.call((
+                &templates,
+                &namespace_names,
+                |templates, headers| {
+                    lua_func.call((
                         SliceOfBorrowedTemplateWithText(&templates),
                         headers,
                         page.title.as_str(),
-                    ))?)
+                    ))
                 },
             )?;
             if !continue_parsing {
@@ -0,0 +1,84 @@
+//! Streaming reader for the MediaWiki SQL table dumps, such as `page.sql`,
+//! `redirect.sql` and `categorylinks.sql`.
+//!
+//! [`Rows`] yields typed rows of one of the tables in [`tables`], whose fields
+//! are looked up by column name, so columns added or reordered in newer
+//! versions of MediaWiki are skipped. [`Table`] yields untyped rows of any
+//! table.
+use std::{fmt::Display, io};
+
+mod table;
+pub use table::Table;
+pub mod tables;
+pub use tables::{Row, Rows};
+mod value;
+pub use value::{FromValue, Value};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Syntax {
+        line_number: usize,
+        position: usize,
+    },
+    NoCreateTable,
+    WrongTable {
+        expected: &'static str,
+        found: String,
+    },
+    MissingColumn(&'static str),
+    InvalidValue {
+        column: &'static str,
+        line_number: usize,
+    },
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "failed to read SQL dump: {}", e),
+            Error::Syntax {
+                line_number,
+                position,
+            } => write!(
+                f,
+                "invalid INSERT statement at byte {} of line {}",
+                position, line_number
+            ),
+            Error::NoCreateTable => {
+                write!(f, "SQL dump has no CREATE TABLE statement")
+            }
+            Error::WrongTable { expected, found } => write!(
+                f,
+                "expected dump of table {}, found table {}",
+                expected, found
+            ),
+            Error::MissingColumn(name) => {
+                write!(f, "SQL dump has no column {}", name)
+            }
+            Error::InvalidValue {
+                column,
+                line_number,
+            } => write!(
+                f,
+                "value of column {} has the wrong type in line {}",
+                column, line_number
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Untyped rows of a table dump. The column names are taken from the
+//! `CREATE TABLE` statement, and each `INSERT` statement, which `mysqldump`
+//! puts on a single line, is parsed into rows of values.
+use std::io::BufRead;
+
+use crate::{Error, Value};
+
+/// Iterator over the rows of a table dump.
+pub struct Table<R> {
+    reader: R,
+    name: String,
+    columns: Vec<String>,
+    line: Vec<u8>,
+    line_number: usize,
+    rows: std::vec::IntoIter<Vec<Value>>,
+}
+
+impl<R: BufRead> Table<R> {
+    /// Read the column names from the `CREATE TABLE` statement.
+    pub fn new(reader: R) -> Result<Self, Error> {
+        let mut table = Table {
+            reader,
+            name: String::new(),
+            columns: Vec::new(),
+            line: Vec::new(),
+            line_number: 0,
+            rows: Vec::new().into_iter(),
+        };
+        while !table.line.starts_with(b"CREATE TABLE") {
+            if !table.read_line()? {
+                return Err(Error::NoCreateTable);
+            }
+        }
+        table.name = backquoted(&String::from_utf8_lossy(&table.line))
+            .unwrap_or_default()
+            .to_string();
+        while table.read_line()? && !table.line.starts_with(b")") {
+            let line = String::from_utf8_lossy(&table.line);
+            // Column definitions have the form "  `page_id` int(8) ...".
+            if line.trim_start().starts_with('`') {
+                if let Some(name) = backquoted(&line) {
+                    table.columns.push(name.to_string());
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    /// The name of the table in the `CREATE TABLE` statement.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The index of a column in each row.
+    pub fn column(&self, name: &'static str) -> Result<usize, Error> {
+        self.columns
+            .iter()
+            .position(|column| column == name)
+            .ok_or(Error::MissingColumn(name))
+    }
+
+    /// The number of the line that the last row came from.
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn read_line(&mut self) -> Result<bool, Error> {
+        self.line.clear();
+        self.line_number += 1;
+        Ok(self.reader.read_until(b'\n', &mut self.line)? > 0)
+    }
+}
+
+impl<R: BufRead> Iterator for Table<R> {
+    type Item = Result<Vec<Value>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.rows.next() {
+                return Some(Ok(row));
+            }
+            match self.read_line() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+            if !self.line.starts_with(b"INSERT INTO ") {
+                continue;
+            }
+            let line_number = self.line_number;
+            match parse_insert(&self.line) {
+                Ok(rows) => self.rows = rows.into_iter(),
+                Err(position) => {
+                    return Some(Err(Error::Syntax {
+                        line_number,
+                        position,
+                    }))
+                }
+            }
+        }
+    }
+}
+
+// The first identifier in backquotes.
+fn backquoted(line: &str) -> Option<&str> {
+    line.split('`').nth(1)
+}
+
+const VALUES: &[u8] = b" VALUES ";
+
+// Parse the rows in `INSERT INTO `table` VALUES (...),(...);`.
+// On failure, returns the byte position of the error.
+fn parse_insert(line: &[u8]) -> Result<Vec<Vec<Value>>, usize> {
+    let start = line
+        .windows(VALUES.len())
+        .position(|window| window == VALUES)
+        .ok_or(0usize)?
+        + VALUES.len();
+    let mut parser = ValueParser {
+        input: line,
+        position: start,
+    };
+    let mut rows = Vec::new();
+    loop {
+        parser.expect(b'(')?;
+        let mut row = Vec::new();
+        loop {
+            row.push(parser.value()?);
+            match parser.next_byte() {
+                Some(b',') => {}
+                Some(b')') => break,
+                _ => return Err(parser.position - 1),
+            }
+        }
+        rows.push(row);
+        match parser.next_byte() {
+            Some(b',') => {}
+            Some(b';') => return Ok(rows),
+            _ => return Err(parser.position - 1),
+        }
+    }
+}
+
+struct ValueParser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ValueParser<'a> {
+    fn next_byte(&mut self) -> Option<u8> {
+        let byte = self.input.get(self.position).copied();
+        self.position += 1;
+        byte
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), usize> {
+        match self.next_byte() {
+            Some(byte) if byte == expected => Ok(()),
+            _ => Err(self.position - 1),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, usize> {
+        let rest = &self.input[self.position.min(self.input.len())..];
+        if rest.starts_with(b"NULL") {
+            self.position += 4;
+            Ok(Value::Null)
+        } else if rest.starts_with(b"'") {
+            self.position += 1;
+            self.string()
+        } else {
+            self.number()
+        }
+    }
+
+    fn string(&mut self) -> Result<Value, usize> {
+        let mut bytes = Vec::new();
+        loop {
+            match self.next_byte() {
+                Some(b'\'') => break,
+                Some(b'\\') => bytes.push(match self.next_byte() {
+                    Some(b'0') => b'\0',
+                    Some(b'n') => b'\n',
+                    Some(b'r') => b'\r',
+                    Some(b't') => b'\t',
+                    Some(b'b') => b'\x08',
+                    Some(b'Z') => b'\x1A',
+                    Some(byte) => byte,
+                    None => return Err(self.position - 1),
+                }),
+                Some(byte) => bytes.push(byte),
+                None => return Err(self.position - 1),
+            }
+        }
+        Ok(Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    fn number(&mut self) -> Result<Value, usize> {
+        let start = self.position;
+        let len = self.input[start..]
+            .iter()
+            .take_while(|&&byte| {
+                byte.is_ascii_digit() || b"-+.eE".contains(&byte)
+            })
+            .count();
+        self.position += len;
+        let number = std::str::from_utf8(&self.input[start..self.position])
+            .map_err(|_| start)?;
+        if let Ok(integer) = number.parse() {
+            Ok(Value::Integer(integer))
+        } else {
+            number.parse().map(Value::Float).map_err(|_| start)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Table, Value};
+
+    const PAGE_SQL: &str = "-- MySQL dump 10.19
+/*!40101 SET NAMES utf8mb4 */;
+DROP TABLE IF EXISTS `page`;
+CREATE TABLE `page` (
+  `page_id` int(8) unsigned NOT NULL AUTO_INCREMENT,
+  `page_namespace` int(11) NOT NULL DEFAULT 0,
+  `page_title` varbinary(255) NOT NULL DEFAULT '',
+  `page_random` double unsigned NOT NULL DEFAULT 0,
+  `page_lang` varbinary(35) DEFAULT NULL,
+  PRIMARY KEY (`page_id`),
+  KEY `page_random` (`page_random`)
+) ENGINE=InnoDB DEFAULT CHARSET=binary;
+LOCK TABLES `page` WRITE;
+INSERT INTO `page` VALUES (1,10,'en-noun',0.5,NULL),(2,0,'it\\'s',1e-3,'en');
+INSERT INTO `page` VALUES (3,10,'a\\\\b\\nc',-2.25,NULL);
+UNLOCK TABLES;
+";
+
+    #[test]
+    fn read_table() {
+        let table = Table::new(PAGE_SQL.as_bytes()).unwrap();
+        assert_eq!(table.name(), "page");
+        assert_eq!(
+            table.columns(),
+            &[
+                "page_id",
+                "page_namespace",
+                "page_title",
+                "page_random",
+                "page_lang"
+            ]
+        );
+        assert_eq!(table.column("page_title").unwrap(), 2);
+        assert!(table.column("page_len").is_err());
+        let rows: Vec<_> = table.collect::<Result<_, _>>().unwrap();
+        use Value::*;
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    Integer(1),
+                    Integer(10),
+                    String("en-noun".into()),
+                    Float(0.5),
+                    Null
+                ],
+                vec![
+                    Integer(2),
+                    Integer(0),
+                    String("it's".into()),
+                    Float(0.001),
+                    String("en".into())
+                ],
+                vec![
+                    Integer(3),
+                    Integer(10),
+                    String("a\\b\nc".into()),
+                    Float(-2.25),
+                    Null
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn syntax_error() {
+        let sql = PAGE_SQL.replace("(3,10,", "(3;10,");
+        let result: Result<Vec<_>, _> =
+            Table::new(sql.as_bytes()).unwrap().collect();
+        assert!(result.is_err());
+        assert!(
+            Table::new("INSERT INTO `page` VALUES (1);".as_bytes()).is_err()
+        );
+    }
+}
@@ -0,0 +1,452 @@
+//! Typed rows of the tables in the dumps. Columns that can be `NULL`, or that
+//! have been added or removed in some version of MediaWiki, are `Option`s.
+use std::{io::BufRead, marker::PhantomData};
+
+use crate::{Error, FromValue, Table, Value};
+
+/// A row of a table, with one field per column.
+pub trait Row: Sized {
+    /// The name of the table in the `CREATE TABLE` statement.
+    const TABLE: &'static str;
+    /// The name of the column of each field.
+    const COLUMNS: &'static [&'static str];
+    /// Whether each column must be present in the dump.
+    const REQUIRED: &'static [bool];
+
+    /// Build a row from its values, given the index of each column in the
+    /// values. Returns the name of the first column with an invalid value.
+    fn from_values(
+        values: &mut [Value],
+        indices: &[Option<usize>],
+    ) -> Result<Self, &'static str>;
+}
+
+/// Iterator over the typed rows of a table dump.
+pub struct Rows<T, R> {
+    table: Table<R>,
+    indices: Vec<Option<usize>>,
+    row: PhantomData<T>,
+}
+
+impl<T: Row, R: BufRead> Rows<T, R> {
+    /// Read the `CREATE TABLE` statement and check that it is for `T::TABLE`
+    /// and has all the required columns.
+    pub fn new(reader: R) -> Result<Self, Error> {
+        let table = Table::new(reader)?;
+        if table.name() != T::TABLE {
+            return Err(Error::WrongTable {
+                expected: T::TABLE,
+                found: table.name().to_string(),
+            });
+        }
+        let indices = T::COLUMNS
+            .iter()
+            .zip(T::REQUIRED)
+            .map(|(&column, &required)| match table.column(column) {
+                Ok(index) => Ok(Some(index)),
+                Err(_) if !required => Ok(None),
+                Err(e) => Err(e),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Rows {
+            table,
+            indices,
+            row: PhantomData,
+        })
+    }
+}
+
+impl<T: Row, R: BufRead> Iterator for Rows<T, R> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut values = match self.table.next()? {
+            Ok(values) => values,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(
+            T::from_values(&mut values, &self.indices).map_err(|column| {
+                Error::InvalidValue {
+                    column,
+                    line_number: self.table.line_number(),
+                }
+            }),
+        )
+    }
+}
+
+fn take(values: &mut [Value], index: Option<usize>) -> Value {
+    index
+        .and_then(|index| values.get_mut(index))
+        .map(std::mem::take)
+        .unwrap_or_default()
+}
+
+macro_rules! tables {
+    (
+        $(
+            $(#[$attribute:meta])*
+            $name:ident = $table:literal {
+                $(
+                    $(#[$field_attribute:meta])*
+                    $field:ident: $type:ty = $column:literal,
+                )+
+            }
+        )+
+    ) => {
+        $(
+            $(#[$attribute])*
+            #[derive(Clone, Debug, PartialEq)]
+            pub struct $name {
+                $(
+                    $(#[$field_attribute])*
+                    pub $field: $type,
+                )+
+            }
+
+            impl Row for $name {
+                const TABLE: &'static str = $table;
+                const COLUMNS: &'static [&'static str] = &[$($column),+];
+                const REQUIRED: &'static [bool] =
+                    &[$(!<$type as FromValue>::NULLABLE),+];
+
+                fn from_values(
+                    values: &mut [Value],
+                    indices: &[Option<usize>],
+                ) -> Result<Self, &'static str> {
+                    let mut indices = indices.iter().copied();
+                    Ok($name {
+                        $(
+                            $field: FromValue::from_value(take(
+                                values,
+                                indices.next().flatten(),
+                            ))
+                            .ok_or($column)?,
+                        )+
+                    })
+                }
+            }
+        )+
+    };
+}
+
+tables! {
+    Babel = "babel" {
+        user: u64 = "babel_user",
+        lang: String = "babel_lang",
+        level: String = "babel_level",
+    }
+
+    Category = "category" {
+        id: u64 = "cat_id",
+        title: String = "cat_title",
+        pages: i64 = "cat_pages",
+        subcats: i64 = "cat_subcats",
+        files: i64 = "cat_files",
+    }
+
+    CategoryLinks = "categorylinks" {
+        from: u64 = "cl_from",
+        to: String = "cl_to",
+        sortkey: String = "cl_sortkey",
+        timestamp: String = "cl_timestamp",
+        sortkey_prefix: String = "cl_sortkey_prefix",
+        collation: Option<String> = "cl_collation",
+        /// `page`, `subcat` or `file`.
+        kind: String = "cl_type",
+    }
+
+    ChangeTag = "change_tag" {
+        id: u64 = "ct_id",
+        rc_id: Option<u64> = "ct_rc_id",
+        log_id: Option<u64> = "ct_log_id",
+        rev_id: Option<u64> = "ct_rev_id",
+        params: Option<String> = "ct_params",
+        tag_id: u64 = "ct_tag_id",
+    }
+
+    ChangeTagDef = "change_tag_def" {
+        id: u64 = "ctd_id",
+        name: String = "ctd_name",
+        user_defined: bool = "ctd_user_defined",
+        count: u64 = "ctd_count",
+    }
+
+    ExternalLinks = "externallinks" {
+        id: u64 = "el_id",
+        from: u64 = "el_from",
+        /// Removed in MediaWiki 1.41.
+        to: Option<String> = "el_to",
+        index: Option<String> = "el_index",
+        index_60: Option<String> = "el_index_60",
+        /// Added in MediaWiki 1.41.
+        to_domain_index: Option<String> = "el_to_domain_index",
+        to_path: Option<String> = "el_to_path",
+    }
+
+    GeoTags = "geo_tags" {
+        id: u64 = "gt_id",
+        page_id: u64 = "gt_page_id",
+        globe: String = "gt_globe",
+        primary: bool = "gt_primary",
+        lat_int: Option<i64> = "gt_lat_int",
+        lon_int: Option<i64> = "gt_lon_int",
+        lat: Option<f64> = "gt_lat",
+        lon: Option<f64> = "gt_lon",
+        dim: Option<i64> = "gt_dim",
+        kind: Option<String> = "gt_type",
+        name: Option<String> = "gt_name",
+        country: Option<String> = "gt_country",
+        region: Option<String> = "gt_region",
+    }
+
+    Image = "image" {
+        name: String = "img_name",
+        size: u64 = "img_size",
+        width: i64 = "img_width",
+        height: i64 = "img_height",
+        metadata: String = "img_metadata",
+        bits: i64 = "img_bits",
+        media_type: Option<String> = "img_media_type",
+        major_mime: String = "img_major_mime",
+        minor_mime: String = "img_minor_mime",
+        description_id: u64 = "img_description_id",
+        actor: u64 = "img_actor",
+        timestamp: String = "img_timestamp",
+        sha1: String = "img_sha1",
+        deleted: Option<i64> = "img_deleted",
+    }
+
+    ImageLinks = "imagelinks" {
+        from: u64 = "il_from",
+        from_namespace: Option<i32> = "il_from_namespace",
+        to: String = "il_to",
+    }
+
+    IwLinks = "iwlinks" {
+        from: u64 = "iwl_from",
+        prefix: String = "iwl_prefix",
+        title: String = "iwl_title",
+    }
+
+    LangLinks = "langlinks" {
+        from: u64 = "ll_from",
+        lang: String = "ll_lang",
+        title: String = "ll_title",
+    }
+
+    Page = "page" {
+        id: u64 = "page_id",
+        namespace: i32 = "page_namespace",
+        title: String = "page_title",
+        /// Removed in MediaWiki 1.38.
+        restrictions: Option<String> = "page_restrictions",
+        is_redirect: bool = "page_is_redirect",
+        is_new: bool = "page_is_new",
+        random: f64 = "page_random",
+        touched: String = "page_touched",
+        links_updated: Option<String> = "page_links_updated",
+        latest: u64 = "page_latest",
+        len: u64 = "page_len",
+        content_model: Option<String> = "page_content_model",
+        lang: Option<String> = "page_lang",
+    }
+
+    PageRestrictions = "page_restrictions" {
+        page: u64 = "pr_page",
+        kind: String = "pr_type",
+        level: String = "pr_level",
+        cascade: i64 = "pr_cascade",
+        user: Option<u64> = "pr_user",
+        expiry: Option<String> = "pr_expiry",
+        id: u64 = "pr_id",
+    }
+
+    PageLinks = "pagelinks" {
+        from: u64 = "pl_from",
+        from_namespace: i32 = "pl_from_namespace",
+        /// Replaced by `pl_target_id` in MediaWiki 1.43.
+        namespace: Option<i32> = "pl_namespace",
+        title: Option<String> = "pl_title",
+        target_id: Option<u64> = "pl_target_id",
+    }
+
+    ProtectedTitles = "protected_titles" {
+        namespace: i32 = "pt_namespace",
+        title: String = "pt_title",
+        user: u64 = "pt_user",
+        reason_id: u64 = "pt_reason_id",
+        timestamp: String = "pt_timestamp",
+        expiry: String = "pt_expiry",
+        create_perm: String = "pt_create_perm",
+    }
+
+    Redirect = "redirect" {
+        from: u64 = "rd_from",
+        namespace: i32 = "rd_namespace",
+        title: String = "rd_title",
+        interwiki: Option<String> = "rd_interwiki",
+        fragment: Option<String> = "rd_fragment",
+    }
+
+    SiteStats = "site_stats" {
+        row_id: u64 = "ss_row_id",
+        total_edits: Option<u64> = "ss_total_edits",
+        good_articles: Option<u64> = "ss_good_articles",
+        total_pages: Option<u64> = "ss_total_pages",
+        users: Option<u64> = "ss_users",
+        images: Option<u64> = "ss_images",
+        active_users: Option<u64> = "ss_active_users",
+    }
+
+    Sites = "sites" {
+        id: u64 = "site_id",
+        global_key: String = "site_global_key",
+        kind: String = "site_type",
+        group: String = "site_group",
+        source: String = "site_source",
+        language: String = "site_language",
+        protocol: String = "site_protocol",
+        domain: String = "site_domain",
+        data: String = "site_data",
+        forward: bool = "site_forward",
+        config: String = "site_config",
+    }
+
+    TemplateLinks = "templatelinks" {
+        from: u64 = "tl_from",
+        /// Replaced by `tl_target_id` in MediaWiki 1.38.
+        namespace: Option<i32> = "tl_namespace",
+        title: Option<String> = "tl_title",
+        from_namespace: i32 = "tl_from_namespace",
+        target_id: Option<u64> = "tl_target_id",
+    }
+
+    UserFormerGroups = "user_former_groups" {
+        user: u64 = "ufg_user",
+        group: String = "ufg_group",
+    }
+
+    UserGroups = "user_groups" {
+        user: u64 = "ug_user",
+        group: String = "ug_group",
+        expiry: Option<String> = "ug_expiry",
+    }
+
+    WbcEntityUsage = "wbc_entity_usage" {
+        row_id: u64 = "eu_row_id",
+        entity_id: String = "eu_entity_id",
+        aspect: String = "eu_aspect",
+        page_id: u64 = "eu_page_id",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Page, Redirect, Rows};
+    use crate::Error;
+
+    const REDIRECT_SQL: &str = "CREATE TABLE `redirect` (
+  `rd_from` int(8) unsigned NOT NULL DEFAULT 0,
+  `rd_namespace` int(11) NOT NULL DEFAULT 0,
+  `rd_title` varbinary(255) NOT NULL DEFAULT '',
+  `rd_interwiki` varbinary(32) DEFAULT NULL,
+  `rd_fragment` varbinary(255) DEFAULT NULL,
+  PRIMARY KEY (`rd_from`)
+) ENGINE=InnoDB DEFAULT CHARSET=binary;
+INSERT INTO `redirect` VALUES (2,10,'link','',NULL),(3,0,'it\\'s','w','a');
+";
+
+    #[test]
+    fn typed_rows() {
+        let rows: Vec<_> = Rows::<Redirect, _>::new(REDIRECT_SQL.as_bytes())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Redirect {
+                    from: 2,
+                    namespace: 10,
+                    title: "link".into(),
+                    interwiki: Some("".into()),
+                    fragment: None,
+                },
+                Redirect {
+                    from: 3,
+                    namespace: 0,
+                    title: "it's".into(),
+                    interwiki: Some("w".into()),
+                    fragment: Some("a".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn optional_columns() {
+        // The columns of MediaWiki 1.38, in a different order.
+        let page_sql = "CREATE TABLE `page` (
+  `page_id` int(8) unsigned NOT NULL AUTO_INCREMENT,
+  `page_namespace` int(11) NOT NULL DEFAULT 0,
+  `page_title` varbinary(255) NOT NULL DEFAULT '',
+  `page_is_redirect` tinyint(1) unsigned NOT NULL DEFAULT 0,
+  `page_is_new` tinyint(1) unsigned NOT NULL DEFAULT 0,
+  `page_random` double unsigned NOT NULL DEFAULT 0,
+  `page_touched` binary(14) NOT NULL,
+  `page_links_updated` varbinary(14) DEFAULT NULL,
+  `page_latest` int(8) unsigned NOT NULL DEFAULT 0,
+  `page_len` int(8) unsigned NOT NULL DEFAULT 0,
+  `page_lang` varbinary(35) DEFAULT NULL,
+  `page_content_model` varbinary(32) DEFAULT NULL
+) ENGINE=InnoDB DEFAULT CHARSET=binary;
+INSERT INTO `page` VALUES (1,10,'en-noun',0,1,0.25,'20210101000000',\
+NULL,123,4567,NULL,'wikitext');
+";
+        let mut rows = Rows::<Page, _>::new(page_sql.as_bytes()).unwrap();
+        assert_eq!(
+            rows.next().unwrap().unwrap(),
+            Page {
+                id: 1,
+                namespace: 10,
+                title: "en-noun".into(),
+                restrictions: None,
+                is_redirect: false,
+                is_new: true,
+                random: 0.25,
+                touched: "20210101000000".into(),
+                links_updated: None,
+                latest: 123,
+                len: 4567,
+                content_model: Some("wikitext".into()),
+                lang: None,
+            }
+        );
+        assert!(rows.next().is_none());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            Rows::<Page, _>::new(REDIRECT_SQL.as_bytes()),
+            Err(Error::WrongTable {
+                expected: "page",
+                ..
+            })
+        ));
+        let sql = REDIRECT_SQL.replace("  `rd_title`", "  `rd_name`");
+        assert!(matches!(
+            Rows::<Redirect, _>::new(sql.as_bytes()),
+            Err(Error::MissingColumn("rd_title"))
+        ));
+        let sql = REDIRECT_SQL.replace("(2,10,'link'", "(2,'10','link'");
+        assert!(matches!(
+            Rows::<Redirect, _>::new(sql.as_bytes()).unwrap().next(),
+            Some(Err(Error::InvalidValue {
+                column: "rd_namespace",
+                line_number: 9
+            }))
+        ));
+    }
+}
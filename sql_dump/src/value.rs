@@ -0,0 +1,80 @@
+use std::convert::TryFrom;
+
+/// A value in an `INSERT` statement.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Null,
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+/// Conversion of a column's value to the type of a field in a row.
+pub trait FromValue: Sized {
+    /// Whether the column may be `NULL` or missing from the dump.
+    const NULLABLE: bool = false;
+
+    /// Returns `None` if the value has the wrong type.
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from_value_for_integers {
+    ($($integer:ty),+) => {
+        $(
+            impl FromValue for $integer {
+                fn from_value(value: Value) -> Option<Self> {
+                    match value {
+                        Value::Integer(integer) => {
+                            <$integer>::try_from(integer).ok()
+                        }
+                        _ => None,
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_from_value_for_integers!(i32, i64, u32, u64);
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Float(float) => Some(float),
+            Value::Integer(integer) => Some(integer as f64),
+            _ => None,
+        }
+    }
+}
+
+/// `tinyint(1)` columns, which contain `0` or `1`.
+impl FromValue for bool {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Integer(0) => Some(false),
+            Value::Integer(1) => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    const NULLABLE: bool = true;
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
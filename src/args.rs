@@ -1,4 +1,3 @@
-use bzip2::bufread::BzDecoder;
 use std::{
     collections::HashMap,
     convert::{AsRef, From},
@@ -6,15 +5,21 @@ use std::{
     fs::File,
     io::{BufRead, BufReader, Read},
     path::{Path, PathBuf},
-    rc::Rc,
     result::Result as StdResult,
     str::FromStr,
+    sync::Arc,
 };
+use serde::Deserialize;
 use structopt::clap::{AppSettings::ColoredHelp, Shell};
 use structopt::StructOpt;
-use dump_parser::Namespace;
+use dump_parser::{
+    input, multistream::Multistream, parse as parse_dump,
+    site_config::SiteConfig, Configuration, DumpParser, Namespace, Namespaces,
+    Page, SiteInfo,
+};
 
 use crate::error::{Error, Result};
+use crate::template_redirects::TemplateRedirects;
 
 #[derive(StructOpt)]
 #[structopt(
@@ -33,18 +38,141 @@ pub struct Args {
 enum Command {
     #[structopt(setting(ColoredHelp))]
     DumpParsedTemplates {
-        #[structopt(long, short)]
-        /// format: cbor (CBOR stream) or json (JSON Lines)
+        #[structopt(flatten)]
+        templates: TemplateDumpArgs,
+        #[structopt(
+            long,
+            conflicts_with_all = &[
+                "pages",
+                "skip-redirects",
+                "only-redirects",
+                "since",
+                "all-revisions",
+                "titles",
+                "ids",
+            ],
+        )]
+        /// file that keeps the templates of each page between runs, so that
+        /// only the pages that changed are parsed, as when the input is an
+        /// adds-changes dump; created if it does not exist; not supported
+        /// with the parquet format or with options that leave out pages
+        cache: Option<PathBuf>,
+        #[structopt(long, requires = "cache")]
+        /// the input is an adds-changes dump, so the cached pages that are
+        /// not in it are kept; otherwise they are removed from the cache as
+        /// deleted pages
+        adds_changes: bool,
+        #[structopt(flatten)]
+        dump_args: DumpArgs,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// do the jobs in a TOML file in a single pass over the dump
+    Run {
+        /// TOML file with a [dump] table with the options shared by the
+        /// other subcommands and a [[jobs]] array of all-headers,
+        /// filter-headers and dump-parsed-templates jobs
+        job_file: PathBuf,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// print the templates with their redirects and output files
+    AddTemplateRedirects {
+        #[structopt(long, short, default_value = "cbor")]
+        /// format whose file extension is used for default output files
         format: SerializationFormat,
         #[structopt(long = "templates", short, required = true)]
         /// path to file containing template names with optional tab and output filepath
         template_filepaths: Vec<PathBuf>,
-        #[structopt(long, short = "I")]
-        /// whether to include source code of templates
-        include_text: bool,
-        #[structopt(long = "template-normalizations", short = "T")]
-        /// JSON file mapping from template name to an array of aliases.
-        template_normalization_filepath: Option<PathBuf>,
+        #[structopt(long)]
+        /// path to page.sql[.gz]
+        page_sql: PathBuf,
+        #[structopt(long)]
+        /// path to redirect.sql[.gz]
+        redirect_sql: PathBuf,
+        #[structopt(long = "input", short = "i")]
+        /// dump whose siteinfo gives the namespaces and case settings used to
+        /// normalize template names [default: as for dump-parsed-templates]
+        dump_filepath: Option<PathBuf>,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// list the modules and functions called with {{#invoke:}} on each page
+    /// as JSON Lines
+    DumpInvocations {
+        #[structopt(long = "module")]
+        /// only list invocations of this module; can be given more than once
+        modules: Vec<String>,
+        #[structopt(flatten)]
+        dump_args: DumpArgs,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// print the wikitext of the language sections of each page as JSON Lines
+    ExtractSections {
+        #[structopt(long = "language", short, required = true)]
+        /// name of the language in the level-2 header; can be given more
+        /// than once
+        languages: Vec<String>,
+        #[structopt(flatten)]
+        dump_args: DumpArgs,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// print the headword-line template and the definitions, examples and
+    /// quotations in each part-of-speech section as JSON Lines
+    DumpEntries {
+        #[structopt(long = "language", short)]
+        /// only print entries in this language; can be given more than once
+        languages: Vec<String>,
+        #[structopt(flatten)]
+        dump_args: DumpArgs,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// print the translations in the translation tables of each page as
+    /// JSON Lines
+    DumpTranslations {
+        #[structopt(flatten)]
+        dump_args: DumpArgs,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// print the IPA, audio, rhymes, hyphenation and homophones templates in
+    /// the Pronunciation sections of each language section
+    DumpPronunciations {
+        #[structopt(long, short, default_value = "json")]
+        /// format: cbor (CBOR stream) or json (JSON Lines)
+        format: SerializationFormat,
+        #[structopt(flatten)]
+        dump_args: DumpArgs,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// print the etymological relations in the Etymology sections and the
+    /// trees in the Descendants sections of each language section as JSON
+    /// Lines
+    DumpEtymologies {
+        #[structopt(flatten)]
+        dump_args: DumpArgs,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// print the internal links, categories, interwiki links and external
+    /// links in each page as JSON Lines
+    DumpLinks {
+        #[structopt(long)]
+        /// whether to include the language section that each link is in
+        include_language: bool,
+        #[structopt(flatten)]
+        dump_args: DumpArgs,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// print the title and target of each redirect in the namespaces, separated
+    /// by a tab
+    DumpRedirects {
+        #[structopt(flatten)]
+        dump_args: DumpArgs,
+    },
+    #[structopt(setting(ColoredHelp))]
+    /// print the link targets that do not exist, by the number of pages
+    /// linking to them, and the entries that no page links to, as JSON; the
+    /// dump is read twice, first to collect the titles of all pages
+    LinkReport {
+        #[structopt(long, short = "P")]
+        /// print pretty JSON
+        pretty: bool,
         #[structopt(flatten)]
         dump_args: DumpArgs,
     },
@@ -69,12 +197,215 @@ enum Command {
         dump_args: DumpArgs,
     },
     #[structopt(setting(ColoredHelp))]
+    /// compare a siteinfo JSON file with the built-in parser configuration
+    DiffSiteConfig {
+        /// output of the MediaWiki API's siteinfo query
+        site_config: PathBuf,
+    },
+    #[structopt(setting(ColoredHelp))]
     Completions { shell: Shell },
 }
 
+// The options of dump-parsed-templates, also used in job files.
+#[derive(StructOpt, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TemplateDumpArgs {
+    #[structopt(long, short)]
+    /// format: cbor (CBOR stream), json (JSON Lines) or parquet (a row for
+    /// each template)
+    format: SerializationFormat,
+    #[structopt(long = "templates", short, required = true)]
+    #[serde(rename = "templates")]
+    /// path to file containing template names with optional tab and output filepath
+    template_filepaths: Vec<PathBuf>,
+    #[structopt(long, short = "I")]
+    #[serde(default)]
+    /// whether to include source code of templates
+    include_text: bool,
+    #[structopt(long, short = "H")]
+    #[serde(default)]
+    /// whether to include the headers enclosing each template, such as
+    /// ["English", "Etymology 1", "Noun"]
+    include_headers: bool,
+    #[structopt(long)]
+    #[serde(default)]
+    /// whether to include the byte offsets and line number of each
+    /// template and the name of the template it is nested in, if any
+    include_positions: bool,
+    #[structopt(long)]
+    #[serde(default)]
+    /// whether to include the ID and timestamp of the revision of each page
+    include_revision: bool,
+    #[structopt(long)]
+    #[serde(default)]
+    /// whether to parse parameter values into lists of text, template,
+    /// link and comment segments instead of giving their wikitext; not
+    /// supported with the parquet format
+    structured_parameters: bool,
+    #[structopt(long = "template-normalizations", short = "T")]
+    #[serde(rename = "template-normalizations")]
+    /// JSON file mapping from template name to an array of aliases.
+    template_normalization_filepath: Option<PathBuf>,
+    #[structopt(long, requires = "redirect-sql")]
+    /// page.sql[.gz] from the same dump, used with --redirect-sql to also
+    /// dump redirects to the templates into the same files
+    page_sql: Option<PathBuf>,
+    #[structopt(long, requires = "page-sql")]
+    /// redirect.sql[.gz] from the same dump
+    redirect_sql: Option<PathBuf>,
+}
+
+impl TemplateDumpArgs {
+    fn read(self) -> Result<TemplateDump> {
+        let TemplateDumpArgs {
+            format,
+            template_filepaths,
+            include_text,
+            include_headers,
+            include_positions,
+            include_revision,
+            structured_parameters,
+            template_normalization_filepath,
+            page_sql,
+            redirect_sql,
+        } = self;
+        if structured_parameters && format == SerializationFormat::Parquet {
+            return Err(Error::ParquetNotSupported("structured parameters"));
+        }
+        let redirects = match (page_sql, redirect_sql) {
+            (Some(page_sql), Some(redirect_sql)) => {
+                Some(read_template_redirects(&page_sql, &redirect_sql)?)
+            }
+            _ => None,
+        };
+        Ok(TemplateDump {
+            format,
+            files: collect_template_names_and_files(&template_filepaths)?,
+            template_normalizations: template_normalization_filepath
+                .as_deref()
+                .map(read_template_normalizations)
+                .transpose()?,
+            include_text,
+            include_headers,
+            include_positions,
+            include_revision,
+            structured_parameters,
+            redirects,
+        })
+    }
+}
+
+// A job in a job file.
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+enum JobArgs {
+    #[serde(rename_all = "kebab-case")]
+    AllHeaders {
+        #[serde(default = "default_namespaces")]
+        namespaces: Vec<String>,
+        output: PathBuf,
+        #[serde(default)]
+        pretty: bool,
+    },
+    #[serde(rename_all = "kebab-case")]
+    FilterHeaders {
+        #[serde(default = "default_namespaces")]
+        namespaces: Vec<String>,
+        output: PathBuf,
+        top_level_headers: Vec<PathBuf>,
+        other_headers: Vec<PathBuf>,
+        #[serde(default)]
+        pretty: bool,
+    },
+    DumpParsedTemplates {
+        #[serde(default = "default_namespaces")]
+        namespaces: Vec<String>,
+        #[serde(flatten)]
+        templates: TemplateDumpArgs,
+    },
+}
+
+fn default_namespaces() -> Vec<String> {
+    vec!["main".into()]
+}
+
+impl JobArgs {
+    fn namespaces(&self) -> &[String] {
+        match self {
+            JobArgs::AllHeaders { namespaces, .. }
+            | JobArgs::FilterHeaders { namespaces, .. }
+            | JobArgs::DumpParsedTemplates { namespaces, .. } => namespaces,
+        }
+    }
+
+    fn read(self, namespace_names: &Namespaces) -> Result<Job> {
+        let namespaces = parse_namespaces(self.namespaces(), namespace_names)?;
+        let job = match self {
+            JobArgs::AllHeaders { output, pretty, .. } => Job::AllHeaders {
+                namespaces,
+                output,
+                pretty,
+            },
+            JobArgs::FilterHeaders {
+                output,
+                top_level_headers,
+                other_headers,
+                pretty,
+                ..
+            } => Job::FilterHeaders {
+                namespaces,
+                output,
+                top_level_headers: collect_lines(top_level_headers)?,
+                other_headers: collect_lines(other_headers)?,
+                pretty,
+            },
+            JobArgs::DumpParsedTemplates { templates, .. } => {
+                Job::DumpParsedTemplates {
+                    namespaces,
+                    templates: templates.read()?,
+                }
+            }
+        };
+        Ok(job)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct JobFile {
+    #[serde(default)]
+    dump: DumpArgs,
+    jobs: Vec<JobArgs>,
+}
+
+fn read_job_file(path: &Path) -> Result<JobFile> {
+    let text = std::fs::read_to_string(path).map_err(|e| Error::IoError {
+        action: "read",
+        path: path.into(),
+        cause: e,
+    })?;
+    toml::from_str(&text).map_err(|e| Error::JobFile {
+        path: path.into(),
+        cause: e,
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SerializationFormat {
     Cbor,
     Json,
+    Parquet,
+}
+
+impl SerializationFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SerializationFormat::Cbor => ".cbor",
+            SerializationFormat::Json => ".jsonl",
+            SerializationFormat::Parquet => ".parquet",
+        }
+    }
 }
 
 impl FromStr for SerializationFormat {
@@ -84,23 +415,69 @@ impl FromStr for SerializationFormat {
         let format = match s.to_lowercase().as_str() {
             "json" => SerializationFormat::Json,
             "cbor" => SerializationFormat::Cbor,
+            "parquet" => SerializationFormat::Parquet,
             _ => return Err("unrecognized format"),
         };
         Ok(format)
     }
 }
 
-#[derive(StructOpt, Clone)]
+// In job files, the namespaces are given for each job instead.
+#[derive(StructOpt, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
 struct DumpArgs {
     #[structopt(long, short, value_delimiter = ",", default_value = "main")]
-    /// namespace to process
-    namespaces: Vec<Namespace>,
+    #[serde(skip)]
+    /// namespace to process, by name, alias or number, as listed in the
+    /// dump's siteinfo
+    namespaces: Vec<String>,
     #[structopt(short, long)]
     /// number of pages to process [default: unlimited]
     pages: Option<usize>,
-    /// path to pages-articles.xml[.bz2] or pages-meta-current.xml[.bz2]
+    #[structopt(long, conflicts_with = "only-redirects")]
+    #[serde(default)]
+    /// skip pages that are redirects
+    skip_redirects: bool,
+    #[structopt(long)]
+    #[serde(default)]
+    /// only process pages that are redirects
+    only_redirects: bool,
+    #[structopt(long)]
+    /// only process pages whose revision was made at or after this time,
+    /// like 2020-07-28 or 2020-07-28T12:00:00Z
+    since: Option<String>,
+    #[structopt(long, conflicts_with = "index")]
+    #[serde(default)]
+    /// process each revision in a pages-meta-history dump as a separate
+    /// page; revisions count as pages for --pages
+    all_revisions: bool,
+    /// path to pages-articles.xml or pages-meta-current.xml, optionally
+    /// compressed with bzip2, gzip, xz or zstd, or - for standard input
     #[structopt(long = "input", short = "i")]
+    #[serde(rename = "input")]
     dump_filepath: Option<PathBuf>,
+    #[structopt(long, short = "j")]
+    /// number of threads that parse pages, and with --index also decompress
+    /// streams, which takes half of them [default: number of CPUs]
+    threads: Option<usize>,
+    #[structopt(long)]
+    /// path to pages-articles-multistream-index.txt[.bz2]; the input must be
+    /// the corresponding multistream dump, whose streams are then decompressed
+    /// in parallel [default input: pages-articles-multistream.xml.bz2]
+    index: Option<PathBuf>,
+    #[structopt(long, requires = "index")]
+    /// file of newline-separated titles; only the streams of the multistream
+    /// dump containing these pages are read, and only these pages are processed
+    titles: Option<PathBuf>,
+    #[structopt(long, requires = "index", conflicts_with = "titles")]
+    /// file of newline-separated page IDs, used like --titles
+    ids: Option<PathBuf>,
+    #[structopt(long)]
+    /// output of the MediaWiki API's siteinfo query (siprop=general|namespaces|
+    /// namespacealiases|extensiontags|magicwords|protocols), used to configure
+    /// the wikitext parser and for namespace aliases, which the dump does not
+    /// list [default: built-in English Wiktionary configuration]
+    site_config: Option<PathBuf>,
 }
 
 pub struct Opts {
@@ -109,7 +486,51 @@ pub struct Opts {
 }
 
 pub enum CommandData {
-    DumpParsedTemplates(DumpParsedTemplates),
+    DumpParsedTemplates {
+        templates: TemplateDump,
+        cache: Option<PathBuf>,
+        adds_changes: bool,
+        dump_options: DumpOptions,
+    },
+    Run {
+        jobs: Vec<Job>,
+        dump_options: DumpOptions,
+    },
+    DumpInvocations {
+        modules: Vec<String>,
+        dump_options: DumpOptions,
+    },
+    ExtractSections {
+        languages: Vec<String>,
+        dump_options: DumpOptions,
+    },
+    DumpEntries {
+        languages: Vec<String>,
+        dump_options: DumpOptions,
+    },
+    DumpTranslations {
+        dump_options: DumpOptions,
+    },
+    DumpPronunciations {
+        format: SerializationFormat,
+        dump_options: DumpOptions,
+    },
+    DumpEtymologies {
+        dump_options: DumpOptions,
+    },
+    DumpLinks {
+        include_language: bool,
+        dump_options: DumpOptions,
+    },
+    DumpRedirects {
+        dump_options: DumpOptions,
+    },
+    LinkReport {
+        pretty: bool,
+        /// The dump read in the first pass.
+        titles: DumpSource,
+        dump_options: DumpOptions,
+    },
     AllHeaders {
         pretty: bool,
         dump_options: DumpOptions,
@@ -120,23 +541,175 @@ pub enum CommandData {
         pretty: bool,
         dump_options: DumpOptions,
     },
+    AddTemplateRedirects {
+        templates: Vec<(String, Option<String>)>,
+        format: SerializationFormat,
+        redirects: TemplateRedirects,
+        namespaces: Namespaces,
+    },
+    DiffSiteConfig {
+        path: PathBuf,
+        site_config: SiteConfig,
+    },
     Completions {
         shell: Shell,
     },
 }
 
-pub struct DumpParsedTemplates {
+pub struct TemplateDump {
     pub format: SerializationFormat,
     pub files: Vec<(String, Option<String>)>,
-    pub template_normalizations: Option<HashMap<String, Rc<str>>>,
+    pub template_normalizations: Option<HashMap<String, Arc<str>>>,
     pub include_text: bool,
-    pub dump_options: DumpOptions,
+    pub include_headers: bool,
+    pub include_positions: bool,
+    pub include_revision: bool,
+    pub structured_parameters: bool,
+    pub redirects: Option<TemplateRedirects>,
+}
+
+pub enum Job {
+    AllHeaders {
+        namespaces: Vec<Namespace>,
+        output: PathBuf,
+        pretty: bool,
+    },
+    FilterHeaders {
+        namespaces: Vec<Namespace>,
+        output: PathBuf,
+        top_level_headers: Vec<String>,
+        other_headers: Vec<String>,
+        pretty: bool,
+    },
+    DumpParsedTemplates {
+        namespaces: Vec<Namespace>,
+        templates: TemplateDump,
+    },
 }
 
 pub struct DumpOptions {
     pub pages: usize,
     pub namespaces: Vec<Namespace>,
-    pub dump_file: Box<dyn Read>,
+    pub configuration: Configuration,
+    pub dump: DumpSource,
+    pub threads: usize,
+    pub siteinfo: SiteInfo,
+}
+
+pub type Pages = Box<dyn Iterator<Item = Result<Page>> + Send>;
+
+/// Which pages to process, depending on whether they are redirects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Redirects {
+    Include,
+    Skip,
+    Only,
+}
+
+impl Redirects {
+    fn includes(self, page: &Page) -> bool {
+        match self {
+            Redirects::Include => true,
+            Redirects::Skip => !page.is_redirect(),
+            Redirects::Only => page.is_redirect(),
+        }
+    }
+}
+
+enum DumpInput {
+    File(DumpParser<Box<dyn Read + Send>>),
+    Multistream {
+        multistream: Multistream,
+        pages: PageSelection,
+        // The number of threads that decompress streams.
+        threads: usize,
+    },
+}
+
+// The pages to read from a multistream dump.
+enum PageSelection {
+    All,
+    Titles(Vec<String>),
+    Ids(Vec<u64>),
+}
+
+pub struct DumpSource {
+    input: DumpInput,
+    redirects: Redirects,
+    since: Option<String>,
+}
+
+// Whether the revision of the page was made at or after `since`. Timestamps
+// in the same format sort in the same order as the times they give.
+fn is_since(page: &Page, since: &str) -> bool {
+    page.timestamp
+        .as_deref()
+        .is_some_and(|timestamp| timestamp >= since)
+}
+
+// A timestamp in the format used in dumps, or only its date.
+fn is_timestamp(timestamp: &str) -> bool {
+    const FORMAT: &str = "0000-00-00T00:00:00Z";
+    (timestamp.len() == "0000-00-00".len() || timestamp.len() == FORMAT.len())
+        && timestamp.bytes().zip(FORMAT.bytes()).all(|(byte, format)| {
+            if format == b'0' {
+                byte.is_ascii_digit()
+            } else {
+                byte == format
+            }
+        })
+}
+
+impl DumpSource {
+    pub fn siteinfo(&mut self) -> Result<&SiteInfo> {
+        match &mut self.input {
+            DumpInput::File(parser) => Ok(parser.siteinfo()?),
+            DumpInput::Multistream { multistream, .. } => {
+                Ok(multistream.siteinfo())
+            }
+        }
+    }
+
+    /// The pages in the dump, without those excluded by `--skip-redirects`,
+    /// `--only-redirects` or `--since`.
+    pub fn into_pages(self) -> Pages {
+        let pages: Pages = match self.input {
+            DumpInput::File(parser) => Box::new(parser.map(|page| Ok(page?))),
+            DumpInput::Multistream {
+                multistream,
+                pages: PageSelection::All,
+                threads,
+            } => Box::new(multistream.pages(threads).map(|page| Ok(page?))),
+            DumpInput::Multistream {
+                multistream,
+                pages: PageSelection::Titles(titles),
+                threads,
+            } => Box::new(
+                multistream
+                    .pages_by_titles(titles, threads)
+                    .map(|page| Ok(page?)),
+            ),
+            DumpInput::Multistream {
+                multistream,
+                pages: PageSelection::Ids(ids),
+                threads,
+            } => Box::new(
+                multistream.pages_by_ids(ids, threads).map(|page| Ok(page?)),
+            ),
+        };
+        let redirects = self.redirects;
+        let since = self.since;
+        Box::new(pages.filter(move |result| match result {
+            Ok(page) => {
+                redirects.includes(page)
+                    && match &since {
+                        Some(since) => is_since(page, since),
+                        None => true,
+                    }
+            }
+            Err(_) => true,
+        }))
+    }
 }
 
 pub fn collect_template_names_and_files<I, S>(
@@ -197,6 +770,22 @@ fn collect_lines(filepaths: Vec<PathBuf>) -> Result<Vec<String>> {
     Ok(lines)
 }
 
+fn read_page_ids(path: &Path) -> Result<Vec<u64>> {
+    collect_lines(vec![path.into()])?
+        .into_iter()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            line.trim().parse().map_err(|_| Error::FormatError {
+                description: "invalid page ID",
+                path: path.into(),
+                line_number: i + 1,
+                line,
+            })
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 pub enum DumpFileError {
     IoError(std::io::Error),
@@ -237,124 +826,335 @@ impl std::error::Error for DumpFileError {
     }
 }
 
+const DEFAULT_MULTISTREAM_DUMP_FILE_NAME: &str =
+    "pages-articles-multistream.xml.bz2";
+
 const DEFAULT_DUMP_FILE_NAMES: &[&str] = &[
     "pages-articles.xml",
     "pages-meta-current.xml",
     "pages-articles.xml.bz2",
     "pages-meta-current.xml.bz2",
+    "pages-articles.xml.gz",
+    "pages-meta-current.xml.gz",
+    "pages-articles.xml.xz",
+    "pages-meta-current.xml.xz",
+    "pages-articles.xml.zst",
+    "pages-meta-current.xml.zst",
 ];
 
 fn get_dump_file(
     path: &Option<PathBuf>,
-) -> StdResult<Box<dyn Read>, DumpFileError> {
-    let (file, path) = if let Some(path) = path {
-        (File::open(&path)?, Path::new(path))
-    } else if let Some((file, path)) = DEFAULT_DUMP_FILE_NAMES
-        .iter()
-        .filter_map(|path| {
-            if let Ok(f) = File::open(path) {
-                Some((f, path))
-            } else {
-                None
-            }
-        })
-        .next()
-    {
-        (file, Path::new(path))
+) -> StdResult<Box<dyn Read + Send>, DumpFileError> {
+    if let Some(path) = path {
+        Ok(input::open(path)?)
+    } else {
+        DEFAULT_DUMP_FILE_NAMES
+            .iter()
+            .find(|path| Path::new(path).is_file())
+            .ok_or(DumpFileError::DefaultsNotFound)
+            .and_then(|path| Ok(input::open(path)?))
+    }
+}
+
+fn read_template_redirects(
+    page_sql: &Path,
+    redirect_sql: &Path,
+) -> Result<TemplateRedirects> {
+    let open = |path: &Path| {
+        input::open(path)
+            .map(BufReader::new)
+            .map_err(|e| Error::IoError {
+                action: "open",
+                path: path.into(),
+                cause: e,
+            })
+    };
+    TemplateRedirects::read(open(page_sql)?, open(redirect_sql)?).map_err(|e| {
+        Error::TemplateRedirects {
+            page_sql: page_sql.into(),
+            redirect_sql: redirect_sql.into(),
+            cause: e,
+        }
+    })
+}
+
+fn read_site_config(path: &Path) -> Result<SiteConfig> {
+    let file = File::open(path).map_err(|e| Error::IoError {
+        action: "open",
+        path: path.into(),
+        cause: e,
+    })?;
+    SiteConfig::from_siteinfo_json(BufReader::new(file)).map_err(|e| {
+        Error::SiteConfig {
+            path: path.into(),
+            cause: e,
+        }
+    })
+}
+
+fn read_dump_options(dump_args: &DumpArgs) -> Result<DumpOptions> {
+    let DumpArgs {
+        namespaces,
+        pages,
+        skip_redirects,
+        only_redirects,
+        since,
+        all_revisions,
+        dump_filepath,
+        threads,
+        index,
+        titles,
+        ids,
+        site_config,
+    } = dump_args;
+    let pages = pages.unwrap_or(std::usize::MAX);
+    let mut threads = threads.unwrap_or_else(num_cpus::get);
+    let input = if let Some(index) = index {
+        let dump_filepath = dump_filepath
+            .clone()
+            .unwrap_or_else(|| DEFAULT_MULTISTREAM_DUMP_FILE_NAME.into());
+        let multistream = Multistream::open(&dump_filepath, index)?;
+        let pages = match (titles, ids) {
+            (Some(titles), _) => PageSelection::Titles(
+                collect_lines(vec![titles.clone()])?
+                    .into_iter()
+                    .map(|title| title.trim().replace('_', " "))
+                    .filter(|title| !title.is_empty())
+                    .collect(),
+            ),
+            (None, Some(ids)) => PageSelection::Ids(read_page_ids(ids)?),
+            (None, None) => PageSelection::All,
+        };
+        // The streams are decompressed on threads of their own, so the
+        // threads are split between decompressing and parsing.
+        let decompression_threads = (threads / 2).max(1);
+        threads = (threads - decompression_threads).max(1);
+        DumpInput::Multistream {
+            multistream,
+            pages,
+            threads: decompression_threads,
+        }
     } else {
-        return Err(DumpFileError::DefaultsNotFound);
+        DumpInput::File(
+            parse_dump(get_dump_file(&dump_filepath)?)
+                .all_revisions(*all_revisions),
+        )
     };
-    Ok(if path.ends_with(".bz2") {
-        Box::new(BzDecoder::new(BufReader::new(file)))
+    let redirects = if *skip_redirects {
+        Redirects::Skip
+    } else if *only_redirects {
+        Redirects::Only
     } else {
-        Box::new(file)
+        Redirects::Include
+    };
+    if let Some(since) = since {
+        if !is_timestamp(since) {
+            return Err(Error::InvalidTimestamp(since.clone()));
+        }
+    }
+    let mut dump = DumpSource {
+        input,
+        redirects,
+        since: since.clone(),
+    };
+    let site_config = match site_config {
+        Some(path) => read_site_config(path)?,
+        None => SiteConfig::wiktionary(),
+    };
+    let mut siteinfo = dump.siteinfo()?.clone();
+    site_config.add_namespace_aliases(&mut siteinfo.namespaces);
+    let namespaces = parse_namespaces(namespaces, &siteinfo.namespaces)?;
+    let configuration = site_config.to_configuration();
+    Ok(DumpOptions {
+        namespaces,
+        configuration,
+        pages,
+        dump,
+        threads,
+        siteinfo,
     })
 }
 
+fn parse_namespaces(
+    names: &[String],
+    namespaces: &Namespaces,
+) -> Result<Vec<Namespace>> {
+    names
+        .iter()
+        .map(|name| {
+            namespaces
+                .parse_arg(name)
+                .ok_or_else(|| Error::UnknownNamespace(name.clone()))
+        })
+        .collect()
+}
+
+fn read_template_normalizations(
+    path: &Path,
+) -> Result<HashMap<String, Arc<str>>> {
+    let file = File::open(path).map_err(|e| Error::IoError {
+        action: "open",
+        path: path.into(),
+        cause: e,
+    })?;
+    let normalizations: HashMap<String, Vec<String>> =
+        serde_json::from_reader(&file).map_err(|e| {
+            Error::ParseTemplateNormalization {
+                path: path.into(),
+                cause: e,
+            }
+        })?;
+    let capacity = normalizations.iter().map(|(_k, v)| v.len()).sum();
+    let normalizations = normalizations.into_iter().fold(
+        HashMap::with_capacity(capacity),
+        |mut map, (template, aliases)| {
+            let template = template.into();
+            map.extend(
+                aliases
+                    .into_iter()
+                    .map(|alias| (alias, Arc::clone(&template))),
+            );
+            map
+        },
+    );
+    Ok(normalizations)
+}
+
 pub fn get_opts() -> Result<Opts> {
     let args = Args::from_args();
     let Args { verbose, cmd } = args;
     let dump_options = match &cmd {
         Command::DumpParsedTemplates { dump_args, .. }
+        | Command::DumpInvocations { dump_args, .. }
+        | Command::ExtractSections { dump_args, .. }
+        | Command::DumpEntries { dump_args, .. }
+        | Command::DumpTranslations { dump_args, .. }
+        | Command::DumpPronunciations { dump_args, .. }
+        | Command::DumpEtymologies { dump_args, .. }
+        | Command::DumpLinks { dump_args, .. }
+        | Command::DumpRedirects { dump_args }
+        | Command::LinkReport { dump_args, .. }
         | Command::AllHeaders { dump_args, .. }
         | Command::FilterHeaders { dump_args, .. } => {
-            let DumpArgs {
-                namespaces,
-                pages,
-                dump_filepath,
-            } = dump_args;
-            let pages = pages.unwrap_or(std::usize::MAX);
-            let dump_file = get_dump_file(&dump_filepath)?;
-            Some(DumpOptions {
-                namespaces: namespaces.to_vec(),
-                pages,
-                dump_file,
-            })
+            Some(read_dump_options(dump_args)?)
         }
         _ => None,
     };
 
-    let template_names_and_files = match &cmd {
-        Command::DumpParsedTemplates {
-            template_filepaths, ..
-        } => Some(collect_template_names_and_files(template_filepaths)?),
-        _ => None,
-    };
-
-    let template_normalizations = match &cmd {
+    let cmd = match cmd {
         Command::DumpParsedTemplates {
-            template_normalization_filepath:
-                Some(template_normalization_filepath),
+            templates,
+            cache,
+            adds_changes,
             ..
         } => {
-            let file =
-                File::open(&template_normalization_filepath).map_err(|e| {
-                    Error::IoError {
-                        action: "open",
-                        path: template_normalization_filepath.into(),
-                        cause: e,
-                    }
-                })?;
-            let normalizations: HashMap<String, Vec<String>> =
-                serde_json::from_reader(&file).map_err(|e| {
-                    Error::ParseTemplateNormalization {
-                        path: template_normalization_filepath.into(),
-                        cause: e,
-                    }
-                })?;
-            let capacity = normalizations.iter().map(|(_k, v)| v.len()).sum();
-            let normalizations = normalizations.into_iter().fold(
-                HashMap::with_capacity(capacity),
-                |mut map, (template, aliases)| {
-                    let template = template.into();
-                    map.extend(
-                        aliases
-                            .into_iter()
-                            .map(|alias| (alias, Rc::clone(&template))),
-                    );
-                    map
-                },
-            );
-            Some(normalizations)
+            if cache.is_some()
+                && templates.format == SerializationFormat::Parquet
+            {
+                return Err(Error::ParquetNotSupported("the cache"));
+            }
+            CommandData::DumpParsedTemplates {
+                templates: templates.read()?,
+                cache,
+                adds_changes,
+                dump_options: dump_options.unwrap(),
+            }
         }
-        _ => None,
-    };
-
-    let cmd = match cmd {
-        Command::DumpParsedTemplates {
+        Command::Run { job_file } => {
+            let JobFile { mut dump, jobs } = read_job_file(&job_file)?;
+            dump.namespaces =
+                jobs.iter().flat_map(JobArgs::namespaces).cloned().collect();
+            let dump_options = read_dump_options(&dump)?;
+            let jobs = jobs
+                .into_iter()
+                .map(|job| job.read(&dump_options.siteinfo.namespaces))
+                .collect::<Result<_>>()?;
+            CommandData::Run { jobs, dump_options }
+        }
+        Command::AddTemplateRedirects {
+            template_filepaths,
             format,
-            include_text,
+            page_sql,
+            redirect_sql,
+            dump_filepath,
             ..
         } => {
-            let files = template_names_and_files.unwrap();
-            let dump_options = dump_options.unwrap();
-            CommandData::DumpParsedTemplates(DumpParsedTemplates {
-                files,
-                dump_options,
-                template_normalizations,
-                include_text,
+            let mut parser = parse_dump(get_dump_file(&dump_filepath)?);
+            let mut namespaces = parser.siteinfo()?.namespaces.clone();
+            SiteConfig::wiktionary().add_namespace_aliases(&mut namespaces);
+            CommandData::AddTemplateRedirects {
+                templates: collect_template_names_and_files(
+                    &template_filepaths,
+                )?,
                 format,
-            })
+                redirects: read_template_redirects(&page_sql, &redirect_sql)?,
+                namespaces,
+            }
+        }
+        Command::DumpInvocations { modules, .. } => {
+            CommandData::DumpInvocations {
+                modules,
+                dump_options: dump_options.unwrap(),
+            }
+        }
+        Command::ExtractSections { languages, .. } => {
+            CommandData::ExtractSections {
+                languages,
+                dump_options: dump_options.unwrap(),
+            }
+        }
+        Command::DumpEntries { languages, .. } => CommandData::DumpEntries {
+            languages,
+            dump_options: dump_options.unwrap(),
+        },
+        Command::DumpTranslations { .. } => CommandData::DumpTranslations {
+            dump_options: dump_options.unwrap(),
+        },
+        Command::DumpPronunciations { format, .. } => {
+            if format == SerializationFormat::Parquet {
+                return Err(Error::ParquetNotSupported("dump-pronunciations"));
+            }
+            CommandData::DumpPronunciations {
+                format,
+                dump_options: dump_options.unwrap(),
+            }
+        }
+        Command::DumpEtymologies { .. } => CommandData::DumpEtymologies {
+            dump_options: dump_options.unwrap(),
+        },
+        Command::DumpLinks {
+            include_language, ..
+        } => CommandData::DumpLinks {
+            include_language,
+            dump_options: dump_options.unwrap(),
+        },
+        Command::DumpRedirects { .. } => CommandData::DumpRedirects {
+            dump_options: dump_options.unwrap(),
+        },
+        Command::LinkReport { pretty, dump_args } => {
+            if dump_args.dump_filepath.as_deref()
+                == Some(Path::new(input::STDIN))
+            {
+                return Err(Error::StdinReadTwice("link-report"));
+            }
+            // The first pass collects the titles of all pages, including
+            // those that are not processed: redirects, pages last edited
+            // before --since and pages not in --titles or --ids.
+            let titles = read_dump_options(&DumpArgs {
+                skip_redirects: false,
+                only_redirects: false,
+                since: None,
+                all_revisions: false,
+                titles: None,
+                ids: None,
+                ..dump_args.clone()
+            })?
+            .dump;
+            CommandData::LinkReport {
+                pretty,
+                titles,
+                dump_options: dump_options.unwrap(),
+            }
         }
         Command::AllHeaders { pretty, .. } => CommandData::AllHeaders {
             pretty,
@@ -371,6 +1171,12 @@ pub fn get_opts() -> Result<Opts> {
             pretty,
             dump_options: dump_options.unwrap(),
         },
+        Command::DiffSiteConfig { site_config } => {
+            CommandData::DiffSiteConfig {
+                site_config: read_site_config(&site_config)?,
+                path: site_config,
+            }
+        }
         Command::Completions { shell } => CommandData::Completions { shell },
     };
     Ok(Opts { verbose, cmd })
@@ -0,0 +1,253 @@
+//! The cache of `dump-parsed-templates --cache`, which keeps the serialized
+//! templates of each page by page ID, so that a run over an adds-changes dump
+//! only parses the pages whose revision has a different SHA-1 and then
+//! rewrites the output files from the cache.
+use dump_parser::Page;
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Deserialize, Serialize, Serializer,
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, File},
+    io::{BufReader, BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::error::{Error, Result};
+use crate::FilePool;
+
+// Serialized as a CBOR byte string rather than an array of numbers.
+struct Bytes(Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a byte string")
+    }
+
+    fn visit_bytes<E: de::Error>(
+        self,
+        bytes: &[u8],
+    ) -> std::result::Result<Bytes, E> {
+        Ok(Bytes(bytes.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(
+        self,
+        bytes: Vec<u8>,
+    ) -> std::result::Result<Bytes, E> {
+        Ok(Bytes(bytes))
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Bytes, D::Error> {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedPage {
+    title: String,
+    sha1: Option<String>,
+    /// The index of each output file in `Cache::files` that the page has
+    /// templates for, and what was written to it.
+    outputs: Vec<(usize, Bytes)>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Cache {
+    /// The options of the run that made the cache.
+    #[serde(default)]
+    settings: String,
+    files: Vec<PathBuf>,
+    pages: BTreeMap<u64, CachedPage>,
+    #[serde(skip)]
+    file_indices: HashMap<PathBuf, usize>,
+}
+
+impl Cache {
+    /// Reads the cache, or starts an empty one if the file does not exist or
+    /// the cache was made with other `settings`.
+    pub fn read(path: &Path, settings: String) -> Result<Self> {
+        let empty = |settings| Cache {
+            settings,
+            ..Cache::default()
+        };
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Ok(empty(settings))
+            }
+            Err(e) => {
+                return Err(Error::IoError {
+                    action: "open",
+                    path: path.into(),
+                    cause: e,
+                })
+            }
+        };
+        let mut cache: Cache = serde_cbor::from_reader(BufReader::new(file))
+            .map_err(|e| Error::Cache {
+                path: path.into(),
+                cause: e,
+            })?;
+        if cache.settings != settings {
+            eprintln!(
+                "{} was made with other options, so all pages are parsed",
+                path.display()
+            );
+            return Ok(empty(settings));
+        }
+        cache.file_indices = cache
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, path)| (path.clone(), i))
+            .collect();
+        Ok(cache)
+    }
+
+    /// Whether the cache has the page with the same title and text.
+    pub fn is_current(&self, page: &Page) -> bool {
+        page.sha1.is_some()
+            && page.id.and_then(|id| self.pages.get(&id)).is_some_and(
+                |cached| cached.sha1 == page.sha1 && cached.title == page.title,
+            )
+    }
+
+    /// Replaces the page with the given ID. `outputs` are the serialized
+    /// templates for each file in `files`.
+    pub fn insert(
+        &mut self,
+        id: u64,
+        title: String,
+        sha1: Option<String>,
+        outputs: Vec<(usize, Vec<u8>)>,
+        files: &FilePool,
+    ) {
+        let outputs = outputs
+            .into_iter()
+            .map(|(file, serialized)| {
+                let path = files.path(file);
+                let index = match self.file_indices.get(path) {
+                    Some(&index) => index,
+                    None => {
+                        let index = self.files.len();
+                        self.files.push(path.into());
+                        self.file_indices.insert(path.into(), index);
+                        index
+                    }
+                };
+                (index, Bytes(serialized))
+            })
+            .collect();
+        self.pages.insert(
+            id,
+            CachedPage {
+                title,
+                sha1,
+                outputs,
+            },
+        );
+    }
+
+    /// Removes the pages whose IDs are not in `ids`.
+    pub fn retain(&mut self, ids: &HashSet<u64>) {
+        self.pages.retain(|id, _| ids.contains(id));
+    }
+
+    /// Writes the templates of all the pages to the output files in `files`,
+    /// in the order of the page IDs, which is the order of the pages in the
+    /// dump. Files that are no longer written to are skipped.
+    pub fn write_outputs(&self, files: &mut FilePool) -> Result<()> {
+        let ids: Vec<_> =
+            self.files.iter().map(|path| files.id(path)).collect();
+        for page in self.pages.values() {
+            for (file, Bytes(serialized)) in &page.outputs {
+                if let Some(id) = ids[*file] {
+                    files.write_all(id, serialized)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the cache to a temporary file and then renames it, so that the
+    /// previous cache is kept if writing fails.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        let io_error = |action, path: &Path| {
+            let path = path.to_path_buf();
+            move |e| Error::IoError {
+                action,
+                path,
+                cause: e,
+            }
+        };
+        let file =
+            File::create(&temporary).map_err(io_error("create", &temporary))?;
+        let mut writer = BufWriter::new(file);
+        serde_cbor::to_writer(&mut writer, self)?;
+        writer.flush().map_err(io_error("write to", &temporary))?;
+        fs::rename(&temporary, path).map_err(io_error("replace", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bytes, Cache, CachedPage};
+    use dump_parser::{Namespace, Page};
+
+    #[test]
+    fn current_pages() {
+        let page = Page {
+            id: Some(1),
+            revision_id: Some(2),
+            sha1: Some("abc".into()),
+            ..Page::new("cat", Namespace::MAIN, "")
+        };
+        let mut cache = Cache::default();
+        assert!(!cache.is_current(&page));
+        cache.pages.insert(
+            1,
+            CachedPage {
+                title: "cat".into(),
+                sha1: Some("abc".into()),
+                outputs: vec![(0, Bytes(b"{}\n".to_vec()))],
+            },
+        );
+        assert!(cache.is_current(&page));
+        let edited = Page {
+            sha1: Some("def".into()),
+            ..page.clone()
+        };
+        assert!(!cache.is_current(&edited));
+        let moved = Page {
+            title: "Cat".into(),
+            ..page.clone()
+        };
+        assert!(!cache.is_current(&moved));
+        let serialized = serde_cbor::to_vec(&cache).unwrap();
+        let cache: Cache = serde_cbor::from_slice(&serialized).unwrap();
+        assert!(cache.is_current(&page));
+        assert_eq!(cache.pages[&1].outputs[0].1 .0, b"{}\n");
+    }
+}
@@ -1,9 +1,15 @@
-use dump_parser::Error as DumpParsingError;
+use dump_parser::{
+    multistream::Error as MultistreamError,
+    site_config::Error as SiteConfigError, Error as DumpParsingError,
+};
+use parquet::errors::ParquetError;
 use serde_cbor::Error as SerdeCborError;
 use serde_json::{self, error::Error as SerdeJsonError};
+use sql_dump::Error as SqlError;
 use std::path::PathBuf;
 use std::{fmt::Display, io::Error as IoError};
 use template_iter::TitleNormalizationError;
+use toml::de::Error as TomlError;
 
 use crate::args::DumpFileError;
 
@@ -17,6 +23,7 @@ pub enum Error {
         cause: IoError,
     },
     DumpParsingError(DumpParsingError),
+    MultistreamError(MultistreamError),
     SerdeJsonError(SerdeJsonError),
     SerdeCborError(SerdeCborError),
     TemplateNameNormalization {
@@ -24,16 +31,45 @@ pub enum Error {
         cause: TitleNormalizationError,
     },
     DumpFileError(DumpFileError),
+    UnknownNamespace(String),
     ParseTemplateNormalization {
         path: PathBuf,
         cause: SerdeJsonError,
     },
+    SiteConfig {
+        path: PathBuf,
+        cause: SiteConfigError,
+    },
+    TemplateRedirects {
+        page_sql: PathBuf,
+        redirect_sql: PathBuf,
+        cause: SqlError,
+    },
     FormatError {
         description: &'static str,
         path: PathBuf,
         line_number: usize,
         line: String,
     },
+    JobFile {
+        path: PathBuf,
+        cause: TomlError,
+    },
+    /// A subcommand that reads the dump twice was given standard input.
+    StdinReadTwice(&'static str),
+    InvalidTimestamp(String),
+    Cache {
+        path: PathBuf,
+        cause: SerdeCborError,
+    },
+    /// A page without `<id>` cannot be cached.
+    MissingPageId(String),
+    /// An output that cannot be written in the Parquet format.
+    ParquetNotSupported(&'static str),
+    Parquet {
+        path: PathBuf,
+        cause: ParquetError,
+    },
 }
 
 impl std::error::Error for Error {
@@ -41,12 +77,23 @@ impl std::error::Error for Error {
         match self {
             Error::IoError { cause, .. } => Some(cause),
             Error::DumpParsingError(e) => Some(e),
+            Error::MultistreamError(e) => Some(e),
             Error::SerdeJsonError(e) => Some(e),
             Error::SerdeCborError(e) => Some(e),
             Error::TemplateNameNormalization { cause, .. } => Some(cause),
             Error::DumpFileError(e) => Some(e),
+            Error::UnknownNamespace(_) => None,
             Error::ParseTemplateNormalization { cause, .. } => Some(cause),
+            Error::SiteConfig { cause, .. } => Some(cause),
+            Error::TemplateRedirects { cause, .. } => Some(cause),
             Error::FormatError { .. } => None,
+            Error::JobFile { cause, .. } => Some(cause),
+            Error::StdinReadTwice(_) => None,
+            Error::InvalidTimestamp(_) => None,
+            Error::Cache { cause, .. } => Some(cause),
+            Error::MissingPageId(_) => None,
+            Error::ParquetNotSupported(_) => None,
+            Error::Parquet { cause, .. } => Some(cause),
         }
     }
 }
@@ -64,29 +111,47 @@ impl Display for Error {
             Error::DumpParsingError(e) => {
                 write!(f, "error while parsing dump: {}", e)
             }
+            Error::MultistreamError(e) => write!(f, "{}", e),
             Error::SerdeJsonError(e) => {
                 write!(f, "error writing or reading JSON: {}", e)
             }
             Error::TemplateNameNormalization { title, cause } => write!(
                 f,
                 "failed to normalize template name {}: {}",
-                title,
-                match cause {
-                    TitleNormalizationError::TooLong => "too long",
-                    TitleNormalizationError::IllegalChar => "illegal character",
-                    _ => "unknown error",
-                },
+                title, cause
             ),
             Error::SerdeCborError(e) => write!(f, "error writing CBOR: {}", e),
             Error::DumpFileError(e) => {
                 write!(f, "error finding dump file: {}", e)
             }
+            Error::UnknownNamespace(name) => write!(
+                f,
+                "namespace {} is not listed in the siteinfo of the dump",
+                name
+            ),
             Error::ParseTemplateNormalization { path, cause } => write!(
                 f,
                 "failed to parse template normalization file {}: {}",
                 path.display(),
                 cause
             ),
+            Error::SiteConfig { path, cause } => write!(
+                f,
+                "failed to read site configuration {}: {}",
+                path.display(),
+                cause
+            ),
+            Error::TemplateRedirects {
+                page_sql,
+                redirect_sql,
+                cause,
+            } => write!(
+                f,
+                "failed to read template redirects from {} and {}: {}",
+                page_sql.display(),
+                redirect_sql.display(),
+                cause
+            ),
             Error::FormatError {
                 description,
                 path,
@@ -100,6 +165,37 @@ impl Display for Error {
                 path.display(),
                 line
             ),
+            Error::JobFile { path, cause } => write!(
+                f,
+                "failed to parse job file {}: {}",
+                path.display(),
+                cause
+            ),
+            Error::StdinReadTwice(command) => write!(
+                f,
+                "{} reads the dump twice, so it cannot read standard input",
+                command
+            ),
+            Error::InvalidTimestamp(timestamp) => write!(
+                f,
+                "{} is not a timestamp like 2020-07-28 or 2020-07-28T12:00:00Z",
+                timestamp
+            ),
+            Error::Cache { path, cause } => {
+                write!(f, "failed to read cache {}: {}", path.display(), cause)
+            }
+            Error::MissingPageId(title) => {
+                write!(f, "page {} has no ID, so it cannot be cached", title)
+            }
+            Error::ParquetNotSupported(output) => {
+                write!(f, "{} cannot be written in the Parquet format", output)
+            }
+            Error::Parquet { path, cause } => write!(
+                f,
+                "failed to write Parquet file {}: {}",
+                path.display(),
+                cause
+            ),
         }
     }
 }
@@ -125,5 +221,11 @@ macro_rules! impl_from {
 }
 
 impl_from! {
-    Error <- [DumpFileError, DumpParsingError, SerdeCborError, SerdeJsonError]
+    Error <- [
+        DumpFileError,
+        DumpParsingError,
+        MultistreamError,
+        SerdeCborError,
+        SerdeJsonError,
+    ]
 }
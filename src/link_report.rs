@@ -0,0 +1,127 @@
+//! The `link-report` subcommand, which reads the dump twice: first to
+//! collect the titles of all pages, then to find the links to pages that do
+//! not exist and the entries that no page links to.
+use dump_parser::{
+    parallel::process_in_order, print_parser_warnings, Namespace,
+};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
+
+use crate::args::{DumpOptions, DumpSource};
+use crate::error::Result;
+use crate::links::{links, Link};
+use crate::{do_dumping, print_time};
+
+#[derive(Debug, Serialize)]
+struct RedLink {
+    target: String,
+    /// The number of pages that link to the target.
+    pages: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct LinkReport {
+    /// Sorted by the number of pages that link to them, most first.
+    red_links: Vec<RedLink>,
+    /// Pages in the main namespace that are not redirects and that no page
+    /// in the processed namespaces links to.
+    orphans: Vec<String>,
+}
+
+pub fn link_report(
+    pretty: bool,
+    titles: DumpSource,
+    options: DumpOptions,
+    main_start: Instant,
+    verbose: bool,
+) -> Result<()> {
+    let DumpOptions {
+        pages,
+        namespaces,
+        configuration,
+        dump,
+        threads,
+        siteinfo,
+    } = options;
+    let start_time = main_start.elapsed();
+    let parse_start = Instant::now();
+    // The titles in the dump are normalized and have the local namespace
+    // prefix, like the link targets.
+    let mut existing = HashSet::new();
+    let mut orphans = HashSet::new();
+    let mut redirects = HashMap::new();
+    for page in titles.into_pages().take(pages) {
+        let page = page?;
+        if page.namespace == Namespace::MAIN && !page.is_redirect() {
+            orphans.insert(page.title.clone());
+        }
+        existing.insert(page.title.clone());
+        if let Some(target) = page.redirect {
+            redirects.insert(page.title, target);
+        }
+    }
+    let pages = dump.into_pages().take(pages).filter(|result| match result {
+        Ok(page) => namespaces.contains(&page.namespace),
+        Err(_) => true,
+    });
+    let mut red_links: HashMap<String, usize> = HashMap::new();
+    process_in_order(
+        pages,
+        threads,
+        |page| -> Result<(String, HashSet<String>)> {
+            let output = configuration.parse(&page.text);
+            if verbose {
+                print_parser_warnings(&page, &output.warnings);
+            }
+            let targets =
+                links(&page.text, &output.nodes, &siteinfo.namespaces, false)
+                    .into_iter()
+                    .filter_map(|link| match link.link {
+                        // Special and Media pages are not in the dump.
+                        Link::Internal {
+                            target: Some(target),
+                            namespace: Some(namespace),
+                            ..
+                        } if namespace.0 >= 0 => Some(target),
+                        _ => None,
+                    })
+                    .collect();
+            Ok((page.title, targets))
+        },
+        |result| {
+            let (title, targets) = result?;
+            for target in targets {
+                if !existing.contains(&target) {
+                    *red_links.entry(target).or_insert(0) += 1;
+                    continue;
+                }
+                // A link to a redirect is a link to its target.
+                let target = redirects.get(&target).unwrap_or(&target);
+                if *target != title {
+                    orphans.remove(target);
+                }
+            }
+            Ok(())
+        },
+    )?;
+    let mut red_links: Vec<_> = red_links
+        .into_iter()
+        .map(|(target, pages)| RedLink { target, pages })
+        .collect();
+    red_links.sort_by(|a, b| {
+        b.pages.cmp(&a.pages).then_with(|| a.target.cmp(&b.target))
+    });
+    let mut orphans: Vec<_> = orphans.into_iter().collect();
+    orphans.sort();
+    do_dumping(&LinkReport { red_links, orphans }, pretty)?;
+    let parse_time = parse_start.elapsed();
+    eprintln!(
+        "startup took {}, parsing and printing {}",
+        print_time(&start_time).unwrap(),
+        print_time(&parse_time).unwrap()
+    );
+    Ok(())
+}
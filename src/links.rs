@@ -0,0 +1,292 @@
+//! Internal links, categories, interwiki links and external links in a page.
+use dump_parser::{Namespace, Namespaces, Node, Positioned};
+use serde::Serialize;
+use template_iter::{
+    parse_wiki_text_ext::visit::{walk, Context, Visit, Visitor},
+    Title,
+};
+
+// Prefixes of links to other Wikimedia projects.
+static PROJECT_PREFIXES: &[&str] = &[
+    "b",
+    "c",
+    "commons",
+    "d",
+    "m",
+    "meta",
+    "metawiki",
+    "mw",
+    "n",
+    "q",
+    "s",
+    "species",
+    "v",
+    "voy",
+    "w",
+    "wikibooks",
+    "wikidata",
+    "wikimedia",
+    "wikinews",
+    "wikipedia",
+    "wikiquote",
+    "wikisource",
+    "wikispecies",
+    "wikiversity",
+    "wikivoyage",
+    "wikt",
+    "wiktionary",
+];
+
+// Whether the prefix before the colon in a link target is an interwiki
+// prefix: the prefix of another project or a lowercase language code like
+// `fr` or `zh-min-nan`.
+fn is_interwiki_prefix(prefix: &str) -> bool {
+    let lowercase = prefix.to_lowercase();
+    if PROJECT_PREFIXES.contains(&lowercase.as_str()) {
+        return true;
+    }
+    let mut parts = prefix.split('-');
+    let first = parts.next().unwrap_or_default();
+    (2..=3).contains(&first.len())
+        && first.bytes().all(|b| b.is_ascii_lowercase())
+        && parts.all(|part| {
+            !part.is_empty() && part.bytes().all(|b| b.is_ascii_lowercase())
+        })
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Link<'a> {
+    /// `[[target#anchor|text]]`. `target` is the normalized title with its
+    /// namespace prefix, or `None` for a link to a section of the same page.
+    Internal {
+        target: Option<String>,
+        /// The namespace of `target`.
+        #[serde(skip)]
+        namespace: Option<Namespace>,
+        anchor: Option<&'a str>,
+        text: Option<&'a str>,
+    },
+    /// `[[Category:name|sort key]]`.
+    Category {
+        category: String,
+        sort_key: Option<&'a str>,
+    },
+    /// `[[prefix:target|text]]`, where the prefix is the prefix of another
+    /// wiki.
+    Interwiki {
+        prefix: &'a str,
+        target: &'a str,
+        text: Option<&'a str>,
+    },
+    /// `[url text]`.
+    External { url: &'a str, text: Option<&'a str> },
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LinkInPage<'a> {
+    #[serde(flatten)]
+    pub link: Link<'a>,
+    /// The first header that encloses the link, normally the language
+    /// section.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<&'a str>,
+}
+
+fn text<'a>(wikitext: &'a str, nodes: &[Node]) -> Option<&'a str> {
+    match (nodes.first(), nodes.last()) {
+        (Some(first), Some(last)) => Some(&wikitext[first.start()..last.end()]),
+        _ => None,
+    }
+}
+
+struct LinkFinder<'a, 'b> {
+    namespaces: &'b Namespaces,
+    include_language: bool,
+    links: Vec<LinkInPage<'a>>,
+}
+
+impl<'a> LinkFinder<'a, '_> {
+    fn link(&self, target: &'a str, text: Option<&'a str>) -> Option<Link<'a>> {
+        let target = target.trim();
+        let without_colon = target.strip_prefix(':').unwrap_or(target);
+        if let Some(colon) = without_colon.find(':') {
+            let prefix = without_colon[..colon].trim();
+            if self.namespaces.lookup(prefix).is_none()
+                && is_interwiki_prefix(prefix)
+            {
+                return Some(Link::Interwiki {
+                    prefix,
+                    target: without_colon[colon + 1..].trim(),
+                    text,
+                });
+            }
+        }
+        let (title, anchor) = match target.find('#') {
+            Some(hash) => (&target[..hash], Some(&target[hash + 1..])),
+            None => (target, None),
+        };
+        let (target, namespace) = if title.trim().is_empty() {
+            (None, None)
+        } else {
+            let title =
+                Title::new(title, Namespace::MAIN, self.namespaces).ok()?;
+            (
+                Some(title.prefixed_text(self.namespaces)),
+                Some(title.namespace()),
+            )
+        };
+        Some(Link::Internal {
+            target,
+            namespace,
+            anchor,
+            text,
+        })
+    }
+}
+
+impl<'a> Visitor<'a> for LinkFinder<'a, '_> {
+    fn visit_node(
+        &mut self,
+        node: &'a Node<'a>,
+        context: &Context<'a>,
+    ) -> Visit {
+        let wikitext = context.wikitext();
+        let link = match node {
+            Node::Link {
+                target,
+                text,
+                start,
+                ..
+            } => {
+                // Without a pipe, the text is the target itself.
+                let text = self::text(wikitext, text)
+                    .filter(|_| text[0].start() > start + 2 + target.len());
+                self.link(target, text)
+            }
+            Node::Category {
+                target, ordinal, ..
+            } => Title::new(target, Namespace::CATEGORY, self.namespaces)
+                .ok()
+                .map(|category| Link::Category {
+                    category: category.text(),
+                    sort_key: self::text(wikitext, ordinal),
+                }),
+            Node::ExternalLink { .. } => {
+                let inner = node.get_text_from(wikitext);
+                let inner = inner.strip_prefix('[').unwrap_or(inner);
+                let inner = inner.strip_suffix(']').unwrap_or(inner).trim();
+                let (url, text) = match inner.find(char::is_whitespace) {
+                    Some(space) => {
+                        (&inner[..space], Some(inner[space..].trim()))
+                    }
+                    None => (inner, None),
+                };
+                Some(Link::External { url, text })
+            }
+            _ => None,
+        };
+        if let Some(link) = link {
+            let language = if self.include_language {
+                context.headers().iter().next()
+            } else {
+                None
+            };
+            self.links.push(LinkInPage { link, language });
+        }
+        Visit::Continue
+    }
+}
+
+/// The links in the page in the order they appear. Links to invalid titles
+/// are skipped. `include_language` adds the first header enclosing each
+/// link.
+pub fn links<'a>(
+    wikitext: &'a str,
+    nodes: &'a [Node<'a>],
+    namespaces: &Namespaces,
+    include_language: bool,
+) -> Vec<LinkInPage<'a>> {
+    let mut finder = LinkFinder {
+        namespaces,
+        include_language,
+        links: Vec::new(),
+    };
+    walk(wikitext, nodes, &mut finder);
+    finder.links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{links, Link::*, LinkInPage};
+    use dump_parser::{
+        wiktionary_configuration, Case, Namespace, NamespaceInfo, Namespaces,
+    };
+
+    #[test]
+    fn links_in_page() {
+        let wikitext = concat!(
+            "==English==\n",
+            "[[word#Noun|a word]], [[#Etymology]], [[Appendix:Glossary]], ",
+            "[[w:Word|Wikipedia]], [[fr:mot]], [https://example.com Example]\n",
+            "[[Category:English nouns|WORD]]\n",
+        );
+        let mut namespaces = Namespaces::canonical();
+        namespaces.insert(NamespaceInfo::new(
+            Namespace::MAIN,
+            "",
+            Case::CaseSensitive,
+        ));
+        namespaces.insert(NamespaceInfo::new(
+            Namespace(100),
+            "Appendix",
+            Case::FirstLetter,
+        ));
+        let output = wiktionary_configuration().parse(wikitext);
+        let link = |link| LinkInPage {
+            link,
+            language: Some("English"),
+        };
+        assert_eq!(
+            links(wikitext, &output.nodes, &namespaces, true),
+            vec![
+                link(Internal {
+                    target: Some("word".into()),
+                    namespace: Some(Namespace::MAIN),
+                    anchor: Some("Noun"),
+                    text: Some("a word"),
+                }),
+                link(Internal {
+                    target: None,
+                    namespace: None,
+                    anchor: Some("Etymology"),
+                    text: None,
+                }),
+                link(Internal {
+                    target: Some("Appendix:Glossary".into()),
+                    namespace: Some(Namespace(100)),
+                    anchor: None,
+                    text: None,
+                }),
+                link(Interwiki {
+                    prefix: "w",
+                    target: "Word",
+                    text: Some("Wikipedia"),
+                }),
+                link(Interwiki {
+                    prefix: "fr",
+                    target: "mot",
+                    text: None,
+                }),
+                link(External {
+                    url: "https://example.com",
+                    text: Some("Example"),
+                }),
+                link(Category {
+                    category: "English nouns".into(),
+                    sort_key: Some("WORD"),
+                }),
+            ]
+        );
+    }
+}
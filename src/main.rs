@@ -1,30 +1,60 @@
 use dump_parser::{
-    parse as parse_dump, parse_wiki_text::Positioned, Page, Warning,
+    parallel::process_in_order, parse_wiki_text::Positioned,
+    print_parser_warnings, site_config::SiteConfig, Namespace, Namespaces,
+    Node, Page,
 };
 use filter_headers::HeaderFilterer;
 use header_stats::HeaderStats;
-use serde::Serialize;
+use language_sections::{
+    entries::{entries, Entry},
+    etymology::{etymologies, Etymology},
+    language_sections,
+};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 use std::{
-    borrow::{BorrowMut, Cow},
-    cell::RefCell,
-    collections::{BTreeMap, HashMap},
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::{Error as FmtError, Write as WriteFmt},
     fs::File,
     io::{self, BufWriter, Write},
-    rc::Rc,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
     time::{Duration, Instant},
 };
 use structopt::StructOpt;
-use template_iter::{normalize_title, TemplateBorrowed, TemplateVisitor};
+use template_iter::{
+    HeaderPath, Segment, StructuredTemplate, TemplateBorrowed, TemplateContext,
+    TemplateVisitor, Title,
+};
 
 mod args;
-use args::{
-    Args, CommandData, DumpOptions, DumpParsedTemplates, SerializationFormat,
-};
+use args::{Args, CommandData, DumpOptions, SerializationFormat, TemplateDump};
+
+mod cache;
+use cache::Cache;
 
 mod error;
 use error::{Error, Result};
 
+mod run;
+
+mod template_redirects;
+use template_redirects::TemplateRedirects;
+
+mod link_report;
+
+mod parquet_output;
+use parquet_output::{ParquetFile, TemplateRows};
+
+mod links;
+use links::{links, LinkInPage};
+
+mod pronunciation;
+use pronunciation::{pronunciations, LanguagePronunciation};
+
+mod translations;
+use translations::{translations, Translation};
+
 fn print_time(time: &Duration) -> std::result::Result<String, FmtError> {
     let mut secs = time.as_secs();
     let mins = secs / 60;
@@ -67,119 +97,152 @@ where
     Ok(())
 }
 
-fn print_parser_warnings(page: &Page, warnings: &[Warning]) {
-    for warning in warnings {
-        let Warning {
-            start,
-            end,
-            message,
-        } = warning;
-        let range = 0..page.text.len();
-        let message = message.message().trim_end_matches('.');
-        if !(range.contains(&start) && range.contains(&end)) {
-            eprintln!("byte position {} or {} in warning {} is out of range of {:?}, size of [[{}]]",
-                start, end, message, range, &page.title);
-        } else {
-            eprintln!(
-                "{} at bytes {}..{} ({:?}) in [[{}]]",
-                &message,
-                start,
-                end,
-                &page.text[*start..*end],
-                &page.title
-            );
+#[derive(Debug)]
+struct TemplateToDump<'a> {
+    name: Cow<'a, str>,
+    parameters: Parameters<'a>,
+    text: Option<&'a str>,
+    headers: Option<HeaderPath<'a>>,
+    position: Option<TemplatePosition<'a>>,
+}
+
+// With `#[serde(flatten)]` for the position, every template would be
+// serialized as a map of unknown length, which changes the CBOR output even
+// without `--include-positions`.
+impl Serialize for TemplateToDump<'_> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let len = 3
+            + usize::from(self.headers.is_some())
+            + if self.position.is_some() { 4 } else { 0 };
+        let mut state = serializer.serialize_struct("TemplateToDump", len)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("parameters", &self.parameters)?;
+        state.serialize_field("text", &self.text)?;
+        match &self.headers {
+            Some(headers) => state.serialize_field("headers", headers)?,
+            None => state.skip_field("headers")?,
+        }
+        if let Some(position) = &self.position {
+            state.serialize_field("start", &position.start)?;
+            state.serialize_field("end", &position.end)?;
+            state.serialize_field("line", &position.line)?;
+            state.serialize_field(
+                "enclosing_template",
+                &position.enclosing_template,
+            )?;
         }
+        state.end()
     }
 }
 
 #[derive(Debug, Serialize)]
-struct TemplateToDump<'a> {
-    name: Cow<'a, str>,
-    parameters: BTreeMap<Cow<'a, str>, &'a str>,
-    text: Option<&'a str>,
+#[serde(untagged)]
+enum Parameters<'a> {
+    Text(BTreeMap<Cow<'a, str>, &'a str>),
+    Structured(BTreeMap<Cow<'a, str>, Vec<Segment<'a>>>),
+}
+
+// The optional fields of the dumped templates.
+struct TemplateFields {
+    text: bool,
+    headers: bool,
+    structured_parameters: bool,
 }
 
 impl<'a> TemplateToDump<'a> {
     fn new(
         wikitext: &'a str,
         template: TemplateBorrowed<'a>,
-        with_text: bool,
+        template_node: &'a Node<'a>,
+        context: &TemplateContext<'a>,
+        fields: &TemplateFields,
+        line_numbers: Option<&LineNumbers>,
     ) -> Self {
         let name = template.name;
-        let parameters = template.parameters;
-        let text = if with_text { Some(wikitext) } else { None };
+        let parameters = if fields.structured_parameters {
+            Parameters::Structured(
+                StructuredTemplate::from_node(wikitext, template_node)
+                    .map(|template| template.parameters)
+                    .unwrap_or_default(),
+            )
+        } else {
+            Parameters::Text(template.parameters)
+        };
+        let text = if fields.text {
+            Some(template_node.get_text_from(wikitext))
+        } else {
+            None
+        };
+        let headers = if fields.headers {
+            Some(context.headers().clone())
+        } else {
+            None
+        };
+        let position = line_numbers.map(|line_numbers| TemplatePosition {
+            start: template_node.start(),
+            end: template_node.end(),
+            line: line_numbers.line_number(template_node.start()),
+            enclosing_template: context.enclosing_template(),
+        });
         Self {
             name,
             parameters,
             text,
+            headers,
+            position,
         }
     }
 }
 
-use std::hash::{Hash, Hasher};
-
-struct HashableWriter<W: Write> {
-    id: usize,
-    writer: W,
-}
-
-impl<W: Write> HashableWriter<W> {
-    fn new(writer: W, id: usize) -> Self {
-        Self { id, writer }
-    }
-}
-
-impl<W: Write> Hash for HashableWriter<W> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.id.hash(state);
-    }
-}
-
-impl<W: Write> PartialEq for HashableWriter<W> {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
-    }
+#[derive(Debug)]
+struct TemplatePosition<'a> {
+    // Byte offsets in the page.
+    start: usize,
+    end: usize,
+    // Starting from 1.
+    line: usize,
+    enclosing_template: Option<&'a str>,
 }
 
-impl<W: Write> Eq for HashableWriter<W> {}
+// The byte offset at which each line of a page starts.
+struct LineNumbers(Vec<usize>);
 
-impl<W: Write> Write for HashableWriter<W> {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.writer.write(buf)
+impl LineNumbers {
+    fn new(text: &str) -> Self {
+        LineNumbers(
+            std::iter::once(0)
+                .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+                .collect(),
+        )
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.writer.flush()
+    fn line_number(&self, offset: usize) -> usize {
+        match self.0.binary_search(&offset) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        }
     }
 }
 
-#[derive(PartialEq, Eq, Clone)]
-struct ShareableHashableFile(Rc<RefCell<HashableWriter<BufWriter<File>>>>);
-
-// Cannot derive `Hash` because derive macro does not manage to delegate `Hash`
-// to `HashableWriter`.
-#[allow(clippy::derive_hash_xor_eq)]
-impl Hash for ShareableHashableFile {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        let writer: &HashableWriter<_> = &(*self.0).borrow();
-        writer.hash(state);
-    }
+enum OutputFile {
+    Stream(BufWriter<File>),
+    // `None` once the footer has been written.
+    Parquet(Option<ParquetFile>),
 }
 
-impl Write for ShareableHashableFile {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        (*self.0).borrow_mut().write(buf)
-    }
-
-    fn flush(&mut self) -> std::io::Result<()> {
-        (*self.0).borrow_mut().flush()
-    }
+// What a page has for one output file, in the format of the file.
+enum Serialized {
+    Bytes(Vec<u8>),
+    Rows(TemplateRows),
 }
 
 #[derive(Default)]
 struct FilePool {
-    files: HashMap<String, ShareableHashableFile>,
-    count: usize,
+    files: Vec<(PathBuf, OutputFile)>,
+    ids: HashMap<String, usize>,
 }
 
 impl FilePool {
@@ -187,134 +250,889 @@ impl FilePool {
         Default::default()
     }
 
-    fn create(&mut self, path: &str) -> std::io::Result<ShareableHashableFile> {
-        match self.files.get(path) {
-            Some(f) => Ok((*f).clone()),
+    fn push(&mut self, path: &str, file: OutputFile) -> usize {
+        let id = self.files.len();
+        self.files.push((path.into(), file));
+        self.ids.insert(path.to_string(), id);
+        id
+    }
+
+    // Returns an index that identifies the file, so that worker threads
+    // can refer to it without sharing the file itself.
+    fn create(&mut self, path: &str) -> std::io::Result<usize> {
+        match self.ids.get(path) {
+            Some(id) => Ok(*id),
             None => {
                 let file = File::create(path)?;
                 let file = BufWriter::new(file);
-                let file_ref = ShareableHashableFile(Rc::new(RefCell::new(
-                    HashableWriter::new(file, self.get_file_id()),
-                )));
-                let cloned = file_ref.clone();
-                self.files.insert(path.to_string(), file_ref);
-                Ok(cloned)
+                Ok(self.push(path, OutputFile::Stream(file)))
+            }
+        }
+    }
+
+    fn create_parquet(&mut self, path: &str) -> Result<usize> {
+        if let Some(id) = self.ids.get(path) {
+            return Ok(*id);
+        }
+        let file = File::create(path).map_err(|e| Error::IoError {
+            action: "create",
+            path: path.into(),
+            cause: e,
+        })?;
+        let file = ParquetFile::new(file).map_err(|e| Error::Parquet {
+            path: path.into(),
+            cause: e,
+        })?;
+        Ok(self.push(path, OutputFile::Parquet(Some(file))))
+    }
+
+    fn path(&self, id: usize) -> &Path {
+        &self.files[id].0
+    }
+
+    fn id(&self, path: &Path) -> Option<usize> {
+        path.to_str().and_then(|path| self.ids.get(path)).copied()
+    }
+
+    fn write_all(&mut self, id: usize, buf: &[u8]) -> Result<()> {
+        let (path, file) = &mut self.files[id];
+        let file = match file {
+            OutputFile::Stream(file) => file,
+            OutputFile::Parquet(_) => {
+                panic!("bytes written to Parquet file {}", path.display())
+            }
+        };
+        file.write_all(buf).map_err(|e| Error::IoError {
+            action: "write to",
+            path: path.clone(),
+            cause: e,
+        })
+    }
+
+    fn write(&mut self, id: usize, serialized: Serialized) -> Result<()> {
+        let rows = match serialized {
+            Serialized::Bytes(bytes) => return self.write_all(id, &bytes),
+            Serialized::Rows(rows) => rows,
+        };
+        let (path, file) = &mut self.files[id];
+        match file {
+            OutputFile::Parquet(Some(file)) => {
+                file.write(rows).map_err(|e| Error::Parquet {
+                    path: path.clone(),
+                    cause: e,
+                })
             }
+            _ => panic!("rows written to {}", path.display()),
         }
     }
 
-    fn get_file_id(&mut self) -> usize {
-        let count = self.count;
-        self.count += 1;
-        count
+    // Also writes the rest of the Parquet files, which cannot be written to
+    // afterwards.
+    fn flush(&mut self) -> Result<()> {
+        for (path, file) in &mut self.files {
+            match file {
+                OutputFile::Stream(file) => {
+                    file.flush().map_err(|e| Error::IoError {
+                        action: "write to",
+                        path: path.clone(),
+                        cause: e,
+                    })?
+                }
+                OutputFile::Parquet(file) => {
+                    if let Some(file) = file.take() {
+                        file.finish().map_err(|e| Error::Parquet {
+                            path: path.clone(),
+                            cause: e,
+                        })?
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, Serialize)]
+fn parse_template_name(name: String, namespaces: &Namespaces) -> Result<Title> {
+    Title::from_template_name(&name, namespaces).map_err(|e| {
+        Error::TemplateNameNormalization {
+            title: name,
+            cause: e,
+        }
+    })
+}
+
+// How a template is named in template files: without the prefix if it is in
+// the template namespace.
+fn template_file_name(title: &Title, namespaces: &Namespaces) -> String {
+    if title.namespace() == Namespace::TEMPLATE {
+        title.dbkey().to_string()
+    } else {
+        title.prefixed_text(namespaces).replace(' ', "_")
+    }
+}
+
+// Parse the template names and give the templates without an output file
+// one named after the template.
+fn template_files(
+    templates: Vec<(String, Option<String>)>,
+    extension: &str,
+    namespaces: &Namespaces,
+) -> Result<Vec<(Title, String)>> {
+    templates
+        .into_iter()
+        .map(|(template, path)| {
+            let title = parse_template_name(template, namespaces)?;
+            let path = path.unwrap_or_else(|| {
+                template_file_name(&title, namespaces) + extension
+            });
+            Ok((title, path))
+        })
+        .collect()
+}
+
+fn add_template_redirects(
+    templates: Vec<(String, Option<String>)>,
+    extension: &str,
+    redirects: &TemplateRedirects,
+    namespaces: &Namespaces,
+) -> Result<()> {
+    let template_files = template_files(templates, extension, namespaces)?;
+    let mut lines: Vec<_> = redirects
+        .add_redirects(template_files)
+        .into_iter()
+        .map(|(title, path)| (template_file_name(&title, namespaces), path))
+        .collect();
+    lines.sort_by(|(a, _), (b, _)| {
+        a.to_lowercase()
+            .cmp(&b.to_lowercase())
+            .then_with(|| a.cmp(b))
+    });
+    for (name, path) in lines {
+        println!("{}\t{}", name, path);
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
 struct TemplatesInPage<'a> {
     title: &'a str,
+    revision: Option<RevisionToDump<'a>>,
     templates: &'a [TemplateToDump<'a>],
 }
 
+// Not flattened with serde, for the same reason as `TemplateToDump`.
+impl Serialize for TemplatesInPage<'_> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let len = if self.revision.is_some() { 4 } else { 2 };
+        let mut state = serializer.serialize_struct("TemplatesInPage", len)?;
+        state.serialize_field("title", &self.title)?;
+        if let Some(revision) = &self.revision {
+            state.serialize_field("revision_id", &revision.revision_id)?;
+            state.serialize_field("timestamp", &revision.timestamp)?;
+        }
+        state.serialize_field("templates", &self.templates)?;
+        state.end()
+    }
+}
+
+#[derive(Debug)]
+struct RevisionToDump<'a> {
+    revision_id: Option<u64>,
+    timestamp: Option<&'a str>,
+}
+
+// The options that determine what is written for each page. A cache made
+// with other options is not used.
+#[derive(Serialize)]
+struct CacheSettings<'a> {
+    format: &'static str,
+    templates: BTreeMap<String, &'a Path>,
+    template_normalizations: Option<BTreeMap<String, &'a str>>,
+    include_text: bool,
+    include_headers: bool,
+    include_positions: bool,
+    include_revision: bool,
+    structured_parameters: bool,
+    namespaces: Vec<i32>,
+}
+
+// Finds the templates to dump in a page and serializes them for the files
+// they are written to.
+struct TemplateDumper {
+    format: SerializationFormat,
+    template_to_file: HashMap<Title, usize>,
+    template_normalizations: Option<HashMap<Title, Arc<str>>>,
+    fields: TemplateFields,
+    include_positions: bool,
+    include_revision: bool,
+    namespaces: Namespaces,
+}
+
+impl TemplateDumper {
+    // Creates the output files in `files`.
+    fn new(
+        templates: TemplateDump,
+        files: &mut FilePool,
+        namespaces: &Namespaces,
+    ) -> Result<Self> {
+        let TemplateDump {
+            format,
+            files: template_to_file,
+            template_normalizations,
+            include_text,
+            include_headers,
+            include_positions,
+            include_revision,
+            structured_parameters,
+            redirects,
+        } = templates;
+        let template_normalizations = template_normalizations
+            .map(|normalizations| {
+                normalizations
+                    .into_iter()
+                    .map(|(alias, template)| {
+                        let title = parse_template_name(alias, namespaces)?;
+                        Ok((title, template))
+                    })
+                    .collect::<Result<HashMap<_, _>>>()
+            })
+            .transpose()?;
+        let template_files =
+            template_files(template_to_file, format.extension(), namespaces)?;
+        let template_files = match redirects {
+            Some(redirects) => redirects
+                .add_redirects(template_files)
+                .into_iter()
+                .collect(),
+            None => template_files,
+        };
+        let template_to_file = template_files
+            .into_iter()
+            .map(|(title, path)| {
+                let file = if format == SerializationFormat::Parquet {
+                    files.create_parquet(&path)?
+                } else {
+                    files.create(&path).map_err(|e| Error::IoError {
+                        action: "create",
+                        path: path.into(),
+                        cause: e,
+                    })?
+                };
+                Ok((title, file))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        Ok(TemplateDumper {
+            format,
+            template_to_file,
+            template_normalizations,
+            fields: TemplateFields {
+                text: include_text,
+                headers: include_headers,
+                structured_parameters,
+            },
+            include_positions,
+            include_revision,
+            namespaces: namespaces.clone(),
+        })
+    }
+
+    // Returns the serialized templates for each file that the page has
+    // templates for.
+    fn dump_page<'a>(
+        &self,
+        page: &'a Page,
+        nodes: &'a [Node<'a>],
+    ) -> Result<Vec<(usize, Serialized)>> {
+        let mut templates_to_print: BTreeMap<usize, Vec<TemplateToDump>> =
+            BTreeMap::new();
+        let wikitext = &page.text;
+        let line_numbers = if self.include_positions {
+            Some(LineNumbers::new(wikitext))
+        } else {
+            None
+        };
+        let visitor = TemplateVisitor::new(wikitext);
+        visitor.visit_with_context(
+            nodes,
+            &mut |mut template, template_node, context| {
+                if let Ok(title) =
+                    Title::from_template_name(&template.name, &self.namespaces)
+                {
+                    if let Some(file) = self.template_to_file.get(&title) {
+                        if let Some(normalizations) =
+                            &self.template_normalizations
+                        {
+                            template.name = normalizations
+                                .get(&title)
+                                .map(|normalized| {
+                                    Cow::Borrowed(normalized.as_ref())
+                                })
+                                .unwrap_or_else(|| {
+                                    Cow::Owned(title.dbkey().to_string())
+                                });
+                        }
+                        let templates = templates_to_print
+                            .entry(*file)
+                            .or_insert_with(Vec::new);
+                        templates.push(TemplateToDump::new(
+                            wikitext,
+                            template,
+                            template_node,
+                            context,
+                            &self.fields,
+                            line_numbers.as_ref(),
+                        ));
+                    }
+                }
+            },
+        );
+        templates_to_print
+            .into_iter()
+            .map(|(file, templates)| -> Result<_> {
+                let revision = if self.include_revision {
+                    Some(RevisionToDump {
+                        revision_id: page.revision_id,
+                        timestamp: page.timestamp.as_deref(),
+                    })
+                } else {
+                    None
+                };
+                let output = TemplatesInPage {
+                    title: &page.title,
+                    revision,
+                    templates: &templates,
+                };
+                let mut serialized = Vec::new();
+                match self.format {
+                    SerializationFormat::Json => {
+                        serde_json::to_writer(&mut serialized, &output)?;
+                        serialized.push(b'\n');
+                    }
+                    SerializationFormat::Cbor => {
+                        serde_cbor::to_writer(&mut serialized, &output)?;
+                    }
+                    SerializationFormat::Parquet => {
+                        let mut rows = TemplateRows::default();
+                        for template in output.templates {
+                            rows.push(
+                                output.title,
+                                page.namespace,
+                                output.revision.as_ref(),
+                                template,
+                            );
+                        }
+                        return Ok((file, Serialized::Rows(rows)));
+                    }
+                }
+                Ok((file, Serialized::Bytes(serialized)))
+            })
+            .collect()
+    }
+
+    fn cache_settings(
+        &self,
+        files: &FilePool,
+        namespaces: &[Namespace],
+    ) -> Result<String> {
+        let mut namespaces: Vec<_> =
+            namespaces.iter().map(|namespace| namespace.0).collect();
+        namespaces.sort_unstable();
+        let settings = CacheSettings {
+            format: self.format.extension(),
+            templates: self
+                .template_to_file
+                .iter()
+                .map(|(title, &file)| {
+                    (title.prefixed_text(&self.namespaces), files.path(file))
+                })
+                .collect(),
+            template_normalizations: self.template_normalizations.as_ref().map(
+                |normalizations| {
+                    normalizations
+                        .iter()
+                        .map(|(title, template)| {
+                            (
+                                title.prefixed_text(&self.namespaces),
+                                template.as_ref(),
+                            )
+                        })
+                        .collect()
+                },
+            ),
+            include_text: self.fields.text,
+            include_headers: self.fields.headers,
+            include_positions: self.include_positions,
+            include_revision: self.include_revision,
+            structured_parameters: self.fields.structured_parameters,
+            namespaces,
+        };
+        Ok(serde_json::to_string(&settings)?)
+    }
+}
+
+// A page that was parsed because it is not in the cache or has changed.
+struct ParsedPage {
+    title: String,
+    sha1: Option<String>,
+    outputs: Vec<(usize, Serialized)>,
+}
+
 fn dump_parsed_templates(
-    options: DumpParsedTemplates,
+    templates: TemplateDump,
+    cache_path: Option<&Path>,
+    adds_changes: bool,
+    options: DumpOptions,
     main_start: Instant,
     verbose: bool,
 ) -> Result<()> {
-    let DumpParsedTemplates {
-        format,
-        files: template_to_file,
-        template_normalizations,
-        include_text,
-        dump_options:
-            DumpOptions {
-                pages,
-                namespaces,
-                dump_file,
-            },
+    let DumpOptions {
+        pages,
+        namespaces,
+        configuration,
+        dump,
+        threads,
+        siteinfo,
     } = options;
-    let template_normalizations_ref = template_normalizations.as_ref();
-    let parser = parse_dump(dump_file).take(pages);
     let mut files = FilePool::new();
-    let extension = match format {
-        SerializationFormat::Cbor => ".cbor",
-        SerializationFormat::Json => ".jsonl",
+    let dumper =
+        TemplateDumper::new(templates, &mut files, &siteinfo.namespaces)?;
+    // The workers check whether pages are in the cache while the pages they
+    // have parsed are added to it.
+    let cache = match cache_path {
+        Some(path) => {
+            let settings = dumper.cache_settings(&files, &namespaces)?;
+            Some(RwLock::new(Cache::read(path, settings)?))
+        }
+        None => None,
     };
-    let template_to_file = template_to_file
-        .into_iter()
-        .map(|(template, path)| {
-            let normalized = normalize_title(&template).map_err(|e| {
-                Error::TemplateNameNormalization { title: template, cause: e }
-            })?;
-            let path =
-            path.unwrap_or_else(|| normalized.clone() + extension);
-            let file = files.create(&path).map_err(|e| {
-                Error::IoError { action: "create", path: path.into(), cause: e }
-            })?;
+    let start_time = main_start.elapsed();
+    let parse_start = Instant::now();
+    let pages = dump.into_pages().take(pages).filter(|result| match result {
+        Ok(page) => namespaces.contains(&page.namespace),
+        Err(_) => true,
+    });
+    let mut changed = 0;
+    let mut seen = HashSet::new();
+    process_in_order(
+        pages,
+        threads,
+        // The page ID, and the page unless it is in the cache.
+        |page| -> Result<(Option<u64>, Option<ParsedPage>)> {
+            if let Some(cache) = &cache {
+                if page.id.is_none() {
+                    return Err(Error::MissingPageId(page.title));
+                }
+                if cache.read().unwrap().is_current(&page) {
+                    return Ok((page.id, None));
+                }
+            }
+            let output = configuration.parse(&page.text);
+            if verbose {
+                print_parser_warnings(&page, &output.warnings);
+            }
+            let outputs = dumper.dump_page(&page, &output.nodes)?;
             Ok((
-                normalized,
-                file,
+                page.id,
+                Some(ParsedPage {
+                    title: page.title.clone(),
+                    sha1: page.sha1.clone(),
+                    outputs,
+                }),
             ))
-        })
-        .collect::<Result<HashMap<_, _>>>()?;
-    let configuration = dump_parser::wiktionary_configuration();
+        },
+        |result| {
+            let (id, parsed) = result?;
+            if cache.is_some() {
+                seen.extend(id);
+            }
+            let parsed = match parsed {
+                Some(parsed) => parsed,
+                None => return Ok(()),
+            };
+            match (&cache, id) {
+                // The workers return an error for pages without an ID.
+                (Some(cache), Some(id)) => {
+                    // The cache is not allowed with the Parquet format.
+                    let outputs = parsed
+                        .outputs
+                        .into_iter()
+                        .filter_map(|(file, serialized)| match serialized {
+                            Serialized::Bytes(bytes) => Some((file, bytes)),
+                            Serialized::Rows(_) => None,
+                        })
+                        .collect();
+                    cache.write().unwrap().insert(
+                        id,
+                        parsed.title,
+                        parsed.sha1,
+                        outputs,
+                        &files,
+                    );
+                    changed += 1;
+                }
+                _ => {
+                    for (file, serialized) in parsed.outputs {
+                        files.write(file, serialized)?;
+                    }
+                }
+            }
+            Ok(())
+        },
+    )?;
+    if let (Some(cache), Some(cache_path)) = (cache, cache_path) {
+        eprintln!("{} pages were parsed and added to the cache", changed);
+        let mut cache = cache.into_inner().unwrap();
+        if !adds_changes {
+            // The options that leave out pages cannot be used with the
+            // cache, so the pages that were not seen have been deleted.
+            cache.retain(&seen);
+        }
+        cache.write_outputs(&mut files)?;
+        cache.write(cache_path)?;
+    }
+    files.flush()?;
+    let parse_time = parse_start.elapsed();
+    eprintln!(
+        "startup took {}, parsing and printing {}",
+        print_time(&start_time).unwrap(),
+        print_time(&parse_time).unwrap()
+    );
+    Ok(())
+}
+
+// Parses the pages in the processed namespaces on the worker threads and
+// prints what `serialize` writes for each of them to standard output, in the
+// order of the dump.
+fn dump_to_stdout<F>(
+    options: DumpOptions,
+    main_start: Instant,
+    verbose: bool,
+    serialize: F,
+) -> Result<()>
+where
+    F: for<'a> Fn(&'a Page, &'a [Node<'a>], &mut Vec<u8>) -> Result<()> + Sync,
+{
+    let DumpOptions {
+        pages,
+        namespaces,
+        configuration,
+        dump,
+        threads,
+        ..
+    } = options;
     let start_time = main_start.elapsed();
     let parse_start = Instant::now();
-    for page in parser {
-        let page = page?;
-        if !namespaces.contains(
-            &page.namespace
-        ) {
-            continue;
-        }
-        let mut templates_to_print: HashMap<
-            ShareableHashableFile,
-            Vec<TemplateToDump>,
-        > = HashMap::new();
-        let wikitext = &page.text;
-        let output = configuration.parse(wikitext);
-        if verbose {
-            print_parser_warnings(&page, &output.warnings);
-        }
-        let visitor = TemplateVisitor::new(wikitext);
-        visitor.visit(&output.nodes, &mut |mut template, template_node| {
-            if let Ok(name) = normalize_title(&template.name) {
-                if let Some(file) = template_to_file.get(&name) {
-                    if let Some(normalizations) = template_normalizations_ref {
-                        template.name = normalizations
-                            .get(&name)
-                            .map(|normalized| {
-                                Cow::Borrowed(normalized.as_ref())
-                            })
-                            .unwrap_or_else(|| Cow::Owned(name));
+    let pages = dump.into_pages().take(pages).filter(|result| match result {
+        Ok(page) => namespaces.contains(&page.namespace),
+        Err(_) => true,
+    });
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    process_in_order(
+        pages,
+        threads,
+        |page| -> Result<Vec<u8>> {
+            let output = configuration.parse(&page.text);
+            if verbose {
+                print_parser_warnings(&page, &output.warnings);
+            }
+            let mut serialized = Vec::new();
+            serialize(&page, &output.nodes, &mut serialized)?;
+            Ok(serialized)
+        },
+        |serialized| {
+            stdout.write_all(&serialized?).map_err(|e| Error::IoError {
+                action: "write to",
+                path: "standard output".into(),
+                cause: e,
+            })
+        },
+    )?;
+    let parse_time = parse_start.elapsed();
+    eprintln!(
+        "startup took {}, parsing and printing {}",
+        print_time(&start_time).unwrap(),
+        print_time(&parse_time).unwrap()
+    );
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct Invocation<'a> {
+    module: String,
+    function: Option<&'a str>,
+    count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct InvocationsInPage<'a> {
+    title: &'a str,
+    invocations: Vec<Invocation<'a>>,
+}
+
+// The title of a module as in `{{#invoke:}}`, with or without the namespace
+// prefix.
+fn module_title(name: &str, namespaces: &Namespaces) -> String {
+    Title::new(name, Namespace::MODULE, namespaces)
+        .map(|title| title.prefixed_text(namespaces))
+        .unwrap_or_else(|_| name.to_string())
+}
+
+fn dump_invocations(
+    modules: Vec<String>,
+    options: DumpOptions,
+    main_start: Instant,
+    verbose: bool,
+) -> Result<()> {
+    let namespaces = options.siteinfo.namespaces.clone();
+    let modules: HashSet<_> = modules
+        .iter()
+        .map(|module| module_title(module, &namespaces))
+        .collect();
+    dump_to_stdout(options, main_start, verbose, |page, nodes, serialized| {
+        let mut counts = BTreeMap::new();
+        TemplateVisitor::new(&page.text).visit_parser_functions(
+            nodes,
+            &mut |function, _, _| {
+                if let Some((module, function)) = function.invocation() {
+                    let module = module_title(module, &namespaces);
+                    if modules.is_empty() || modules.contains(&module) {
+                        *counts.entry((module, function)).or_insert(0) += 1;
                     }
-                    let templates = templates_to_print
-                        .entry(file.clone())
-                        .or_insert_with(Vec::new);
-                    templates.push(TemplateToDump::new(
-                        template_node.get_text_from(&wikitext),
-                        template,
-                        include_text,
-                    ));
                 }
+            },
+        );
+        if !counts.is_empty() {
+            let invocations = counts
+                .into_iter()
+                .map(|((module, function), count)| Invocation {
+                    module,
+                    function,
+                    count,
+                })
+                .collect();
+            serde_json::to_writer(
+                &mut *serialized,
+                &InvocationsInPage {
+                    title: &page.title,
+                    invocations,
+                },
+            )?;
+            serialized.push(b'\n');
+        }
+        Ok(())
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct SectionToDump<'a> {
+    title: &'a str,
+    language: &'a str,
+    text: &'a str,
+}
+
+fn extract_sections(
+    languages: Vec<String>,
+    options: DumpOptions,
+    main_start: Instant,
+    verbose: bool,
+) -> Result<()> {
+    dump_to_stdout(options, main_start, verbose, |page, nodes, serialized| {
+        for section in language_sections(page, nodes) {
+            if languages.iter().any(|language| language == section.name) {
+                serde_json::to_writer(
+                    &mut *serialized,
+                    &SectionToDump {
+                        title: &page.title,
+                        language: section.name,
+                        text: section.text(&page.text),
+                    },
+                )?;
+                serialized.push(b'\n');
             }
-        });
-        for (mut file, templates) in templates_to_print {
-            let output = TemplatesInPage {
+        }
+        Ok(())
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct EntryToDump<'a> {
+    title: &'a str,
+    #[serde(flatten)]
+    entry: Entry<'a>,
+}
+
+fn dump_entries(
+    languages: Vec<String>,
+    options: DumpOptions,
+    main_start: Instant,
+    verbose: bool,
+) -> Result<()> {
+    dump_to_stdout(options, main_start, verbose, |page, nodes, serialized| {
+        for entry in entries(page, nodes) {
+            if languages.is_empty()
+                || languages.iter().any(|language| language == entry.language)
+            {
+                serde_json::to_writer(
+                    &mut *serialized,
+                    &EntryToDump {
+                        title: &page.title,
+                        entry,
+                    },
+                )?;
+                serialized.push(b'\n');
+            }
+        }
+        Ok(())
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct TranslationToDump<'a> {
+    title: &'a str,
+    #[serde(flatten)]
+    translation: Translation<'a>,
+}
+
+fn dump_translations(
+    options: DumpOptions,
+    main_start: Instant,
+    verbose: bool,
+) -> Result<()> {
+    dump_to_stdout(options, main_start, verbose, |page, nodes, serialized| {
+        for translation in translations(&page.text, nodes) {
+            serde_json::to_writer(
+                &mut *serialized,
+                &TranslationToDump {
+                    title: &page.title,
+                    translation,
+                },
+            )?;
+            serialized.push(b'\n');
+        }
+        Ok(())
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct PronunciationsToDump<'a> {
+    title: &'a str,
+    #[serde(flatten)]
+    pronunciations: LanguagePronunciation<'a>,
+}
+
+fn dump_pronunciations(
+    format: SerializationFormat,
+    options: DumpOptions,
+    main_start: Instant,
+    verbose: bool,
+) -> Result<()> {
+    dump_to_stdout(options, main_start, verbose, |page, nodes, serialized| {
+        for pronunciations in pronunciations(&page.text, nodes) {
+            let output = PronunciationsToDump {
                 title: &page.title,
-                templates: &templates,
+                pronunciations,
             };
-            let mut writer = &mut *file.borrow_mut();
             match format {
                 SerializationFormat::Json => {
-                    serde_json::to_writer(&mut writer, &output)?;
-                    write!(&mut writer, "\n").unwrap();
+                    serde_json::to_writer(&mut *serialized, &output)?;
+                    serialized.push(b'\n');
                 }
                 SerializationFormat::Cbor => {
-                    serde_cbor::to_writer(&mut writer, &output)?;
+                    serde_cbor::to_writer(&mut *serialized, &output)?;
                 }
+                // Rejected when reading the arguments.
+                SerializationFormat::Parquet => unreachable!(),
             }
         }
+        Ok(())
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct EtymologyToDump<'a> {
+    title: &'a str,
+    #[serde(flatten)]
+    etymology: Etymology<'a>,
+}
+
+fn dump_etymologies(
+    options: DumpOptions,
+    main_start: Instant,
+    verbose: bool,
+) -> Result<()> {
+    dump_to_stdout(options, main_start, verbose, |page, nodes, serialized| {
+        for etymology in etymologies(page, nodes) {
+            serde_json::to_writer(
+                &mut *serialized,
+                &EtymologyToDump {
+                    title: &page.title,
+                    etymology,
+                },
+            )?;
+            serialized.push(b'\n');
+        }
+        Ok(())
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct LinksInPage<'a> {
+    title: &'a str,
+    links: Vec<LinkInPage<'a>>,
+}
+
+fn dump_links(
+    include_language: bool,
+    options: DumpOptions,
+    main_start: Instant,
+    verbose: bool,
+) -> Result<()> {
+    let namespaces = options.siteinfo.namespaces.clone();
+    dump_to_stdout(options, main_start, verbose, |page, nodes, serialized| {
+        let links = links(&page.text, nodes, &namespaces, include_language);
+        if !links.is_empty() {
+            serde_json::to_writer(
+                &mut *serialized,
+                &LinksInPage {
+                    title: &page.title,
+                    links,
+                },
+            )?;
+            serialized.push(b'\n');
+        }
+        Ok(())
+    })
+}
+
+fn dump_redirects(options: DumpOptions, main_start: Instant) -> Result<()> {
+    let DumpOptions {
+        pages,
+        namespaces,
+        dump,
+        ..
+    } = options;
+    let start_time = main_start.elapsed();
+    let parse_start = Instant::now();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for page in dump.into_pages().take(pages) {
+        let page = page?;
+        if !namespaces.contains(&page.namespace) {
+            continue;
+        }
+        if let Some(target) = page.redirect {
+            writeln!(stdout, "{}\t{}", page.title, target).map_err(|e| {
+                Error::IoError {
+                    action: "write to",
+                    path: "standard output".into(),
+                    cause: e,
+                }
+            })?;
+        }
     }
     let parse_time = parse_start.elapsed();
     eprintln!(
@@ -330,18 +1148,92 @@ fn try_main() -> Result<()> {
     let opts = args::get_opts()?;
     let verbose = opts.verbose;
     match opts.cmd {
-        CommandData::DumpParsedTemplates(options) => {
-            dump_parsed_templates(options, main_start, verbose)?;
+        CommandData::DumpParsedTemplates {
+            templates,
+            cache,
+            adds_changes,
+            dump_options,
+        } => {
+            dump_parsed_templates(
+                templates,
+                cache.as_deref(),
+                adds_changes,
+                dump_options,
+                main_start,
+                verbose,
+            )?;
+        }
+        CommandData::Run { jobs, dump_options } => {
+            run::run(jobs, dump_options, main_start, verbose)?;
+        }
+        CommandData::DumpInvocations {
+            modules,
+            dump_options,
+        } => {
+            dump_invocations(modules, dump_options, main_start, verbose)?;
+        }
+        CommandData::ExtractSections {
+            languages,
+            dump_options,
+        } => {
+            extract_sections(languages, dump_options, main_start, verbose)?;
+        }
+        CommandData::DumpEntries {
+            languages,
+            dump_options,
+        } => {
+            dump_entries(languages, dump_options, main_start, verbose)?;
+        }
+        CommandData::DumpTranslations { dump_options } => {
+            dump_translations(dump_options, main_start, verbose)?;
+        }
+        CommandData::DumpPronunciations {
+            format,
+            dump_options,
+        } => {
+            dump_pronunciations(format, dump_options, main_start, verbose)?;
+        }
+        CommandData::DumpEtymologies { dump_options } => {
+            dump_etymologies(dump_options, main_start, verbose)?;
+        }
+        CommandData::DumpLinks {
+            include_language,
+            dump_options,
+        } => {
+            dump_links(include_language, dump_options, main_start, verbose)?;
+        }
+        CommandData::DumpRedirects { dump_options } => {
+            dump_redirects(dump_options, main_start)?;
+        }
+        CommandData::LinkReport {
+            pretty,
+            titles,
+            dump_options,
+        } => {
+            link_report::link_report(
+                pretty,
+                titles,
+                dump_options,
+                main_start,
+                verbose,
+            )?;
         }
         CommandData::AllHeaders {
             pretty,
             dump_options: opts,
         } => {
-            let parser = parse_dump(opts.dump_file);
+            let pages = opts.dump.into_pages();
             let mut dumper = HeaderStats::new();
             let start_time = main_start.elapsed();
             let parse_start = Instant::now();
-            dumper.parse(parser, opts.pages, opts.namespaces, verbose);
+            dumper.parse(
+                pages,
+                opts.pages,
+                opts.namespaces,
+                &opts.configuration,
+                opts.threads,
+                verbose,
+            )?;
             do_dumping(&dumper, pretty).unwrap_or_else(|e| eprintln!("{}", e));
             let parse_time = parse_start.elapsed();
             eprintln!(
@@ -356,12 +1248,19 @@ fn try_main() -> Result<()> {
             pretty,
             dump_options: opts,
         } => {
-            let parser = parse_dump(opts.dump_file);
+            let pages = opts.dump.into_pages();
             let mut filterer =
                 HeaderFilterer::new(top_level_headers, other_headers);
             let start_time = main_start.elapsed();
             let parse_start = Instant::now();
-            filterer.parse(parser, opts.pages, opts.namespaces, verbose);
+            filterer.parse(
+                pages,
+                opts.pages,
+                opts.namespaces,
+                &opts.configuration,
+                opts.threads,
+                verbose,
+            )?;
             do_dumping(&filterer, pretty)?;
             let parse_time = parse_start.elapsed();
             eprintln!(
@@ -370,6 +1269,31 @@ fn try_main() -> Result<()> {
                 print_time(&parse_time).unwrap()
             );
         }
+        CommandData::AddTemplateRedirects {
+            templates,
+            format,
+            redirects,
+            namespaces,
+        } => {
+            add_template_redirects(
+                templates,
+                format.extension(),
+                &redirects,
+                &namespaces,
+            )?;
+        }
+        CommandData::DiffSiteConfig { path, site_config } => {
+            let differences = SiteConfig::wiktionary().diff(&site_config);
+            if differences.is_empty() {
+                eprintln!(
+                    "the built-in configuration matches {}",
+                    path.display()
+                );
+            }
+            for difference in differences {
+                print!("{}", difference);
+            }
+        }
         CommandData::Completions { shell } => {
             Args::clap().gen_completions_to(
                 env!("CARGO_PKG_NAME"),
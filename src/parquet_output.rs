@@ -0,0 +1,380 @@
+//! Parquet files of `dump-parsed-templates --format parquet`, with a row for
+//! each template. Every file has the same schema whatever options are given;
+//! the columns of the fields that are not included are null.
+use dump_parser::Namespace;
+use parquet::{
+    basic::Compression,
+    data_type::{ByteArray, ByteArrayType, DataType, Int32Type, Int64Type},
+    errors::Result as ParquetResult,
+    file::{
+        properties::WriterProperties,
+        writer::{SerializedFileWriter, SerializedRowGroupWriter},
+    },
+    schema::parser::parse_message_type,
+};
+use std::{fs::File, io::BufWriter, mem, sync::Arc};
+
+use crate::{Parameters, RevisionToDump, TemplateToDump};
+
+const SCHEMA: &str = "
+message template {
+    required binary title (UTF8);
+    required int32 namespace;
+    optional int64 revision_id;
+    optional binary timestamp (UTF8);
+    required binary name (UTF8);
+    required group parameters (MAP) {
+        repeated group key_value {
+            required binary key (UTF8);
+            required binary value (UTF8);
+        }
+    }
+    optional binary text (UTF8);
+    optional group headers (LIST) {
+        repeated group list {
+            required binary element (UTF8);
+        }
+    }
+    optional int64 start;
+    optional int64 end;
+    optional int64 line;
+    optional binary enclosing_template (UTF8);
+}
+";
+
+/// The number of templates in each row group. The rows are buffered until
+/// there are this many, so this bounds the memory used for each file.
+const ROW_GROUP_SIZE: usize = 100_000;
+
+// The values of a column with their definition and repetition levels.
+struct Column<T> {
+    values: Vec<T>,
+    definition_levels: Vec<i16>,
+    repetition_levels: Vec<i16>,
+}
+
+// Not derived, which would require `T: Default`.
+impl<T> Default for Column<T> {
+    fn default() -> Self {
+        Column {
+            values: Vec::new(),
+            definition_levels: Vec::new(),
+            repetition_levels: Vec::new(),
+        }
+    }
+}
+
+impl<T> Column<T> {
+    fn push_optional(&mut self, value: Option<T>) {
+        match value {
+            Some(value) => {
+                self.values.push(value);
+                self.definition_levels.push(1);
+            }
+            None => self.definition_levels.push(0),
+        }
+    }
+
+    // Pushes the items of a repeated field. `level` is the definition level
+    // of an item, one more than that of an empty list.
+    fn push_repeated(
+        &mut self,
+        items: impl IntoIterator<Item = T>,
+        level: i16,
+    ) {
+        let len = self.values.len();
+        self.values.extend(items);
+        let count = self.values.len() - len;
+        if count == 0 {
+            self.definition_levels.push(level - 1);
+            self.repetition_levels.push(0);
+        } else {
+            let levels = self.definition_levels.len() + count;
+            self.definition_levels.resize(levels, level);
+            // The first item starts a row.
+            self.repetition_levels.push(0);
+            self.repetition_levels.resize(levels, 1);
+        }
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        self.values.append(&mut other.values);
+        self.definition_levels.append(&mut other.definition_levels);
+        self.repetition_levels.append(&mut other.repetition_levels);
+    }
+}
+
+/// The templates of one or more pages, by column.
+#[derive(Default)]
+pub struct TemplateRows {
+    len: usize,
+    title: Vec<ByteArray>,
+    namespace: Vec<i32>,
+    revision_id: Column<i64>,
+    timestamp: Column<ByteArray>,
+    name: Vec<ByteArray>,
+    parameter_key: Column<ByteArray>,
+    parameter_value: Column<ByteArray>,
+    text: Column<ByteArray>,
+    headers: Column<ByteArray>,
+    start: Column<i64>,
+    end: Column<i64>,
+    line: Column<i64>,
+    enclosing_template: Column<ByteArray>,
+}
+
+impl TemplateRows {
+    pub fn push(
+        &mut self,
+        title: &str,
+        namespace: Namespace,
+        revision: Option<&RevisionToDump>,
+        template: &TemplateToDump,
+    ) {
+        self.len += 1;
+        self.title.push(title.into());
+        self.namespace.push(namespace.0);
+        self.revision_id.push_optional(
+            revision
+                .and_then(|revision| revision.revision_id)
+                .map(|id| id as i64),
+        );
+        self.timestamp.push_optional(
+            revision
+                .and_then(|revision| revision.timestamp)
+                .map(ByteArray::from),
+        );
+        self.name.push(ByteArray::from(&*template.name));
+        match &template.parameters {
+            Parameters::Text(parameters) => {
+                self.parameter_key.push_repeated(
+                    parameters.keys().map(|key| ByteArray::from(&**key)),
+                    1,
+                );
+                self.parameter_value.push_repeated(
+                    parameters.values().map(|&value| ByteArray::from(value)),
+                    1,
+                );
+            }
+            // Not allowed with this format.
+            Parameters::Structured(_) => {
+                self.parameter_key.push_repeated(None, 1);
+                self.parameter_value.push_repeated(None, 1);
+            }
+        }
+        self.text.push_optional(template.text.map(ByteArray::from));
+        match &template.headers {
+            Some(headers) => self
+                .headers
+                .push_repeated(headers.iter().map(ByteArray::from), 2),
+            None => {
+                self.headers.definition_levels.push(0);
+                self.headers.repetition_levels.push(0);
+            }
+        }
+        let position = template.position.as_ref();
+        self.start
+            .push_optional(position.map(|position| position.start as i64));
+        self.end
+            .push_optional(position.map(|position| position.end as i64));
+        self.line
+            .push_optional(position.map(|position| position.line as i64));
+        self.enclosing_template.push_optional(
+            position
+                .and_then(|position| position.enclosing_template)
+                .map(ByteArray::from),
+        );
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        self.len += mem::take(&mut other.len);
+        self.title.append(&mut other.title);
+        self.namespace.append(&mut other.namespace);
+        self.revision_id.append(&mut other.revision_id);
+        self.timestamp.append(&mut other.timestamp);
+        self.name.append(&mut other.name);
+        self.parameter_key.append(&mut other.parameter_key);
+        self.parameter_value.append(&mut other.parameter_value);
+        self.text.append(&mut other.text);
+        self.headers.append(&mut other.headers);
+        self.start.append(&mut other.start);
+        self.end.append(&mut other.end);
+        self.line.append(&mut other.line);
+        self.enclosing_template
+            .append(&mut other.enclosing_template);
+    }
+}
+
+type Writer = BufWriter<File>;
+
+// Writes the next column of the row group, in the order of the schema.
+fn write_column<T: DataType>(
+    row_group: &mut SerializedRowGroupWriter<'_, Writer>,
+    values: &[T::T],
+    definition_levels: Option<&[i16]>,
+    repetition_levels: Option<&[i16]>,
+) -> ParquetResult<()> {
+    let mut column = row_group
+        .next_column()?
+        .expect("a column for each leaf field of the schema");
+    column.typed::<T>().write_batch(
+        values,
+        definition_levels,
+        repetition_levels,
+    )?;
+    column.close()
+}
+
+fn write_optional<T: DataType>(
+    row_group: &mut SerializedRowGroupWriter<'_, Writer>,
+    column: &Column<T::T>,
+) -> ParquetResult<()> {
+    write_column::<T>(
+        row_group,
+        &column.values,
+        Some(&column.definition_levels),
+        // Only repeated fields have repetition levels.
+        Some(column.repetition_levels.as_slice())
+            .filter(|levels| !levels.is_empty()),
+    )
+}
+
+pub struct ParquetFile {
+    writer: SerializedFileWriter<Writer>,
+    rows: TemplateRows,
+}
+
+impl ParquetFile {
+    pub fn new(file: File) -> ParquetResult<Self> {
+        let schema = Arc::new(parse_message_type(SCHEMA)?);
+        let properties = Arc::new(
+            WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build(),
+        );
+        Ok(ParquetFile {
+            writer: SerializedFileWriter::new(
+                BufWriter::new(file),
+                schema,
+                properties,
+            )?,
+            rows: TemplateRows::default(),
+        })
+    }
+
+    pub fn write(&mut self, mut rows: TemplateRows) -> ParquetResult<()> {
+        self.rows.append(&mut rows);
+        if self.rows.len >= ROW_GROUP_SIZE {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn write_row_group(&mut self) -> ParquetResult<()> {
+        let rows = mem::take(&mut self.rows);
+        let mut row_group = self.writer.next_row_group()?;
+        write_column::<ByteArrayType>(&mut row_group, &rows.title, None, None)?;
+        write_column::<Int32Type>(&mut row_group, &rows.namespace, None, None)?;
+        write_optional::<Int64Type>(&mut row_group, &rows.revision_id)?;
+        write_optional::<ByteArrayType>(&mut row_group, &rows.timestamp)?;
+        write_column::<ByteArrayType>(&mut row_group, &rows.name, None, None)?;
+        write_optional::<ByteArrayType>(&mut row_group, &rows.parameter_key)?;
+        write_optional::<ByteArrayType>(&mut row_group, &rows.parameter_value)?;
+        write_optional::<ByteArrayType>(&mut row_group, &rows.text)?;
+        write_optional::<ByteArrayType>(&mut row_group, &rows.headers)?;
+        write_optional::<Int64Type>(&mut row_group, &rows.start)?;
+        write_optional::<Int64Type>(&mut row_group, &rows.end)?;
+        write_optional::<Int64Type>(&mut row_group, &rows.line)?;
+        write_optional::<ByteArrayType>(
+            &mut row_group,
+            &rows.enclosing_template,
+        )?;
+        row_group.close()?;
+        Ok(())
+    }
+
+    /// Writes the remaining rows and the footer.
+    pub fn finish(mut self) -> ParquetResult<()> {
+        if self.rows.len > 0 {
+            self.write_row_group()?;
+        }
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParquetFile, TemplateRows};
+    use crate::{Parameters, RevisionToDump, TemplatePosition, TemplateToDump};
+    use dump_parser::Namespace;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use std::{collections::BTreeMap, fs::File};
+
+    #[test]
+    fn template_rows() {
+        let path =
+            std::env::temp_dir().join("enwikt-dump-rs-templates.parquet");
+        let mut parameters = BTreeMap::new();
+        parameters.insert("1".into(), "en");
+        parameters.insert("2".into(), "cat");
+        let templates = [
+            TemplateToDump {
+                name: "l".into(),
+                parameters: Parameters::Text(parameters),
+                text: Some("{{l|en|cat}}"),
+                headers: None,
+                position: Some(TemplatePosition {
+                    start: 7,
+                    end: 19,
+                    line: 2,
+                    enclosing_template: None,
+                }),
+            },
+            TemplateToDump {
+                name: "-".into(),
+                parameters: Parameters::Text(BTreeMap::new()),
+                text: None,
+                headers: None,
+                position: None,
+            },
+        ];
+        let revision = RevisionToDump {
+            revision_id: Some(1),
+            timestamp: Some("2020-07-28T12:00:00Z"),
+        };
+        let mut file = ParquetFile::new(File::create(&path).unwrap()).unwrap();
+        for template in &templates {
+            let mut rows = TemplateRows::default();
+            rows.push("cat", Namespace::MAIN, Some(&revision), template);
+            file.write(rows).unwrap();
+        }
+        file.finish().unwrap();
+        let rows = SerializedFileReader::new(File::open(&path).unwrap())
+            .unwrap()
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().to_string())
+            .collect::<Vec<_>>();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                concat!(
+                    r#"{title: "cat", namespace: 0, revision_id: 1, "#,
+                    r#"timestamp: "2020-07-28T12:00:00Z", name: "l", "#,
+                    r#"parameters: {"1" -> "en", "2" -> "cat"}, "#,
+                    r#"text: "{{l|en|cat}}", headers: null, start: 7, "#,
+                    r#"end: 19, line: 2, enclosing_template: null}"#,
+                ),
+                concat!(
+                    r#"{title: "cat", namespace: 0, revision_id: 1, "#,
+                    r#"timestamp: "2020-07-28T12:00:00Z", name: "-", "#,
+                    r#"parameters: {}, text: null, headers: null, "#,
+                    r#"start: null, end: null, line: null, "#,
+                    r#"enclosing_template: null}"#,
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! The pronunciation templates in the Pronunciation sections of Wiktionary
+//! entries: `{{IPA}}`, `{{audio}}`, `{{rhymes}}`, `{{hyphenation}}` and
+//! `{{homophones}}`, with the accents given by `{{a}}` before them on the
+//! same line.
+use dump_parser::{Node, Positioned};
+use serde::Serialize;
+use template_iter::{TemplateBorrowed, TemplateVisitor};
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PronunciationKind<'a> {
+    Ipa {
+        pronunciations: Vec<&'a str>,
+    },
+    Audio {
+        file: &'a str,
+        caption: Option<&'a str>,
+    },
+    Rhymes {
+        rhymes: Vec<&'a str>,
+    },
+    /// The syllables of each hyphenation. Alternative hyphenations are
+    /// separated by an empty parameter in the template.
+    Hyphenation {
+        hyphenations: Vec<Vec<&'a str>>,
+    },
+    Homophones {
+        homophones: Vec<&'a str>,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PronunciationItem<'a> {
+    #[serde(flatten)]
+    pub kind: PronunciationKind<'a>,
+    /// The labels in the `{{a}}` template before the item on the same line,
+    /// and the `a` parameter of `{{IPA}}`.
+    pub accents: Vec<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LanguagePronunciation<'a> {
+    /// The level-2 header of the language section.
+    pub language: &'a str,
+    pub items: Vec<PronunciationItem<'a>>,
+}
+
+fn non_empty<'a>(values: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    values.filter(|value| !value.is_empty()).collect()
+}
+
+impl<'a> PronunciationKind<'a> {
+    fn new(template: &TemplateBorrowed<'a>) -> Option<Self> {
+        let kind = match template.name.trim() {
+            "IPA" => PronunciationKind::Ipa {
+                pronunciations: non_empty(template.numbered_parameters(2)),
+            },
+            "audio" => PronunciationKind::Audio {
+                file: template.parameter("2")?,
+                caption: template.parameter("3"),
+            },
+            "rhymes" | "rhyme" => PronunciationKind::Rhymes {
+                rhymes: non_empty(template.numbered_parameters(2)),
+            },
+            "hyphenation" | "hyph" => {
+                let syllables: Vec<_> =
+                    template.numbered_parameters(2).collect();
+                PronunciationKind::Hyphenation {
+                    hyphenations: syllables
+                        .split(|syllable| syllable.is_empty())
+                        .filter(|syllables| !syllables.is_empty())
+                        .map(<[_]>::to_vec)
+                        .collect(),
+                }
+            }
+            "homophones" | "homophone" | "hmp" => {
+                PronunciationKind::Homophones {
+                    homophones: non_empty(template.numbered_parameters(2)),
+                }
+            }
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+fn is_accent_template(name: &str) -> bool {
+    matches!(name, "a" | "accent")
+}
+
+/// The pronunciation templates in the Pronunciation sections of the page,
+/// grouped by language section.
+pub fn pronunciations<'a>(
+    wikitext: &'a str,
+    nodes: &'a [Node<'a>],
+) -> Vec<LanguagePronunciation<'a>> {
+    let mut languages: Vec<LanguagePronunciation> = Vec::new();
+    // The end of the last `{{a}}` template and its parameters.
+    let mut last_accents: Option<(usize, Vec<&'a str>)> = None;
+    TemplateVisitor::new(wikitext).visit_with_context(
+        nodes,
+        &mut |template, node, context| {
+            let mut headers = context.headers().iter();
+            let language = match headers.next() {
+                Some(language) => language,
+                None => return,
+            };
+            if !headers.any(|header| header.starts_with("Pronunciation")) {
+                return;
+            }
+            if is_accent_template(template.name.trim()) {
+                last_accents = Some((
+                    node.end(),
+                    non_empty(template.numbered_parameters(1)),
+                ));
+                return;
+            }
+            let kind = match PronunciationKind::new(&template) {
+                Some(kind) => kind,
+                None => return,
+            };
+            let mut accents = match &last_accents {
+                Some((end, accents))
+                    if !wikitext[*end..node.start()].contains('\n') =>
+                {
+                    let mut accents = accents.as_slice();
+                    // In `{{a|en|UK}}`, the first parameter is the language
+                    // code, as in the pronunciation templates.
+                    if accents.len() > 1
+                        && accents.first().copied() == template.parameter("1")
+                    {
+                        accents = &accents[1..];
+                    }
+                    accents.to_vec()
+                }
+                _ => Vec::new(),
+            };
+            if let Some(a) = template.parameter("a") {
+                accents.extend(a.split(',').map(str::trim));
+            }
+            let item = PronunciationItem { kind, accents };
+            match languages.last_mut() {
+                Some(last) if last.language == language => {
+                    last.items.push(item)
+                }
+                _ => languages.push(LanguagePronunciation {
+                    language,
+                    items: vec![item],
+                }),
+            }
+        },
+    );
+    languages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pronunciations, LanguagePronunciation, PronunciationItem,
+        PronunciationKind::*,
+    };
+    use dump_parser::wiktionary_configuration;
+
+    #[test]
+    fn pronunciation_section() {
+        let wikitext = concat!(
+            "==English==\n",
+            "===Pronunciation===\n",
+            "* {{a|en|UK}} {{IPA|en|/kæt/}}\n",
+            "* {{IPA|en|/kat/|a=Scotland}}\n",
+            "* {{audio|en|En-us-cat.ogg|Audio (US)}}\n",
+            "* {{rhymes|en|æt|s=1}}\n",
+            "* {{hyphenation|en|cat||ca|t}}\n",
+            "===Noun===\n",
+            "{{en-noun}} {{IPA|en|/x/}}\n",
+            "==French==\n",
+            "===Pronunciation===\n",
+            "* {{IPA|fr|/ʃa/}}\n",
+            "* {{homophones|fr|chas|chats}}\n",
+        );
+        let output = wiktionary_configuration().parse(wikitext);
+        let item = |kind, accents| PronunciationItem { kind, accents };
+        assert_eq!(
+            pronunciations(wikitext, &output.nodes),
+            vec![
+                LanguagePronunciation {
+                    language: "English",
+                    items: vec![
+                        item(
+                            Ipa {
+                                pronunciations: vec!["/kæt/"],
+                            },
+                            vec!["UK"],
+                        ),
+                        item(
+                            Ipa {
+                                pronunciations: vec!["/kat/"],
+                            },
+                            vec!["Scotland"],
+                        ),
+                        item(
+                            Audio {
+                                file: "En-us-cat.ogg",
+                                caption: Some("Audio (US)"),
+                            },
+                            vec![],
+                        ),
+                        item(
+                            Rhymes {
+                                rhymes: vec!["æt"]
+                            },
+                            vec![],
+                        ),
+                        item(
+                            Hyphenation {
+                                hyphenations: vec![
+                                    vec!["cat"],
+                                    vec!["ca", "t"]
+                                ],
+                            },
+                            vec![],
+                        ),
+                    ],
+                },
+                LanguagePronunciation {
+                    language: "French",
+                    items: vec![
+                        item(
+                            Ipa {
+                                pronunciations: vec!["/ʃa/"],
+                            },
+                            vec![],
+                        ),
+                        item(
+                            Homophones {
+                                homophones: vec!["chas", "chats"],
+                            },
+                            vec![],
+                        ),
+                    ],
+                },
+            ]
+        );
+    }
+}
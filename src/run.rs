@@ -0,0 +1,243 @@
+//! The `run` subcommand, which parses each page of the dump once and gives
+//! the nodes to all of the jobs in a job file.
+use dump_parser::{
+    parallel::process_in_order, print_parser_warnings, Namespace,
+};
+use filter_headers::HeaderFilterer;
+use header_stats::HeaderStats;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::args::{DumpOptions, Job};
+use crate::error::{Error, Result};
+use crate::{print_time, FilePool, Serialized, TemplateDumper};
+
+// The part of a job that the worker threads use to process a page.
+enum JobState {
+    AllHeaders,
+    FilterHeaders(HeaderFilterer),
+    DumpParsedTemplates(TemplateDumper),
+}
+
+// The part of a job that collects the outputs for the pages, in order.
+enum JobOutput {
+    AllHeaders {
+        output: PathBuf,
+        pretty: bool,
+        stats: HeaderStats,
+    },
+    FilterHeaders {
+        output: PathBuf,
+        pretty: bool,
+        pages: Vec<(String, HashSet<String>)>,
+    },
+    DumpParsedTemplates,
+}
+
+// What a job found in one page.
+enum PageOutput {
+    Headers(HeaderStats),
+    FilteredHeaders(HashSet<String>),
+    Templates(Vec<(usize, Serialized)>),
+}
+
+fn write_json<S: Serialize>(
+    path: &Path,
+    value: &S,
+    pretty: bool,
+) -> Result<()> {
+    let file = File::create(path).map_err(|e| Error::IoError {
+        action: "create",
+        path: path.into(),
+        cause: e,
+    })?;
+    let mut file = BufWriter::new(file);
+    if pretty {
+        serde_json::to_writer_pretty(&mut file, value)?;
+    } else {
+        serde_json::to_writer(&mut file, value)?;
+    }
+    file.flush().map_err(|e| Error::IoError {
+        action: "write to",
+        path: path.into(),
+        cause: e,
+    })
+}
+
+pub fn run(
+    jobs: Vec<Job>,
+    options: DumpOptions,
+    main_start: Instant,
+    verbose: bool,
+) -> Result<()> {
+    let DumpOptions {
+        pages,
+        namespaces,
+        configuration,
+        dump,
+        threads,
+        siteinfo,
+    } = options;
+    let mut files = FilePool::new();
+    let mut job_namespaces: Vec<Vec<Namespace>> = Vec::new();
+    let mut states = Vec::new();
+    let mut outputs = Vec::new();
+    for job in jobs {
+        let (namespaces, state, output) = match job {
+            Job::AllHeaders {
+                namespaces,
+                output,
+                pretty,
+            } => (
+                namespaces,
+                JobState::AllHeaders,
+                JobOutput::AllHeaders {
+                    output,
+                    pretty,
+                    stats: HeaderStats::new(),
+                },
+            ),
+            Job::FilterHeaders {
+                namespaces,
+                output,
+                top_level_headers,
+                other_headers,
+                pretty,
+            } => (
+                namespaces,
+                JobState::FilterHeaders(HeaderFilterer::new(
+                    top_level_headers,
+                    other_headers,
+                )),
+                JobOutput::FilterHeaders {
+                    output,
+                    pretty,
+                    pages: Vec::new(),
+                },
+            ),
+            Job::DumpParsedTemplates {
+                namespaces,
+                templates,
+            } => (
+                namespaces,
+                JobState::DumpParsedTemplates(TemplateDumper::new(
+                    templates,
+                    &mut files,
+                    &siteinfo.namespaces,
+                )?),
+                JobOutput::DumpParsedTemplates,
+            ),
+        };
+        job_namespaces.push(namespaces);
+        states.push(state);
+        outputs.push(output);
+    }
+    let start_time = main_start.elapsed();
+    let parse_start = Instant::now();
+    let pages = dump.into_pages().take(pages).filter(|result| match result {
+        Ok(page) => namespaces.contains(&page.namespace),
+        Err(_) => true,
+    });
+    process_in_order(
+        pages,
+        threads,
+        |page| -> Result<(String, Vec<Option<PageOutput>>)> {
+            let output = configuration.parse(&page.text);
+            if verbose {
+                print_parser_warnings(&page, &output.warnings);
+            }
+            let page_outputs = states
+                .iter()
+                .zip(&job_namespaces)
+                .map(|(state, namespaces)| -> Result<_> {
+                    if !namespaces.contains(&page.namespace) {
+                        return Ok(None);
+                    }
+                    let page_output = match state {
+                        JobState::AllHeaders => PageOutput::Headers(
+                            HeaderStats::from_page(&page, &output.nodes),
+                        ),
+                        JobState::FilterHeaders(filterer) => {
+                            PageOutput::FilteredHeaders(
+                                filterer.page_headers(&page, &output.nodes),
+                            )
+                        }
+                        JobState::DumpParsedTemplates(dumper) => {
+                            PageOutput::Templates(
+                                dumper.dump_page(&page, &output.nodes)?,
+                            )
+                        }
+                    };
+                    Ok(Some(page_output))
+                })
+                .collect::<Result<_>>()?;
+            Ok((page.title, page_outputs))
+        },
+        |result| {
+            let (title, page_outputs) = result?;
+            for (output, page_output) in outputs.iter_mut().zip(page_outputs) {
+                match (output, page_output) {
+                    (
+                        JobOutput::AllHeaders { stats, .. },
+                        Some(PageOutput::Headers(page_stats)),
+                    ) => stats.merge(page_stats),
+                    (
+                        JobOutput::FilterHeaders { pages, .. },
+                        Some(PageOutput::FilteredHeaders(headers)),
+                    ) => {
+                        if !headers.is_empty() {
+                            pages.push((title.clone(), headers));
+                        }
+                    }
+                    (_, Some(PageOutput::Templates(serialized))) => {
+                        for (file, serialized) in serialized {
+                            files.write(file, serialized)?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        },
+    )?;
+    files.flush()?;
+    for (state, output) in states.into_iter().zip(outputs) {
+        match (state, output) {
+            (
+                JobState::AllHeaders,
+                JobOutput::AllHeaders {
+                    output,
+                    pretty,
+                    stats,
+                },
+            ) => write_json(&output, &stats, pretty)?,
+            (
+                JobState::FilterHeaders(mut filterer),
+                JobOutput::FilterHeaders {
+                    output,
+                    pretty,
+                    pages,
+                },
+            ) => {
+                for (title, headers) in pages {
+                    filterer.add_page(title, headers);
+                }
+                write_json(&output, &filterer, pretty)?;
+            }
+            _ => {}
+        }
+    }
+    let parse_time = parse_start.elapsed();
+    eprintln!(
+        "startup took {}, parsing and printing {}",
+        print_time(&start_time).unwrap(),
+        print_time(&parse_time).unwrap()
+    );
+    Ok(())
+}
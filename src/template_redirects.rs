@@ -0,0 +1,179 @@
+//! Redirects between templates, from the `page` and `redirect` SQL dumps.
+use dump_parser::Namespace;
+use sql_dump::{tables::Redirect, Error, FromValue, Rows, Table, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::BufRead,
+};
+use template_iter::Title;
+
+#[derive(Debug, Default)]
+pub struct TemplateRedirects {
+    // Redirects in the template namespace, by target, in alphabetical order.
+    by_target: BTreeMap<Title, Vec<Title>>,
+}
+
+impl TemplateRedirects {
+    pub fn read<P: BufRead, R: BufRead>(
+        page_sql: P,
+        redirect_sql: R,
+    ) -> Result<Self, Error> {
+        // Only three columns of the page table are needed, so the rows are
+        // left untyped.
+        let pages = Table::new(page_sql)?;
+        let id = pages.column("page_id")?;
+        let namespace = pages.column("page_namespace")?;
+        let title = pages.column("page_title")?;
+        let mut templates = HashMap::new();
+        for row in pages {
+            let mut row = row?;
+            if row.get(namespace)
+                != Some(&Value::Integer(Namespace::TEMPLATE.0.into()))
+            {
+                continue;
+            }
+            let mut take = |i| row.get_mut(i).map(std::mem::take);
+            if let (Some(id), Some(title)) = (
+                take(id).and_then(u64::from_value),
+                take(title).and_then(String::from_value),
+            ) {
+                templates.insert(id, title);
+            }
+        }
+
+        let mut by_target: BTreeMap<Title, Vec<Title>> = BTreeMap::new();
+        for redirect in Rows::<Redirect, _>::new(redirect_sql)? {
+            let Redirect {
+                from,
+                namespace,
+                title: target,
+                interwiki,
+                ..
+            } = redirect?;
+            if namespace != Namespace::TEMPLATE.0
+                || interwiki.is_some_and(|interwiki| !interwiki.is_empty())
+            {
+                continue;
+            }
+            if let Some(redirect) = templates.remove(&from) {
+                by_target
+                    .entry(Title::from_dbkey(Namespace::TEMPLATE, target))
+                    .or_default()
+                    .push(Title::from_dbkey(Namespace::TEMPLATE, redirect));
+            }
+        }
+        for redirects in by_target.values_mut() {
+            redirects.sort();
+        }
+        Ok(TemplateRedirects { by_target })
+    }
+
+    pub fn insert(&mut self, target: Title, redirect: Title) {
+        let redirects = self.by_target.entry(target).or_default();
+        if let Err(i) = redirects.binary_search(&redirect) {
+            redirects.insert(i, redirect);
+        }
+    }
+
+    pub fn redirects(&self, target: &Title) -> &[Title] {
+        self.by_target.get(target).map_or(&[], Vec::as_slice)
+    }
+
+    /// Give each redirect that has no value of its own the value of its
+    /// target. If the target has no value, the target and those redirects get
+    /// the value of the first redirect that has one.
+    pub fn add_redirects<V: Clone>(
+        &self,
+        values: impl IntoIterator<Item = (Title, V)>,
+    ) -> BTreeMap<Title, V> {
+        let values: BTreeMap<_, _> = values.into_iter().collect();
+        let mut with_redirects = BTreeMap::new();
+        for (target, redirects) in &self.by_target {
+            if let Some(value) = values.get(target).or_else(|| {
+                redirects.iter().find_map(|redirect| values.get(redirect))
+            }) {
+                for title in std::iter::once(target).chain(redirects) {
+                    let value = values.get(title).unwrap_or(value);
+                    with_redirects.insert(title.clone(), value.clone());
+                }
+            }
+        }
+        for (title, value) in values {
+            with_redirects.entry(title).or_insert(value);
+        }
+        with_redirects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemplateRedirects;
+    use dump_parser::Namespace;
+    use template_iter::Title;
+
+    fn template(name: &str) -> Title {
+        Title::from_dbkey(Namespace::TEMPLATE, name)
+    }
+
+    #[test]
+    fn read() {
+        let page_sql = "CREATE TABLE `page` (
+  `page_id` int(8) unsigned NOT NULL AUTO_INCREMENT,
+  `page_namespace` int(11) NOT NULL DEFAULT 0,
+  `page_title` varbinary(255) NOT NULL DEFAULT '',
+  `page_is_redirect` tinyint(1) unsigned NOT NULL DEFAULT 0
+) ENGINE=InnoDB DEFAULT CHARSET=binary;
+INSERT INTO `page` VALUES (1,10,'link',0),(2,10,'l',1),(3,0,'l',1);
+INSERT INTO `page` VALUES (4,10,'ll',1),(5,10,'wp',1);
+";
+        let redirect_sql = "CREATE TABLE `redirect` (
+  `rd_from` int(8) unsigned NOT NULL DEFAULT 0,
+  `rd_namespace` int(11) NOT NULL DEFAULT 0,
+  `rd_title` varbinary(255) NOT NULL DEFAULT '',
+  `rd_interwiki` varbinary(32) DEFAULT NULL,
+  `rd_fragment` varbinary(255) DEFAULT NULL
+) ENGINE=InnoDB DEFAULT CHARSET=binary;
+INSERT INTO `redirect` VALUES (2,10,'link','',''),(3,10,'link','',NULL);
+INSERT INTO `redirect` VALUES (4,10,'link',NULL,NULL),(5,10,'wp','w','');
+";
+        let redirects = TemplateRedirects::read(
+            page_sql.as_bytes(),
+            redirect_sql.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            redirects.redirects(&template("link")),
+            &[template("l"), template("ll")]
+        );
+        assert!(redirects.redirects(&template("wp")).is_empty());
+    }
+
+    #[test]
+    fn add_redirects() {
+        let mut redirects = TemplateRedirects::default();
+        redirects.insert(template("link"), template("l"));
+        redirects.insert(template("link"), template("ll"));
+        redirects.insert(template("m"), template("mention"));
+        redirects.insert(template("m"), template("mention2"));
+        redirects.insert(template("other"), template("o"));
+        let with_redirects = redirects.add_redirects(vec![
+            (template("link"), "link"),
+            (template("ll"), "ll"),
+            (template("mention2"), "mention2"),
+            (template("mention"), "mention"),
+            (template("en-noun"), "en-noun"),
+        ]);
+        assert_eq!(
+            with_redirects.into_iter().collect::<Vec<_>>(),
+            vec![
+                (template("en-noun"), "en-noun"),
+                (template("l"), "link"),
+                (template("link"), "link"),
+                (template("ll"), "ll"),
+                (template("m"), "mention"),
+                (template("mention"), "mention"),
+                (template("mention2"), "mention2"),
+            ]
+        );
+    }
+}
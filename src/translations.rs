@@ -0,0 +1,148 @@
+//! Translations in the translation tables of Wiktionary entries, which are
+//! between `{{trans-top}}` and `{{trans-bottom}}` and list the terms in
+//! `{{t}}`, `{{t+}}`, `{{tt}}` and `{{tt+}}`.
+use dump_parser::Node;
+use filter_headers::is_part_of_speech;
+use serde::Serialize;
+use template_iter::{TemplateBorrowed, TemplateVisitor};
+
+static TRANSLATION_TEMPLATES: &[&str] =
+    &["t", "t+", "t-check", "t+check", "tt", "tt+"];
+
+static TABLE_START_TEMPLATES: &[&str] =
+    &["trans-top", "trans-top-also", "checktrans-top"];
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Translation<'a> {
+    /// The header of the part-of-speech section that the table is in.
+    pub part_of_speech: Option<&'a str>,
+    /// The first parameter of `{{trans-top}}`, a gloss of the sense that is
+    /// translated.
+    pub gloss: Option<&'a str>,
+    /// The language code.
+    pub language: &'a str,
+    pub term: &'a str,
+    /// The gender and number codes after the term, like `m` or `f-p`.
+    pub genders: Vec<&'a str>,
+    /// The `tr` parameter.
+    pub transliteration: Option<&'a str>,
+    /// The `ts` parameter.
+    pub transcription: Option<&'a str>,
+    /// The `alt` parameter, the term as displayed.
+    pub alt: Option<&'a str>,
+    /// The `lit` parameter, a literal translation of the term.
+    pub literal: Option<&'a str>,
+    /// The `sc` parameter.
+    pub script: Option<&'a str>,
+}
+
+impl<'a> Translation<'a> {
+    fn new(
+        template: &TemplateBorrowed<'a>,
+        part_of_speech: Option<&'a str>,
+        gloss: Option<&'a str>,
+    ) -> Option<Self> {
+        let genders = template
+            .numbered_parameters(3)
+            .filter(|value| !value.is_empty())
+            .collect();
+        Some(Translation {
+            part_of_speech,
+            gloss,
+            language: template.parameter("1")?,
+            term: template.parameter("2")?,
+            genders,
+            transliteration: template.parameter("tr"),
+            transcription: template.parameter("ts"),
+            alt: template.parameter("alt"),
+            literal: template.parameter("lit"),
+            script: template.parameter("sc"),
+        })
+    }
+}
+
+/// The translations in the page in the order they appear. Translation
+/// templates without a language code or term are skipped.
+pub fn translations<'a>(
+    wikitext: &'a str,
+    nodes: &'a [Node<'a>],
+) -> Vec<Translation<'a>> {
+    let mut translations = Vec::new();
+    // The gloss of the table that the visitor is in, if any.
+    let mut table: Option<Option<&'a str>> = None;
+    TemplateVisitor::new(wikitext).visit_with_context(
+        nodes,
+        &mut |template, _, context| {
+            let name = template.name.trim();
+            if TABLE_START_TEMPLATES.contains(&name) {
+                table = Some(template.parameter("1"));
+            } else if name == "trans-bottom" {
+                table = None;
+            } else if TRANSLATION_TEMPLATES.contains(&name) {
+                let part_of_speech = context
+                    .headers()
+                    .iter()
+                    .filter(|header| is_part_of_speech(header))
+                    .last();
+                translations.extend(Translation::new(
+                    &template,
+                    part_of_speech,
+                    table.flatten(),
+                ));
+            }
+        },
+    );
+    translations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{translations, Translation};
+    use dump_parser::wiktionary_configuration;
+
+    #[test]
+    fn translation_table() {
+        let wikitext = concat!(
+            "==English==\n",
+            "===Noun===\n",
+            "====Translations====\n",
+            "{{trans-top|domestic animal}}\n",
+            "* French: {{t+|fr|chat|m}}, {{t|fr|chatte|f}}\n",
+            "* Russian: {{t+|ru|ко́шка|f|tr=kóška}}\n",
+            "{{trans-bottom}}\n",
+            "{{checktrans-top}}\n",
+            "* German: {{t|de|Katze|f|f-p|alt=Katzen}}\n",
+            "* Spanish: {{t|es}}\n",
+            "{{trans-bottom}}\n",
+        );
+        let output = wiktionary_configuration().parse(wikitext);
+        let translation = |gloss, language, term, genders| Translation {
+            part_of_speech: Some("Noun"),
+            gloss,
+            language,
+            term,
+            genders,
+            transliteration: None,
+            transcription: None,
+            alt: None,
+            literal: None,
+            script: None,
+        };
+        let gloss = Some("domestic animal");
+        assert_eq!(
+            translations(wikitext, &output.nodes),
+            vec![
+                translation(gloss, "fr", "chat", vec!["m"]),
+                translation(gloss, "fr", "chatte", vec!["f"]),
+                Translation {
+                    transliteration: Some("kóška"),
+                    ..translation(gloss, "ru", "ко́шка", vec!["f"])
+                },
+                Translation {
+                    alt: Some("Katzen"),
+                    ..translation(None, "de", "Katze", vec!["f", "f-p"])
+                },
+            ]
+        );
+    }
+}
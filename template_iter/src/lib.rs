@@ -4,11 +4,26 @@ use dump_parser::{
     Positioned,
 };
 pub use parse_wiki_text_ext;
-use parse_wiki_text_ext::template_parameters::{self, ParameterKey};
+use parse_wiki_text_ext::{
+    template_parameters::{self, ParameterKey},
+    visit::{walk, Visit, Visitor},
+};
+pub use parse_wiki_text_ext::{visit::Context as TemplateContext, HeaderPath};
 use serde::{Deserialize, Serialize};
-use std::{borrow::Cow, collections::BTreeMap, error::Error, fmt::Display};
+use std::{borrow::Cow, collections::BTreeMap};
+
+mod html_entities;
+mod parser_functions;
+pub use parser_functions::{is_parser_function, ParserFunction};
+mod structured;
+pub use structured::{segments, Segment, StructuredTemplate};
+mod title;
+pub use title::{
+    decode_char_references, normalize_title, Title, TitleNormalizationError,
+    TITLE_MAX,
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct TemplateBorrowed<'a> {
     #[serde(borrow)]
     pub name: Cow<'a, str>,
@@ -38,29 +53,31 @@ static NUMBERS: &[&str] = &[
     "199", "200",
 ];
 
+fn parameter_key<'a>(wikitext: &'a str, key: ParameterKey<'a>) -> Cow<'a, str> {
+    match key {
+        ParameterKey::NodeList(nodes) => {
+            Cow::Borrowed(nodes.get_text_from(wikitext))
+        }
+        ParameterKey::Number(num) => {
+            if let Some(s) = NUMBERS.get(num as usize) {
+                Cow::Borrowed(*s)
+            } else {
+                Cow::Owned(num.to_string())
+            }
+        }
+    }
+}
+
 impl<'a> TemplateBorrowed<'a> {
     pub fn new(
         wikitext: &'a str,
         name: &'a [Node<'a>],
         parameters: &'a [dump_parser::Parameter<'a>],
     ) -> Self {
-        use Cow::*;
-        let name = Borrowed(name.get_text_from(wikitext));
+        let name = Cow::Borrowed(name.get_text_from(wikitext));
         let parameters = template_parameters::enumerate(parameters)
             .map(|(key, value)| {
-                let key = match key {
-                    ParameterKey::NodeList(nodes) => {
-                        Borrowed(nodes.get_text_from(wikitext))
-                    }
-                    ParameterKey::Number(num) => {
-                        if let Some(s) = NUMBERS.get(num as usize) {
-                            Borrowed(*s)
-                        } else {
-                            Owned(num.to_string())
-                        }
-                    }
-                };
-                (key, value.get_text_from(wikitext))
+                (parameter_key(wikitext, key), value.get_text_from(wikitext))
             })
             .collect();
         Self { name, parameters }
@@ -80,6 +97,29 @@ impl<'a> TemplateBorrowed<'a> {
             Err("not a template")
         }
     }
+
+    /// The trimmed value of a parameter, unless it is missing or blank.
+    pub fn parameter(&self, key: &str) -> Option<&'a str> {
+        self.parameters
+            .get(key)
+            .copied()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    }
+
+    /// The trimmed values of the numbered parameters from `first` up to the
+    /// first one that is missing. Blank values are included.
+    pub fn numbered_parameters(
+        &self,
+        first: usize,
+    ) -> impl Iterator<Item = &'a str> + '_ {
+        (first..)
+            .map(move |i| self.parameters.get(i.to_string().as_str()))
+            .take_while(Option::is_some)
+            .flatten()
+            .copied()
+            .map(str::trim)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -102,6 +142,45 @@ impl<'a> From<TemplateBorrowed<'a>> for TemplateOwned {
     }
 }
 
+// Calls a function with each template after the templates nested in it.
+struct TemplateFinder<F>(F);
+
+impl<F> TemplateFinder<F> {
+    // Gives the closure its signature.
+    fn new<'a>(func: F) -> Self
+    where
+        F: FnMut(
+            &'a Node<'a>,
+            &'a [Node<'a>],
+            &'a [dump_parser::Parameter<'a>],
+            &TemplateContext<'a>,
+        ),
+    {
+        TemplateFinder(func)
+    }
+}
+
+impl<'a, F> Visitor<'a> for TemplateFinder<F>
+where
+    F: FnMut(
+        &'a Node<'a>,
+        &'a [Node<'a>],
+        &'a [dump_parser::Parameter<'a>],
+        &TemplateContext<'a>,
+    ),
+{
+    fn leave_template(
+        &mut self,
+        node: &'a Node<'a>,
+        name: &'a [Node<'a>],
+        parameters: &'a [dump_parser::Parameter<'a>],
+        context: &TemplateContext<'a>,
+    ) -> Visit {
+        (self.0)(node, name, parameters, context);
+        Visit::Continue
+    }
+}
+
 pub struct TemplateVisitor<'a> {
     wikitext: &'a str,
 }
@@ -115,196 +194,45 @@ impl<'a> TemplateVisitor<'a> {
     where
         F: FnMut(TemplateBorrowed<'a>, &'a Node),
     {
-        for node in nodes {
-            match node {
-                DefinitionList { items, .. } => {
-                    for item in items {
-                        self.visit(&item.nodes, func);
-                    }
-                }
-                Heading { nodes, .. }
-                | Preformatted { nodes, .. }
-                | Tag { nodes, .. } => {
-                    self.visit(&nodes, func);
-                }
-                Image { text, .. } | Link { text, .. } => {
-                    self.visit(&text, func);
-                }
-                OrderedList { items, .. } | UnorderedList { items, .. } => {
-                    for item in items {
-                        self.visit(&item.nodes, func);
-                    }
-                }
-                Parameter { name, default, .. } => {
-                    if let Some(nodes) = default {
-                        self.visit(&nodes, func);
-                    }
-                    self.visit(&name, func);
-                }
-                Table {
-                    attributes,
-                    captions,
-                    rows,
-                    ..
-                } => {
-                    self.visit(&attributes, func);
-                    for caption in captions {
-                        if let Some(attributes) = &caption.attributes {
-                            self.visit(attributes, func)
-                        }
-                        self.visit(&caption.content, func);
-                    }
-                    for row in rows {
-                        self.visit(&row.attributes, func);
-                        for cell in &row.cells {
-                            if let Some(attributes) = &cell.attributes {
-                                self.visit(attributes, func);
-                            }
-                            self.visit(&cell.content, func);
-                        }
-                    }
-                }
-                Template {
-                    name, parameters, ..
-                } => {
-                    self.visit(&name, func);
-                    for parameter in parameters {
-                        if let Some(name) = &parameter.name {
-                            self.visit(name, func);
-                        }
-                        self.visit(&parameter.value, func);
-                    }
-                    let template = TemplateBorrowed::new(
-                        self.wikitext,
-                        &name,
-                        &parameters,
-                    );
-                    func(template, &node);
-                }
-                Bold { .. }
-                | BoldItalic { .. }
-                | Category { .. }
-                | CharacterEntity { .. }
-                | Comment { .. }
-                | EndTag { .. }
-                | ExternalLink { .. }
-                | HorizontalDivider { .. }
-                | Italic { .. }
-                | MagicWord { .. }
-                | ParagraphBreak { .. }
-                | Redirect { .. }
-                | StartTag { .. }
-                | Text { .. } => {}
-            }
-        }
+        self.visit_with_context(nodes, &mut |template, node, _| {
+            func(template, node)
+        });
     }
-}
-
-#[derive(Debug, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum TitleNormalizationError {
-    TooLong,
-    IllegalChar,
-}
-
-impl Error for TitleNormalizationError {
-    fn source(&self) -> Option<&(dyn Error + 'static)> {
-        None
-    }
-}
-
-impl Display for TitleNormalizationError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            TitleNormalizationError::TooLong => write!(f, "title too long"),
-            TitleNormalizationError::IllegalChar => {
-                write!(f, "title contains illegal character")
-            }
-        }
-    }
-}
-
-pub const TITLE_MAX: usize = 255;
 
-// This trims whitespace on either end and converts
-// sequences of whitespace characters to a single underscore.
-// In titles, underscores count as whitespace.
-// This is only a subset of the stuff done when resolving a template name.
-// TODO: figure out all the characters that count as whitespace
-// at the beginning and end and when collapsing whitespace sequences,
-// and all the illegal characters. Perhaps this information is in one of the
-// routines called by Title.php?
-// Perhaps also decode HTML character entities?
-pub fn normalize_title(name: &str) -> Result<String, TitleNormalizationError> {
-    fn is_title_whitespace(c: char) -> bool {
-        c.is_ascii_whitespace() || c == '_'
-    }
-
-    let name = name.trim_matches(is_title_whitespace);
-    let mut normalized_title = String::new();
-    let mut name_iter = name.chars().peekable();
-    while let Some(c) = name_iter.next() {
-        if normalized_title.len() >= TITLE_MAX {
-            return Err(TitleNormalizationError::TooLong);
-        }
-        if ('\u{00}'..'\u{1F}').contains(&c) {
-            return Err(TitleNormalizationError::IllegalChar);
-        }
-        if is_title_whitespace(c) {
-            normalized_title.push('_');
-            while name_iter.peek().map(|c| is_title_whitespace(*c))
-                == Some(true)
-            {
-                let _ = name_iter.next();
-            }
-        } else {
-            normalized_title.push(c);
-        }
-    }
-    if normalized_title.len() > TITLE_MAX {
-        Err(TitleNormalizationError::TooLong)
-    } else {
-        Ok(normalized_title)
+    /// Like `visit`, but also passes the headers and templates that enclose
+    /// each template. Parser functions are skipped.
+    pub fn visit_with_context<F>(&self, nodes: &'a [Node<'a>], func: &mut F)
+    where
+        F: FnMut(TemplateBorrowed<'a>, &'a Node<'a>, &TemplateContext<'a>),
+    {
+        walk(
+            self.wikitext,
+            nodes,
+            &mut TemplateFinder::new(|node, name, parameters, context| {
+                if !is_parser_function(name.get_text_from(self.wikitext)) {
+                    let template =
+                        TemplateBorrowed::new(self.wikitext, name, parameters);
+                    func(template, node, context);
+                }
+            }),
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn test_normalize_title() {
-        use super::{normalize_title, TitleNormalizationError::*, TITLE_MAX};
-        use std::iter;
-
-        fn rep<T: Clone>(c: T, n: usize) -> iter::Take<iter::Repeat<T>> {
-            iter::repeat(c).take(n)
-        }
-
-        for (name, normalized) in &[
-            (
-                rep('_', TITLE_MAX)
-                    .chain(iter::once('l').chain(rep(' ', TITLE_MAX)))
-                    .collect(),
-                Ok("l".to_string()),
-            ),
-            (
-                rep("_", TITLE_MAX)
-                    .chain(iter::once("auto").chain(rep(" ", TITLE_MAX)))
-                    .chain(iter::once("cat").chain(rep(" ", TITLE_MAX)))
-                    .collect(),
-                Ok("auto_cat".to_string()),
-            ),
-            (
-                rep('a', TITLE_MAX).collect(),
-                Ok(rep('a', TITLE_MAX).collect()),
-            ),
-            (
-                rep('a', TITLE_MAX).chain(iter::once(' ')).collect(),
-                Ok(rep('a', TITLE_MAX).collect()),
-            ),
-            (rep('a', TITLE_MAX + 1).collect(), Err(TooLong)),
-            ("\u{0}".to_string(), Err(IllegalChar)),
-        ] {
-            assert_eq!(&normalize_title(name), normalized);
-        }
+    /// Visit the parser functions and variables instead of the templates.
+    pub fn visit_parser_functions<F>(&self, nodes: &'a [Node<'a>], func: &mut F)
+    where
+        F: FnMut(ParserFunction<'a>, &'a Node<'a>, &TemplateContext<'a>),
+    {
+        walk(
+            self.wikitext,
+            nodes,
+            &mut TemplateFinder::new(|node, name, parameters, context| {
+                if let Some(function) =
+                    ParserFunction::new(self.wikitext, name, parameters)
+                {
+                    func(function, node, context);
+                }
+            }),
+        );
     }
 }
@@ -0,0 +1,282 @@
+//! Parser functions like `{{#invoke:}}`, `{{#if:}}` and `{{lc:}}` and
+//! variables like `{{PAGENAME}}`, which the parser treats as templates.
+use dump_parser::{Node, Parameter, Positioned};
+use serde::Serialize;
+
+// Parser functions in MediaWiki core whose names do not start with `#`.
+// Their names are case-insensitive.
+static FUNCTIONS: &[&str] = &[
+    "anchorencode",
+    "basepagename",
+    "basepagenamee",
+    "bidi",
+    "canonicalurl",
+    "canonicalurle",
+    "cascadingsources",
+    "defaultsort",
+    "defaultsortkey",
+    "defaultcategorysort",
+    "displaytitle",
+    "filepath",
+    "formatnum",
+    "fullpagename",
+    "fullpagenamee",
+    "fullurl",
+    "fullurle",
+    "gender",
+    "grammar",
+    "int",
+    "lc",
+    "lcfirst",
+    "localurl",
+    "localurle",
+    "namespace",
+    "namespacee",
+    "namespacenumber",
+    "ns",
+    "nse",
+    "numberingroup",
+    "numberofactiveusers",
+    "numberofadmins",
+    "numberofarticles",
+    "numberofedits",
+    "numberoffiles",
+    "numberofpages",
+    "numberofusers",
+    "padleft",
+    "padright",
+    "pageid",
+    "pagename",
+    "pagenamee",
+    "pagesincategory",
+    "pagesize",
+    "plural",
+    "protectionexpiry",
+    "protectionlevel",
+    "revisionday",
+    "revisionday2",
+    "revisionid",
+    "revisionmonth",
+    "revisionmonth1",
+    "revisiontimestamp",
+    "revisionuser",
+    "revisionyear",
+    "rootpagename",
+    "rootpagenamee",
+    "special",
+    "speciale",
+    "subjectpagename",
+    "subjectpagenamee",
+    "subjectspace",
+    "subjectspacee",
+    "subpagename",
+    "subpagenamee",
+    "talkpagename",
+    "talkpagenamee",
+    "talkspace",
+    "talkspacee",
+    "uc",
+    "ucfirst",
+    "urlencode",
+];
+
+// Variables in MediaWiki core, which take no arguments. Their names are
+// case-sensitive.
+static VARIABLES: &[&str] = &[
+    "!",
+    "=",
+    "BASEPAGENAME",
+    "BASEPAGENAMEE",
+    "CONTENTLANG",
+    "CONTENTLANGUAGE",
+    "CURRENTDAY",
+    "CURRENTDAY2",
+    "CURRENTDAYNAME",
+    "CURRENTDOW",
+    "CURRENTHOUR",
+    "CURRENTMONTH",
+    "CURRENTMONTH1",
+    "CURRENTMONTHABBREV",
+    "CURRENTMONTHNAME",
+    "CURRENTMONTHNAMEGEN",
+    "CURRENTTIME",
+    "CURRENTTIMESTAMP",
+    "CURRENTVERSION",
+    "CURRENTWEEK",
+    "CURRENTYEAR",
+    "DIRECTIONMARK",
+    "DIRMARK",
+    "FULLPAGENAME",
+    "FULLPAGENAMEE",
+    "LOCALDAY",
+    "LOCALDAY2",
+    "LOCALDAYNAME",
+    "LOCALDOW",
+    "LOCALHOUR",
+    "LOCALMONTH",
+    "LOCALMONTH1",
+    "LOCALMONTHABBREV",
+    "LOCALMONTHNAME",
+    "LOCALMONTHNAMEGEN",
+    "LOCALTIME",
+    "LOCALTIMESTAMP",
+    "LOCALWEEK",
+    "LOCALYEAR",
+    "NAMESPACE",
+    "NAMESPACEE",
+    "NAMESPACENUMBER",
+    "NUMBEROFACTIVEUSERS",
+    "NUMBEROFADMINS",
+    "NUMBEROFARTICLES",
+    "NUMBEROFEDITS",
+    "NUMBEROFFILES",
+    "NUMBEROFPAGES",
+    "NUMBEROFUSERS",
+    "PAGEID",
+    "PAGELANGUAGE",
+    "PAGENAME",
+    "PAGENAMEE",
+    "REVISIONDAY",
+    "REVISIONDAY2",
+    "REVISIONID",
+    "REVISIONMONTH",
+    "REVISIONMONTH1",
+    "REVISIONSIZE",
+    "REVISIONTIMESTAMP",
+    "REVISIONUSER",
+    "REVISIONYEAR",
+    "ROOTPAGENAME",
+    "ROOTPAGENAMEE",
+    "SCRIPTPATH",
+    "SERVER",
+    "SERVERNAME",
+    "SITENAME",
+    "STYLEPATH",
+    "SUBJECTPAGENAME",
+    "SUBJECTPAGENAMEE",
+    "SUBJECTSPACE",
+    "SUBJECTSPACEE",
+    "SUBPAGENAME",
+    "SUBPAGENAMEE",
+    "TALKPAGENAME",
+    "TALKPAGENAMEE",
+    "TALKSPACE",
+    "TALKSPACEE",
+];
+
+// Split `#invoke:Module name` into the function name and the first argument.
+fn split_name(name: &str) -> Option<(&str, Option<&str>)> {
+    let name = name.trim();
+    match name.find(':') {
+        Some(colon) => {
+            let function = name[..colon].trim_end();
+            let is_function = function.starts_with('#')
+                || FUNCTIONS.iter().any(|f| f.eq_ignore_ascii_case(function));
+            if is_function {
+                Some((function, Some(&name[colon + 1..])))
+            } else {
+                None
+            }
+        }
+        None if VARIABLES.contains(&name) => Some((name, None)),
+        None => None,
+    }
+}
+
+/// Whether a template name is actually the name of a parser function or
+/// variable.
+pub fn is_parser_function(name: &str) -> bool {
+    split_name(name).is_some()
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ParserFunction<'a> {
+    /// The name before the colon, such as `#invoke` or `lc`, or the name of
+    /// the variable.
+    pub name: &'a str,
+    /// The text after the colon, such as the module name in `{{#invoke:}}`.
+    pub first_argument: Option<&'a str>,
+    /// The wikitext of the other arguments. An `=` does not make an argument
+    /// named, except to the functions that treat it that way like `#invoke`
+    /// and `#switch`.
+    pub arguments: Vec<&'a str>,
+}
+
+impl<'a> ParserFunction<'a> {
+    pub fn new(
+        wikitext: &'a str,
+        name: &'a [Node<'a>],
+        parameters: &'a [Parameter<'a>],
+    ) -> Option<Self> {
+        let (name, first_argument) = split_name(name.get_text_from(wikitext))?;
+        let arguments = parameters
+            .iter()
+            .map(|parameter| &wikitext[parameter.start..parameter.end])
+            .collect();
+        Some(ParserFunction {
+            name,
+            first_argument,
+            arguments,
+        })
+    }
+
+    /// Returns `None` if the node is not a template or is an ordinary
+    /// template.
+    pub fn from_node(
+        wikitext: &'a str,
+        template: &'a Node<'a>,
+    ) -> Option<Self> {
+        if let Node::Template {
+            name, parameters, ..
+        } = template
+        {
+            ParserFunction::new(wikitext, name, parameters)
+        } else {
+            None
+        }
+    }
+
+    /// The module and function called by `{{#invoke:module|function}}`.
+    pub fn invocation(&self) -> Option<(&'a str, Option<&'a str>)> {
+        if self.name.eq_ignore_ascii_case("#invoke") {
+            let module = self.first_argument?.trim();
+            let function = self.arguments.first().map(|f| f.trim());
+            Some((module, function))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_parser_function, split_name, ParserFunction};
+    use dump_parser::wiktionary_configuration;
+
+    #[test]
+    fn parser_function_names() {
+        assert_eq!(
+            split_name(" #invoke: links "),
+            Some(("#invoke", Some(" links")))
+        );
+        assert_eq!(split_name("#if:"), Some(("#if", Some(""))));
+        assert_eq!(split_name("LC:Abc"), Some(("LC", Some("Abc"))));
+        assert_eq!(split_name("PAGENAME"), Some(("PAGENAME", None)));
+        assert_eq!(split_name("!"), Some(("!", None)));
+        assert!(!is_parser_function("pagename"));
+        assert!(!is_parser_function("en-noun"));
+        assert!(!is_parser_function("Template:l"));
+        assert!(!is_parser_function("w:Cat"));
+    }
+
+    #[test]
+    fn invocation() {
+        let wikitext = "{{#invoke:links| l_term_t |en}}";
+        let output = wiktionary_configuration().parse(wikitext);
+        let function =
+            ParserFunction::from_node(wikitext, &output.nodes[0]).unwrap();
+        assert_eq!(function.arguments.len(), 2);
+        assert!(!function.arguments[0].starts_with('|'));
+        assert_eq!(function.invocation(), Some(("links", Some("l_term_t"))));
+    }
+}
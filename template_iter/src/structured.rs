@@ -0,0 +1,235 @@
+//! Templates whose parameter values are parsed into text, nested templates,
+//! links and comments, so that they can be queried without a wikitext parser.
+use dump_parser::{Node, Parameter, Positioned};
+use parse_wiki_text_ext::{
+    template_parameters,
+    visit::{walk, Context, Visit, Visitor},
+};
+use serde::Serialize;
+use std::{borrow::Cow, collections::BTreeMap};
+
+use crate::parameter_key;
+
+/// A part of a parameter value.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Segment<'a> {
+    /// Wikitext without templates, links or comments.
+    Text {
+        text: &'a str,
+    },
+    Template(StructuredTemplate<'a>),
+    Link {
+        target: &'a str,
+        text: Vec<Segment<'a>>,
+    },
+    /// The text between `<!--` and `-->`.
+    Comment {
+        text: &'a str,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StructuredTemplate<'a> {
+    pub name: Cow<'a, str>,
+    pub parameters: BTreeMap<Cow<'a, str>, Vec<Segment<'a>>>,
+}
+
+impl<'a> StructuredTemplate<'a> {
+    pub fn new(
+        wikitext: &'a str,
+        name: &'a [Node<'a>],
+        parameters: &'a [Parameter<'a>],
+    ) -> Self {
+        let name = Cow::Borrowed(name.get_text_from(wikitext));
+        let parameters = template_parameters::enumerate(parameters)
+            .map(|(key, value)| {
+                (parameter_key(wikitext, key), segments(wikitext, value))
+            })
+            .collect();
+        Self { name, parameters }
+    }
+
+    pub fn from_node(
+        wikitext: &'a str,
+        template: &'a Node<'a>,
+    ) -> Result<Self, &'static str> {
+        if let Node::Template {
+            name, parameters, ..
+        } = template
+        {
+            Ok(StructuredTemplate::new(wikitext, name, parameters))
+        } else {
+            Err("not a template")
+        }
+    }
+}
+
+// Collects the segments of a parameter value. The text segments are the
+// wikitext between the templates, links and comments, which may be nested in
+// tags, lists, tables and other nodes.
+struct SegmentFinder<'a> {
+    wikitext: &'a str,
+    segments: Vec<Segment<'a>>,
+    // Where the text after the last template, link or comment starts.
+    text_start: usize,
+}
+
+impl<'a> SegmentFinder<'a> {
+    fn push_text(&mut self, end: usize) {
+        if self.text_start < end {
+            self.segments.push(Segment::Text {
+                text: &self.wikitext[self.text_start..end],
+            });
+        }
+    }
+
+    fn push(&mut self, node: &'a Node<'a>, segment: Segment<'a>) -> Visit {
+        self.push_text(node.start());
+        self.segments.push(segment);
+        self.text_start = node.end();
+        Visit::SkipChildren
+    }
+}
+
+impl<'a> Visitor<'a> for SegmentFinder<'a> {
+    fn visit_comment(
+        &mut self,
+        node: &'a Node<'a>,
+        _context: &Context<'a>,
+    ) -> Visit {
+        let comment = node.get_text_from(self.wikitext);
+        let comment = comment.strip_prefix("<!--").unwrap_or(comment);
+        let text = comment.strip_suffix("-->").unwrap_or(comment);
+        self.push(node, Segment::Comment { text })
+    }
+
+    fn visit_link(
+        &mut self,
+        node: &'a Node<'a>,
+        target: &'a str,
+        text: &'a [Node<'a>],
+        _context: &Context<'a>,
+    ) -> Visit {
+        let text = segments(self.wikitext, text);
+        self.push(node, Segment::Link { target, text })
+    }
+
+    fn visit_template(
+        &mut self,
+        node: &'a Node<'a>,
+        name: &'a [Node<'a>],
+        parameters: &'a [Parameter<'a>],
+        _context: &Context<'a>,
+    ) -> Visit {
+        let template = StructuredTemplate::new(self.wikitext, name, parameters);
+        self.push(node, Segment::Template(template))
+    }
+}
+
+/// Split nodes into segments. Templates, links and comments are found in the
+/// other nodes too, such as tags, lists and tables, and the wikitext between
+/// them is joined into text segments.
+pub fn segments<'a>(
+    wikitext: &'a str,
+    nodes: &'a [Node<'a>],
+) -> Vec<Segment<'a>> {
+    let (start, end) = match (nodes.first(), nodes.last()) {
+        (Some(first), Some(last)) => (first.start(), last.end()),
+        _ => return Vec::new(),
+    };
+    let mut finder = SegmentFinder {
+        wikitext,
+        segments: Vec::new(),
+        text_start: start,
+    };
+    walk(wikitext, nodes, &mut finder);
+    finder.push_text(end);
+    finder.segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Segment, StructuredTemplate};
+    use dump_parser::wiktionary_configuration;
+
+    #[test]
+    fn nested_template() {
+        let wikitext =
+            "{{der|en|la|{{m|la|[[verbum|word]]}}<!-- c -->|t=a ''b''}}";
+        let output = wiktionary_configuration().parse(wikitext);
+        let template =
+            StructuredTemplate::from_node(wikitext, &output.nodes[0]).unwrap();
+        use Segment::*;
+        let mention = StructuredTemplate {
+            name: "m".into(),
+            parameters: vec![
+                ("1".into(), vec![Text { text: "la" }]),
+                (
+                    "2".into(),
+                    vec![Link {
+                        target: "verbum",
+                        text: vec![Text { text: "word" }],
+                    }],
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        assert_eq!(
+            template,
+            StructuredTemplate {
+                name: "der".into(),
+                parameters: vec![
+                    ("1".into(), vec![Text { text: "en" }]),
+                    ("2".into(), vec![Text { text: "la" }]),
+                    (
+                        "3".into(),
+                        vec![Template(mention), Comment { text: " c " }]
+                    ),
+                    ("t".into(), vec![Text { text: "a ''b''" }]),
+                ]
+                .into_iter()
+                .collect(),
+            }
+        );
+    }
+
+    #[test]
+    fn nested_in_other_nodes() {
+        let wikitext =
+            "{{der|en|la|<span>{{m|la|x}}</span>|<ref>[[a|b]] c</ref>}}";
+        let output = wiktionary_configuration().parse(wikitext);
+        let template =
+            StructuredTemplate::from_node(wikitext, &output.nodes[0]).unwrap();
+        use Segment::*;
+        let mention = StructuredTemplate {
+            name: "m".into(),
+            parameters: vec![
+                ("1".into(), vec![Text { text: "la" }]),
+                ("2".into(), vec![Text { text: "x" }]),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        assert_eq!(
+            template.parameters["3"],
+            vec![
+                Text { text: "<span>" },
+                Template(mention),
+                Text { text: "</span>" },
+            ]
+        );
+        assert_eq!(
+            template.parameters["4"],
+            vec![
+                Text { text: "<ref>" },
+                Link {
+                    target: "a",
+                    text: vec![Text { text: "b" }],
+                },
+                Text { text: " c</ref>" },
+            ]
+        );
+    }
+}
@@ -0,0 +1,645 @@
+//! Title normalization following the rules of MediaWiki's `Title.php` and
+//! `MediaWikiTitleCodec::splitTitleString`.
+use dump_parser::{Case, Namespace, Namespaces};
+use std::{borrow::Cow, error::Error, fmt::Display};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::html_entities::HTML_ENTITIES;
+
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TitleNormalizationError {
+    TooLong,
+    IllegalChar,
+    /// The title is empty, or contains only a namespace prefix or fragment.
+    Empty,
+    /// The title contains U+FFFD, usually from an invalid character reference.
+    InvalidUtf8,
+    /// The title is or contains a path segment `.` or `..`.
+    RelativePath,
+    /// The title contains `~~~`, which would be expanded to a signature.
+    MagicTilde,
+    /// The title has a colon directly after the namespace prefix.
+    LeadingColon,
+    /// The title has a namespace prefix after the `Talk:` prefix.
+    TalkNamespacePrefix,
+}
+
+impl Error for TitleNormalizationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+impl Display for TitleNormalizationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use TitleNormalizationError::*;
+        match self {
+            TooLong => write!(f, "title too long"),
+            IllegalChar => write!(f, "title contains illegal character"),
+            Empty => write!(f, "title is empty"),
+            InvalidUtf8 => write!(f, "title contains invalid character"),
+            RelativePath => write!(f, "title is a relative path"),
+            MagicTilde => write!(f, "title contains ~~~"),
+            LeadingColon => {
+                write!(f, "title has a colon after the namespace prefix")
+            }
+            TalkNamespacePrefix => {
+                write!(f, "title has a namespace prefix after Talk:")
+            }
+        }
+    }
+}
+
+pub const TITLE_MAX: usize = 255;
+// Special pages can have longer titles.
+const SPECIAL_TITLE_MAX: usize = 512;
+
+// Prefixes of template names that change how a template is transcluded
+// but are not part of the title. Each group is tried once, in order.
+const TEMPLATE_MODIFIERS: &[&[&str]] =
+    &[&["subst:", "safesubst:"], &["msgnw:", "msg:"], &["raw:"]];
+
+/// A title with its namespace and the rest of the title normalized:
+/// character references decoded, whitespace collapsed to underscores
+/// and the first letter uppercased if the namespace requires it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Title {
+    namespace: Namespace,
+    dbkey: String,
+}
+
+impl Title {
+    /// Parse a title. A namespace prefix recognized by `namespaces` overrides
+    /// `default_namespace`, and a leading colon selects the main namespace.
+    /// The fragment after `#` is dropped.
+    pub fn new(
+        text: &str,
+        default_namespace: Namespace,
+        namespaces: &Namespaces,
+    ) -> Result<Self, TitleNormalizationError> {
+        let (namespace, dbkey) =
+            split_title(text, default_namespace, Some(namespaces))?;
+        Ok(Title { namespace, dbkey })
+    }
+
+    /// A title that MediaWiki has already normalized, such as `page_title`
+    /// in the SQL dumps. The dbkey is not checked.
+    pub fn from_dbkey<S: Into<String>>(namespace: Namespace, dbkey: S) -> Self {
+        Title {
+            namespace,
+            dbkey: dbkey.into(),
+        }
+    }
+
+    /// Parse the name of a transcluded template, as in `{{name|...}}`.
+    /// `subst:`, `safesubst:`, `msg:`, `msgnw:` and `raw:` are removed
+    /// and the default namespace is `Template`.
+    pub fn from_template_name(
+        name: &str,
+        namespaces: &Namespaces,
+    ) -> Result<Self, TitleNormalizationError> {
+        let mut name = name.trim_matches(is_php_whitespace);
+        for modifiers in TEMPLATE_MODIFIERS {
+            if let Some(modifier) = modifiers.iter().find(|modifier| {
+                name.get(..modifier.len())
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(modifier))
+            }) {
+                name = &name[modifier.len()..];
+            }
+        }
+        Self::new(name, Namespace::TEMPLATE, namespaces)
+    }
+
+    pub fn namespace(&self) -> Namespace {
+        self.namespace
+    }
+
+    /// The title without the namespace prefix, with underscores for spaces.
+    pub fn dbkey(&self) -> &str {
+        &self.dbkey
+    }
+
+    /// The title without the namespace prefix, with spaces.
+    pub fn text(&self) -> String {
+        self.dbkey.replace('_', " ")
+    }
+
+    /// The title with the local name of its namespace as a prefix,
+    /// with spaces, as titles appear in dumps.
+    pub fn prefixed_text(&self, namespaces: &Namespaces) -> String {
+        match namespaces.name(self.namespace) {
+            Some(name) if !name.is_empty() => {
+                format!("{}:{}", name, self.text())
+            }
+            Some(_) => self.text(),
+            None => format!("{}:{}", self.namespace, self.text()),
+        }
+    }
+}
+
+/// Normalize a title without recognizing namespace prefixes or changing the
+/// case of the first letter.
+pub fn normalize_title(name: &str) -> Result<String, TitleNormalizationError> {
+    split_title(name, Namespace::MAIN, None).map(|(_, dbkey)| dbkey)
+}
+
+fn split_title(
+    text: &str,
+    default_namespace: Namespace,
+    namespaces: Option<&Namespaces>,
+) -> Result<(Namespace, String), TitleNormalizationError> {
+    use TitleNormalizationError::*;
+
+    let text: String = decode_char_references(text).nfc().collect();
+    let dbkey = collapse_whitespace(&text);
+    if dbkey.contains('\u{FFFD}') {
+        return Err(InvalidUtf8);
+    }
+    if dbkey.is_empty() {
+        return Err(Empty);
+    }
+
+    let mut namespace = default_namespace;
+    let mut dbkey = dbkey.as_str();
+    if let Some(rest) = dbkey.strip_prefix(':') {
+        namespace = Namespace::MAIN;
+        dbkey = rest.trim_matches('_');
+    }
+    if let Some(namespaces) = namespaces {
+        if let Some((prefix_namespace, rest)) =
+            split_namespace_prefix(dbkey, namespaces)
+        {
+            if prefix_namespace == Namespace::TALK
+                && split_namespace_prefix(rest, namespaces).is_some()
+            {
+                return Err(TalkNamespacePrefix);
+            }
+            namespace = prefix_namespace;
+            dbkey = rest;
+        }
+    }
+
+    if let Some(hash) = dbkey.find('#') {
+        dbkey = dbkey[..hash].trim_end_matches('_');
+    }
+    if has_illegal_chars(dbkey) {
+        return Err(IllegalChar);
+    }
+    if is_relative_path(dbkey) {
+        return Err(RelativePath);
+    }
+    if dbkey.contains("~~~") {
+        return Err(MagicTilde);
+    }
+    let max = if namespace == Namespace::SPECIAL {
+        SPECIAL_TITLE_MAX
+    } else {
+        TITLE_MAX
+    };
+    if dbkey.len() > max {
+        return Err(TooLong);
+    }
+    if dbkey.is_empty() {
+        return Err(Empty);
+    }
+    if dbkey.starts_with(':') {
+        return Err(LeadingColon);
+    }
+
+    let capitalize = namespaces.is_some_and(|namespaces| {
+        namespaces
+            .get(namespace)
+            .map_or(Case::default(), |info| info.case)
+            == Case::FirstLetter
+    });
+    let dbkey = if capitalize {
+        uppercase_first(dbkey)
+    } else {
+        dbkey.to_string()
+    };
+    Ok((namespace, dbkey))
+}
+
+// Characters that PHP's `trim` removes by default.
+fn is_php_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\0' | '\u{0B}')
+}
+
+#[rustfmt::skip]
+fn is_title_whitespace(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '_'
+            | '\u{A0}'
+            | '\u{1680}'
+            | '\u{180E}'
+            | '\u{2000}'..='\u{200A}'
+            | '\u{2028}'
+            | '\u{2029}'
+            | '\u{202F}'
+            | '\u{205F}'
+            | '\u{3000}'
+    )
+}
+
+// Left-to-right and right-to-left marks and embedding and override characters,
+// which are invisible and often pasted in by accident.
+fn is_bidi_mark(c: char) -> bool {
+    matches!(c, '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}')
+}
+
+// Remove bidi marks, replace each sequence of whitespace with an underscore
+// and trim underscores on either end.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut pending_whitespace = false;
+    for c in text.chars().filter(|&c| !is_bidi_mark(c)) {
+        if is_title_whitespace(c) {
+            pending_whitespace = true;
+        } else {
+            if pending_whitespace && !collapsed.is_empty() {
+                collapsed.push('_');
+            }
+            pending_whitespace = false;
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+// If the title has a prefix that is a namespace name, return the namespace
+// and the rest of the title.
+fn split_namespace_prefix<'a>(
+    dbkey: &'a str,
+    namespaces: &Namespaces,
+) -> Option<(Namespace, &'a str)> {
+    let colon = dbkey.find(':')?;
+    let prefix = dbkey[..colon].trim_end_matches('_');
+    if prefix.is_empty() {
+        return None;
+    }
+    let namespace = namespaces.lookup(prefix)?;
+    Some((namespace, dbkey[colon + 1..].trim_start_matches('_')))
+}
+
+fn has_illegal_chars(dbkey: &str) -> bool {
+    let bytes = dbkey.as_bytes();
+    dbkey.char_indices().any(|(i, c)| match c {
+        '\u{00}'..='\u{1F}'
+        | '\u{7F}'
+        | '#'
+        | '<'
+        | '>'
+        | '['
+        | ']'
+        | '{'
+        | '|'
+        | '}' => true,
+        // Percent-encoding would be decoded in URLs.
+        '%' => {
+            bytes.len() > i + 2
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit()
+        }
+        // Character references that were not decoded.
+        '&' => parse_entity_name(&dbkey[i + 1..]).is_some(),
+        _ => false,
+    })
+}
+
+fn is_relative_path(dbkey: &str) -> bool {
+    dbkey == "."
+        || dbkey == ".."
+        || dbkey.starts_with("./")
+        || dbkey.starts_with("../")
+        || dbkey.contains("/./")
+        || dbkey.contains("/../")
+        || dbkey.ends_with("/.")
+        || dbkey.ends_with("/..")
+}
+
+// Uppercase the first letter, unless its uppercase form is more than one
+// character (such as "SS" for "ß"), as MediaWiki does.
+fn uppercase_first(dbkey: &str) -> String {
+    let mut chars = dbkey.chars();
+    let first = match chars.next() {
+        Some(first) => first,
+        None => return String::new(),
+    };
+    let mut upper = first.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(upper), None) => std::iter::once(upper).chain(chars).collect(),
+        _ => dbkey.to_string(),
+    }
+}
+
+// The name in `&name;`, if the text after the ampersand starts with one.
+fn parse_entity_name(text: &str) -> Option<&str> {
+    let end =
+        text.find(|c: char| c.is_ascii() && !c.is_ascii_alphanumeric())?;
+    if end > 0 && text[end..].starts_with(';') {
+        Some(&text[..end])
+    } else {
+        None
+    }
+}
+
+// Decode the number in `&#123;` or `&#x7B;`. Returns the length of the
+// reference after the ampersand, and U+FFFD for numbers that are not valid
+// characters in HTML.
+fn parse_numeric_reference(text: &str) -> Option<(char, usize)> {
+    let rest = text.strip_prefix('#')?;
+    let (digits, radix, prefix_len) = match rest.strip_prefix(&['x', 'X'][..]) {
+        Some(hex) => (hex, 16, 2),
+        None => (rest, 10, 1),
+    };
+    let end = digits
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(digits.len());
+    if end == 0 || !digits[end..].starts_with(';') {
+        return None;
+    }
+    let c = u32::from_str_radix(&digits[..end], radix)
+        .ok()
+        .filter(|&code_point| {
+            matches!(
+                code_point,
+                0x09 | 0x0A
+                    | 0x0D
+                    | 0x20..=0xD7FF
+                    | 0xE000..=0xFFFD
+                    | 0x10000..=0x10FFFF
+            )
+        })
+        .and_then(std::char::from_u32)
+        .unwrap_or('\u{FFFD}');
+    Some((c, prefix_len + end + 1))
+}
+
+/// Decode numeric character references and the named references from
+/// HTML 4. References to unknown names are left alone.
+pub fn decode_char_references(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(ampersand) = rest.find('&') {
+        decoded.push_str(&rest[..ampersand]);
+        rest = &rest[ampersand + 1..];
+        let reference = parse_numeric_reference(rest).or_else(|| {
+            let name = parse_entity_name(rest)?;
+            let i = HTML_ENTITIES
+                .binary_search_by(|(entity, _)| (*entity).cmp(name))
+                .ok()?;
+            Some((HTML_ENTITIES[i].1, name.len() + 1))
+        });
+        match reference {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => decoded.push('&'),
+        }
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        normalize_title, Title, TitleNormalizationError::*, TITLE_MAX,
+    };
+    use dump_parser::{
+        site_config::SiteConfig, Case, Namespace, NamespaceInfo, Namespaces,
+    };
+    use std::iter;
+
+    #[test]
+    fn test_normalize_title() {
+        fn rep<T: Clone>(c: T, n: usize) -> iter::RepeatN<T> {
+            iter::repeat_n(c, n)
+        }
+
+        for (name, normalized) in &[
+            (
+                rep('_', TITLE_MAX)
+                    .chain(iter::once('l').chain(rep(' ', TITLE_MAX)))
+                    .collect(),
+                Ok("l".to_string()),
+            ),
+            (
+                rep("_", TITLE_MAX)
+                    .chain(iter::once("auto").chain(rep(" ", TITLE_MAX)))
+                    .chain(iter::once("cat").chain(rep(" ", TITLE_MAX)))
+                    .collect(),
+                Ok("auto_cat".to_string()),
+            ),
+            (
+                rep('a', TITLE_MAX).collect(),
+                Ok(rep('a', TITLE_MAX).collect()),
+            ),
+            (
+                rep('a', TITLE_MAX).chain(iter::once(' ')).collect(),
+                Ok(rep('a', TITLE_MAX).collect()),
+            ),
+            (rep('a', TITLE_MAX + 1).collect(), Err(TooLong)),
+            ("\u{0}".to_string(), Err(IllegalChar)),
+            ("\u{1F}".to_string(), Err(IllegalChar)),
+        ] {
+            assert_eq!(&normalize_title(name), normalized);
+        }
+    }
+
+    // The namespaces as the command-line tools have them: those in the
+    // siteinfo of the dump, with the built-in namespace aliases.
+    fn namespaces() -> Namespaces {
+        let mut namespaces = Namespaces::canonical();
+        namespaces.insert(NamespaceInfo::new(
+            Namespace::PROJECT,
+            "Wiktionary",
+            Case::FirstLetter,
+        ));
+        namespaces.insert(NamespaceInfo::new(
+            Namespace(118),
+            "Reconstruction",
+            Case::FirstLetter,
+        ));
+        namespaces.insert(NamespaceInfo::new(
+            Namespace(1000),
+            "Lowercase",
+            Case::CaseSensitive,
+        ));
+        SiteConfig::wiktionary().add_namespace_aliases(&mut namespaces);
+        namespaces
+    }
+
+    #[test]
+    fn title() {
+        let namespaces = namespaces();
+        let long = "a".repeat(TITLE_MAX);
+        let too_long = "a".repeat(TITLE_MAX + 1);
+        let long_special = format!("Special:{}", too_long);
+        let ok = |ns: i32, dbkey: &str| Ok((Namespace(ns), dbkey.to_string()));
+        #[rustfmt::skip]
+        let cases = vec![
+            // Namespaces
+            ("Foo", ok(0, "Foo")),
+            ("Template:foo", ok(10, "Foo")),
+            ("template:foo", ok(10, "Foo")),
+            ("Template : foo", ok(10, "Foo")),
+            ("Template__:__foo", ok(10, "Foo")),
+            ("T:foo", ok(10, "Foo")),
+            ("WT:About", ok(4, "About")),
+            ("Project:About", ok(4, "About")),
+            ("RC:Proto-Indo-European/méh₂tēr",
+                ok(118, "Proto-Indo-European/méh₂tēr")),
+            ("Image:a.png", ok(6, "A.png")),
+            ("Reconstruction:Proto-Indo-European/méh₂tēr",
+                ok(118, "Proto-Indo-European/méh₂tēr")),
+            ("w:Dictionary", ok(0, "W:Dictionary")),
+            (":Foo", ok(0, "Foo")),
+            (": Template:foo", ok(10, "Foo")),
+            ("Talk:foo", ok(1, "Foo")),
+            ("Talk:Help:foo", Err(TalkNamespacePrefix)),
+            ("Talk:w:foo", ok(1, "W:foo")),
+            ("Template::foo", Err(LeadingColon)),
+            ("Template:", Err(Empty)),
+            (":", Err(Empty)),
+            // Case
+            ("lowercase:foo", ok(1000, "foo")),
+            ("ßa", ok(0, "ßa")),
+            ("ǆ", ok(0, "Ǆ")),
+            ("ﬁ", ok(0, "ﬁ")),
+            ("1a", ok(0, "1a")),
+            // Whitespace and bidi marks
+            ("  a  b  ", ok(0, "A_b")),
+            ("a\u{A0}\u{2003}\u{3000}b", ok(0, "A_b")),
+            ("\u{200E}a\u{200F}b\u{202A}", ok(0, "Ab")),
+            ("a\tb", Err(IllegalChar)),
+            ("a\nb", Err(IllegalChar)),
+            // Character references
+            ("a&#32;b", ok(0, "A_b")),
+            ("a&#x20;b", ok(0, "A_b")),
+            ("&amp;", ok(0, "&")),
+            ("&eacute;t&eacute;", ok(0, "Été")),
+            ("a&nbsp;b", ok(0, "A_b")),
+            ("&#0;", Err(InvalidUtf8)),
+            ("&#xD800;", Err(InvalidUtf8)),
+            ("&#x123;", ok(0, "Ģ")),
+            ("&foo;", Err(IllegalChar)),
+            ("a&b", ok(0, "A&b")),
+            ("a & b;", ok(0, "A_&_b;")),
+            ("&#x7B;", Err(IllegalChar)),
+            ("\u{FFFD}", Err(InvalidUtf8)),
+            // Unicode normalization
+            ("e\u{301}", ok(0, "É")),
+            // Fragments
+            ("a#b", ok(0, "A")),
+            ("a #b", ok(0, "A")),
+            ("#b", Err(Empty)),
+            ("Template:#b", Err(Empty)),
+            // Illegal characters
+            ("a<b", Err(IllegalChar)),
+            ("a>b", Err(IllegalChar)),
+            ("a[b", Err(IllegalChar)),
+            ("a]b", Err(IllegalChar)),
+            ("a{b", Err(IllegalChar)),
+            ("a}b", Err(IllegalChar)),
+            ("a|b", Err(IllegalChar)),
+            ("a\u{7F}b", Err(IllegalChar)),
+            ("a%20b", Err(IllegalChar)),
+            ("100%", ok(0, "100%")),
+            ("a%2", ok(0, "A%2")),
+            ("a%zz", ok(0, "A%zz")),
+            ("\"a\"?!*=@$^`~\\", ok(0, "\"a\"?!*=@$^`~\\")),
+            // Relative paths
+            (".", Err(RelativePath)),
+            ("..", Err(RelativePath)),
+            ("./a", Err(RelativePath)),
+            ("../a", Err(RelativePath)),
+            ("a/./b", Err(RelativePath)),
+            ("a/../b", Err(RelativePath)),
+            ("a/.", Err(RelativePath)),
+            ("a/..", Err(RelativePath)),
+            ("...", ok(0, "...")),
+            ("a/.b", ok(0, "A/.b")),
+            ("a..b", ok(0, "A..b")),
+            // Tildes
+            ("a~~~", Err(MagicTilde)),
+            ("a~~b", ok(0, "A~~b")),
+            // Length
+            (long.as_str(), Ok((Namespace::MAIN, format!("A{}", &long[1..])))),
+            (too_long.as_str(), Err(TooLong)),
+            (long_special.as_str(),
+                Ok((Namespace::SPECIAL, format!("A{}", &too_long[1..])))),
+            ("", Err(Empty)),
+            (" _ ", Err(Empty)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(
+                Title::new(text, Namespace::MAIN, &namespaces).map(|title| (
+                    title.namespace(),
+                    title.dbkey().to_string()
+                )),
+                expected,
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn template_name() {
+        let namespaces = namespaces();
+        let ok = |ns: i32, dbkey: &str| Ok((Namespace(ns), dbkey.to_string()));
+        #[rustfmt::skip]
+        let cases = vec![
+            ("en-noun", ok(10, "En-noun")),
+            ("\n en-noun \n", ok(10, "En-noun")),
+            ("Template:en-noun", ok(10, "En-noun")),
+            ("T:en-noun", ok(10, "En-noun")),
+            ("t:en-noun", ok(10, "En-noun")),
+            ("MOD:links", ok(828, "Links")),
+            (":en", ok(0, "En")),
+            ("WT:About", ok(4, "About")),
+            ("subst:en-noun", ok(10, "En-noun")),
+            ("SUBST:en-noun", ok(10, "En-noun")),
+            ("safesubst:en-noun", ok(10, "En-noun")),
+            ("subst: en-noun", ok(10, "En-noun")),
+            ("subst:safesubst:en-noun", ok(10, "Safesubst:en-noun")),
+            ("msg:en-noun", ok(10, "En-noun")),
+            ("msgnw:en-noun", ok(10, "En-noun")),
+            ("raw:en-noun", ok(10, "En-noun")),
+            ("subst:msgnw:raw:en-noun", ok(10, "En-noun")),
+            ("subst:", Err(Empty)),
+            ("en&#32;noun", ok(10, "En_noun")),
+            ("en-noun#a", ok(10, "En-noun")),
+            ("{{{1}}}", Err(IllegalChar)),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(
+                Title::from_template_name(name, &namespaces).map(|title| (
+                    title.namespace(),
+                    title.dbkey().to_string()
+                )),
+                expected,
+                "{:?}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn prefixed_text() {
+        let namespaces = namespaces();
+        let title =
+            Title::new("T:en_noun", Namespace::MAIN, &namespaces).unwrap();
+        assert_eq!(title.text(), "En noun");
+        assert_eq!(title.prefixed_text(&namespaces), "Template:En noun");
+        let title = Title::new("a_b", Namespace::MAIN, &namespaces).unwrap();
+        assert_eq!(title.prefixed_text(&namespaces), "A b");
+    }
+}
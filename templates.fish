@@ -23,21 +23,18 @@ if not begin; set -q CBOR_DIR; and set -q DUMP_DIR; end
 end
 set -l dump_prefix $DUMP_DIR/$dump_date-
 
-set -l template_redirects_bin template_redirects
-set -l template_redirects_json template_redirects.json
-if which $template_redirects_bin > /dev/null
-	echo "Generating $template_redirects_json"
-	$template_redirects_bin {$dump_prefix}page.sql {$dump_prefix}redirect.sql > $template_redirects_json
-else if not test -f $template_redirects_json
-	echo "$template_redirects_bin binary (from parse-wiki-text) not found on \$PATH" \
-		"and $template_redirects_json not found"
-	exit 1
-end
-
 set -l template_names template_names.txt
 set -l template_names_cbor (readlink -f template_names_cbor.txt)
-cat $template_names | lua lua/add_template_redirects.lua "%s.cbor" $template_redirects_json \
+echo "Adding template redirects to $template_names_cbor"
+if not wiktionary-data add-template-redirects \
+	--templates $template_names \
+	--format cbor \
+	--page-sql {$dump_prefix}page.sql \
+	--redirect-sql {$dump_prefix}redirect.sql \
+	--input $DUMP_DIR/$dump_date-pages-articles.xml \
 	> $template_names_cbor
+	echo "Failed to add template redirects"; exit 1
+end
 
 set -l orig_dir $PWD
 set -l dated_cbor_dir $CBOR_DIR/$dump_date